@@ -0,0 +1,232 @@
+//! Derive macros for actix-remote
+//!
+//! ```rust,ignore
+//! #[derive(Message, Serialize, Deserialize, RemoteMessage)]
+//! #[remote(id="reports.Generate", version=2)]
+//! struct Generate {
+//!     #[remote(routing_key)]
+//!     account: String,
+//! }
+//! ```
+//!
+//! Type id defaults to module path and type name, version defaults to `1`.
+//! Fingerprint is a hash of type name, field names and field types,
+//! types are compared by last path segment, so `String` and
+//! `std::string::String` have same fingerprint.
+extern crate proc_macro;
+extern crate syn;
+#[macro_use] extern crate quote;
+
+use proc_macro::TokenStream;
+use syn::{Data, DeriveInput, Fields, GenericArgument, Index, Lit, Meta, NestedMeta,
+          PathArguments, Type};
+
+const ATTR: &str = "remote";
+
+
+#[proc_macro_derive(RemoteMessage, attributes(remote))]
+pub fn remote_message(input: TokenStream) -> TokenStream {
+    let ast: DeriveInput = syn::parse(input).unwrap();
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    let mut id = None;
+    let mut version = 1u64;
+    for meta in remote_attrs(&ast.attrs) {
+        match meta {
+            Meta::NameValue(ref nv) if nv.ident == "id" => match nv.lit {
+                Lit::Str(ref s) => id = Some(s.value()),
+                _ => panic!("#[remote(id=\"...\")] expects string literal"),
+            },
+            Meta::NameValue(ref nv) if nv.ident == "version" => match nv.lit {
+                Lit::Int(ref v) => version = v.value(),
+                Lit::Str(ref s) => version = s.value().parse()
+                    .expect("#[remote(version=...)] expects integer"),
+                _ => panic!("#[remote(version=...)] expects integer"),
+            },
+            _ => panic!("Unknown remote attribute, expected id or version"),
+        }
+    }
+    if version > u64::from(u32::MAX) {
+        panic!("#[remote(version=...)] is too large");
+    }
+    let version = version as u32;
+
+    let type_id = match id {
+        Some(id) => quote!{ #id },
+        None => {
+            let type_name = name.as_ref();
+            quote!{ concat!(module_path!(), "::", #type_name) }
+        }
+    };
+
+    let fingerprint = fingerprint(&ast);
+
+    let routing_key = match routing_key(&ast.data) {
+        Some(field) => quote!{
+            fn routing_key(&self) -> Option<String> {
+                Some(::std::string::ToString::to_string(&self.#field))
+            }
+        },
+        None => quote!{},
+    };
+
+    let expanded = quote!{
+        impl #impl_generics ::actix_remote::RemoteMessage for #name #ty_generics #where_clause {
+            fn type_id() -> &'static str {
+                #type_id
+            }
+
+            fn version() -> u32 {
+                #version
+            }
+
+            fn fingerprint() -> u64 {
+                #fingerprint
+            }
+
+            #routing_key
+        }
+    };
+    expanded.into()
+}
+
+/// Content of `#[remote(...)]` attributes
+fn remote_attrs(attrs: &[syn::Attribute]) -> Vec<Meta> {
+    let mut result = Vec::new();
+    for attr in attrs {
+        if let Some(Meta::List(list)) = attr.interpret_meta() {
+            if list.ident != ATTR {
+                continue
+            }
+            for nested in list.nested {
+                match nested {
+                    NestedMeta::Meta(meta) => result.push(meta),
+                    _ => panic!("Unsupported remote attribute"),
+                }
+            }
+        }
+    }
+    result
+}
+
+/// FNV-1a hash of the type schema
+fn fingerprint(ast: &DeriveInput) -> u64 {
+    let mut schema = ast.ident.as_ref().to_owned();
+    match ast.data {
+        Data::Struct(ref s) => schema.push_str(&fields_schema(&s.fields)),
+        Data::Enum(ref e) => for variant in &e.variants {
+            schema.push_str(&format!("|{}{}", variant.ident.as_ref(),
+                                     fields_schema(&variant.fields)));
+        },
+        Data::Union(_) => panic!("RemoteMessage can not be derived for union"),
+    }
+
+    hash(&schema)
+}
+
+/// FNV-1a hash, same as `actix_remote::fingerprint()`
+fn hash(schema: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in schema.as_bytes() {
+        hash ^= u64::from(*b);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    // zero means unknown fingerprint
+    if hash == 0 { 1 } else { hash }
+}
+
+fn fields_schema(fields: &Fields) -> String {
+    let fields: Vec<_> = match *fields {
+        Fields::Named(ref f) => f.named.iter().collect(),
+        Fields::Unnamed(ref f) => f.unnamed.iter().collect(),
+        Fields::Unit => Vec::new(),
+    };
+    let fields: Vec<String> = fields.into_iter().map(|field| {
+        let ty = &field.ty;
+        match field.ident {
+            Some(ref ident) => format!("{}:{}", ident.as_ref(), type_schema(ty)),
+            None => type_schema(ty),
+        }
+    }).collect();
+    format!("({})", fields.join(","))
+}
+
+/// Normalized type description, independent of paths and formatting
+fn type_schema(ty: &Type) -> String {
+    match *ty {
+        Type::Path(ref p) => match p.path.segments.iter().last() {
+            Some(segment) => {
+                let mut schema = segment.ident.as_ref().to_owned();
+                match segment.arguments {
+                    PathArguments::None => (),
+                    PathArguments::AngleBracketed(ref args) => {
+                        let args: Vec<String> = args.args.iter().filter_map(|arg| match *arg {
+                            GenericArgument::Type(ref ty) => Some(type_schema(ty)),
+                            GenericArgument::Lifetime(_) => None,
+                            ref arg => Some(tokens_schema(quote!{ #arg })),
+                        }).collect();
+                        if !args.is_empty() {
+                            schema.push_str(&format!("<{}>", args.join(",")));
+                        }
+                    },
+                    ref args => schema.push_str(&tokens_schema(quote!{ #args })),
+                }
+                schema
+            },
+            None => tokens_schema(quote!{ #ty }),
+        },
+        // references serialize as referenced type
+        Type::Reference(ref r) => type_schema(&r.elem),
+        Type::Paren(ref p) => type_schema(&p.elem),
+        Type::Group(ref g) => type_schema(&g.elem),
+        Type::Slice(ref s) => format!("[{}]", type_schema(&s.elem)),
+        Type::Array(ref a) => {
+            let len = &a.len;
+            format!("[{};{}]", type_schema(&a.elem), tokens_schema(quote!{ #len }))
+        },
+        Type::Tuple(ref t) => {
+            let elems: Vec<String> = t.elems.iter().map(type_schema).collect();
+            format!("({})", elems.join(","))
+        },
+        ref ty => tokens_schema(quote!{ #ty }),
+    }
+}
+
+/// Token text without whitespace
+fn tokens_schema(tokens: quote::Tokens) -> String {
+    tokens.to_string().chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// Field marked with `#[remote(routing_key)]`
+fn routing_key(data: &Data) -> Option<quote::Tokens> {
+    let fields = match *data {
+        Data::Struct(ref s) => match s.fields {
+            Fields::Named(ref f) => f.named.iter().collect::<Vec<_>>(),
+            Fields::Unnamed(ref f) => f.unnamed.iter().collect::<Vec<_>>(),
+            Fields::Unit => Vec::new(),
+        },
+        _ => Vec::new(),
+    };
+
+    let mut key = None;
+    for (idx, field) in fields.into_iter().enumerate() {
+        let marked = remote_attrs(&field.attrs).iter().any(|meta| match *meta {
+            Meta::Word(ref ident) => *ident == "routing_key",
+            _ => panic!("Unknown remote field attribute, expected routing_key"),
+        });
+        if marked {
+            if key.is_some() {
+                panic!("Only one field can be marked with #[remote(routing_key)]");
+            }
+            key = Some(match field.ident {
+                Some(ref ident) => quote!{ #ident },
+                None => {
+                    let idx = Index::from(idx);
+                    quote!{ #idx }
+                }
+            });
+        }
+    }
+    key
+}
@@ -0,0 +1,110 @@
+#![allow(proc_macro_derive_resolution_fallback, non_local_definitions)]
+
+#[macro_use] extern crate actix;
+extern crate actix_remote;
+#[macro_use] extern crate actix_remote_derive;
+extern crate serde;
+#[macro_use] extern crate serde_derive;
+
+use actix_remote::RemoteMessage;
+
+mod short {
+    #[derive(Message, Serialize, Deserialize, RemoteMessage)]
+    #[remote(id="test.Fingerprint")]
+    pub struct Msg {
+        pub name: String,
+        pub tags: Vec<(String, u32)>,
+        pub data: Option<Vec<u8>>,
+    }
+}
+
+mod full {
+    #[derive(Message, Serialize, Deserialize, RemoteMessage)]
+    #[remote(id="test.Fingerprint")]
+    pub struct Msg {
+        pub name: ::std::string::String,
+        pub tags: ::std::vec::Vec<( String , u32 )>,
+        pub data: ::std::option::Option<Vec<u8>>,
+    }
+}
+
+mod changed {
+    #[derive(Message, Serialize, Deserialize, RemoteMessage)]
+    #[remote(id="test.Fingerprint")]
+    pub struct Msg {
+        pub name: String,
+        pub tags: Vec<(String, u64)>,
+        pub data: Option<Vec<u8>>,
+    }
+}
+
+#[derive(Message, Serialize, Deserialize, RemoteMessage)]
+struct Plain {
+    name: String,
+}
+
+#[derive(Message, Serialize, Deserialize, RemoteMessage)]
+#[remote(id="test.Deposit", version=3)]
+struct Deposit {
+    #[remote(routing_key)]
+    account: u64,
+    amount: u64,
+}
+
+#[derive(Message, Serialize, Deserialize, RemoteMessage)]
+#[remote(id="test.Tuple", version="2")]
+struct Tuple(u32, #[remote(routing_key)] String);
+
+mod command {
+    #[derive(Message, Serialize, Deserialize, RemoteMessage)]
+    #[remote(id="test.Command")]
+    pub enum Command {
+        Start,
+        Stop(u32),
+    }
+}
+
+mod renamed {
+    #[derive(Message, Serialize, Deserialize, RemoteMessage)]
+    #[remote(id="test.Command")]
+    pub enum Command {
+        Start,
+        Halt(u32),
+    }
+}
+
+#[test]
+fn test_defaults() {
+    assert_eq!(Plain::type_id(), "derive::Plain");
+    assert_eq!(Plain::version(), 1);
+    assert_eq!(Plain{name: "name".to_owned()}.routing_key(), None);
+}
+
+#[test]
+fn test_attributes() {
+    assert_eq!(Deposit::type_id(), "test.Deposit");
+    assert_eq!(Deposit::version(), 3);
+    assert_eq!(Tuple::type_id(), "test.Tuple");
+    assert_eq!(Tuple::version(), 2);
+}
+
+#[test]
+fn test_routing_key() {
+    let msg = Deposit{account: 42, amount: 100};
+    assert_eq!(msg.routing_key(), Some("42".to_owned()));
+    let msg = Tuple(1, "key".to_owned());
+    assert_eq!(msg.routing_key(), Some("key".to_owned()));
+}
+
+#[test]
+fn test_fingerprint_enum() {
+    assert_ne!(command::Command::fingerprint(), renamed::Command::fingerprint());
+    assert_ne!(command::Command::fingerprint(), 0);
+}
+
+#[test]
+fn test_fingerprint_paths() {
+    assert_eq!(short::Msg::fingerprint(), full::Msg::fingerprint());
+    assert_ne!(short::Msg::fingerprint(), changed::Msg::fingerprint());
+    assert_ne!(short::Msg::fingerprint(), 0);
+}
@@ -4,17 +4,18 @@
 //!
 //! first instance sends messages, second instance respondes to messages from first instance
 //!
-#![allow(dead_code, unused_variables)]
+#![allow(dead_code, unused_variables, proc_macro_derive_resolution_fallback)]
 
 extern crate log;
 extern crate env_logger;
 extern crate futures;
 #[macro_use] extern crate actix;
 extern crate actix_remote;
+#[macro_use] extern crate actix_remote_derive;
 extern crate serde_json;
 #[macro_use] extern crate serde_derive;
 extern crate structopt;
-#[macro_use] extern crate structopt_derive;
+extern crate structopt_derive;
 
 use std::time::Duration;
 
@@ -76,7 +77,7 @@ struct Cli {
 
 fn main() {
     ::std::env::set_var("RUST_LOG", "actix_remote=debug");
-    let _ = env_logger::init();
+    env_logger::init();
 
     // cmd arguments
     let args = Cli::from_args();
@@ -98,8 +99,12 @@ fn main() {
     let a: Addr<Unsync, _> = MyActor::create(move |ctx| {
         ctx.run_later(Duration::from_millis(5000), move |_, ctx| {
             // register actor as recipient for `TestMessage` message
-            World::register_recipient(
-                &addr, ctx.address::<Addr<Syn, _>>().recipient());
+            Arbiter::handle().spawn(
+                World::register_recipient(&addr, ctx.address::<Addr<Syn, _>>().recipient())
+                    .map_err(|err| {
+                        eprintln!("Can not register recipient: {}", err);
+                        Arbiter::system().do_send(actix::msgs::SystemExit(1));
+                    }));
         });
 
         MyActor{cnt: 0, hb, recipient}
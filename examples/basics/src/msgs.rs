@@ -1,13 +1,5 @@
-use actix_remote::*;
-
-
-#[derive(Debug, Message, Serialize, Deserialize)]
+#[derive(Debug, Message, Serialize, Deserialize, RemoteMessage)]
+#[remote(id="TestMessage")]
 pub struct TestMessage {
     pub msg: String,
 }
-
-impl RemoteMessage for TestMessage {
-    fn type_id() -> &'static str {
-        "TestMessage"
-    }
-}
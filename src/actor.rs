@@ -0,0 +1,348 @@
+use std::{fmt, mem};
+use std::cell::{Cell, RefCell};
+use std::sync::Arc;
+use std::marker::PhantomData;
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+use futures::{future, Future};
+use actix::prelude::*;
+
+use msgs;
+use error::{RegistrationError, RemoteError};
+use migration::Migrations;
+use node::NodeId;
+use policy::Policy;
+use recipient::{Provider, RemoteMessageHandler};
+use remote::RemoteMessage;
+use world::World;
+
+/// Check if registered actor is running
+pub(crate) type Probe = Arc<Fn() -> bool + Send + Sync>;
+
+thread_local!(static WORLD: RefCell<Option<Addr<Syn, World>>> = const { RefCell::new(None) });
+thread_local!(static ORIGIN: Cell<Option<NodeId>> = const { Cell::new(None) });
+
+
+/// Run `f` with world that receives deserialized actor references,
+/// `origin` is node that sent the data
+pub(crate) fn with_world<F, R>(world: Option<&Addr<Syn, World>>, origin: NodeId, f: F) -> R
+    where F: FnOnce() -> R
+{
+    let world = world.cloned();
+    let prev = WORLD.with(|w| mem::replace(&mut *w.borrow_mut(), world));
+    let prev_origin = ORIGIN.with(|o| o.replace(Some(origin)));
+    let res = f();
+    WORLD.with(|w| *w.borrow_mut() = prev);
+    ORIGIN.with(|o| o.set(prev_origin));
+    res
+}
+
+/// Decode message result of `origin` node, actor references are bound to `world`
+pub(crate) fn decode<T: DeserializeOwned>(world: Option<&Addr<Syn, World>>, origin: NodeId,
+                                          data: &str) -> Result<T, RemoteError>
+{
+    with_world(world, origin, || {
+        serde_json::from_str::<T>(data).map_err(|_| RemoteError::Decode)})
+}
+
+/// World of the message that is being deserialized
+fn current_world() -> Option<Addr<Syn, World>> {
+    WORLD.with(|w| w.borrow().clone())
+}
+
+/// Node that sent the message that is being deserialized
+fn current_origin() -> Option<NodeId> {
+    ORIGIN.with(|o| o.get())
+}
+
+/// Identity of actor instance registered in world
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct ActorId {
+    /// Node of the actor
+    pub node: NodeId,
+    /// Actor id, unique within node incarnation
+    pub id: u64,
+}
+
+impl fmt::Display for ActorId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.node, self.id)
+    }
+}
+
+/// Serializable reference to specific remote actor
+///
+/// Reference can be sent to other nodes as a field of remote message,
+/// on the wire it is actor identity only. Reference that is received
+/// from other node sends messages through the world of the receiver.
+/// Messages to local actor through reference received from other node
+/// are delivered with identity of that node.
+#[derive(Serialize, Deserialize)]
+#[serde(bound="")]
+pub struct RemoteAddr<A: Actor> {
+    id: ActorId,
+    #[serde(skip, default="current_world")]
+    world: Option<Addr<Syn, World>>,
+    #[serde(skip, default="current_origin")]
+    origin: Option<NodeId>,
+    #[serde(skip)]
+    a: PhantomData<A>,
+}
+
+impl<A: Actor> RemoteAddr<A> {
+    pub(crate) fn new(id: ActorId, world: Addr<Syn, World>) -> RemoteAddr<A> {
+        RemoteAddr{id: id, world: Some(world), origin: None, a: PhantomData}
+    }
+
+    /// Actor identity
+    pub fn id(&self) -> ActorId {
+        self.id
+    }
+
+    /// Reference for one message type of the actor
+    pub fn recipient<M>(&self) -> RemoteRecipient<M>
+        where A: Handler<M>,
+              M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+    {
+        RemoteRecipient{id: self.id, world: self.world.clone(), origin: self.origin,
+                        m: PhantomData}
+    }
+
+    /// Send message to the actor
+    pub fn send<M>(&self, msg: M) -> Box<Future<Item=M::Result, Error=RemoteError>>
+        where A: Handler<M>,
+              M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+    {
+        self.recipient().send(msg)
+    }
+
+    /// Send message to the actor, ignore result
+    pub fn do_send<M>(&self, msg: M)
+        where A: Handler<M>,
+              M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+    {
+        self.recipient().do_send(msg)
+    }
+
+    /// Remove actor registration, see `World::stop_actor()`
+    ///
+    /// Local actor is stopped through reference received from other
+    /// node only if that node spawned the actor.
+    pub fn stop(&self) {
+        if let Some(ref world) = self.world {
+            world.do_send(msgs::StopActor{actor: self.id, origin: self.origin})
+        }
+    }
+}
+
+impl<A: Actor> Clone for RemoteAddr<A> {
+    fn clone(&self) -> Self {
+        RemoteAddr{id: self.id, world: self.world.clone(), origin: self.origin, a: PhantomData}
+    }
+}
+
+impl<A: Actor> fmt::Debug for RemoteAddr<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RemoteAddr({})", self.id)
+    }
+}
+
+/// Serializable reference to specific remote actor, for single message type
+///
+/// Recipient can be passed inside remote message for reply-to patterns,
+/// receiver of the message uses it to call back the actor.
+///
+/// ```rust,ignore
+/// #[derive(Message, Serialize, Deserialize, RemoteMessage)]
+/// struct Subscribe {
+///     reply_to: RemoteRecipient<Notification>,
+/// }
+///
+/// let reply_to = World::register_actor_recipient(&world, addr.recipient());
+/// ```
+#[derive(Serialize, Deserialize)]
+#[serde(bound="")]
+pub struct RemoteRecipient<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    id: ActorId,
+    #[serde(skip, default="current_world")]
+    world: Option<Addr<Syn, World>>,
+    #[serde(skip, default="current_origin")]
+    origin: Option<NodeId>,
+    #[serde(skip)]
+    m: PhantomData<M>,
+}
+
+impl<M> RemoteRecipient<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    pub(crate) fn new(id: ActorId, world: Addr<Syn, World>) -> RemoteRecipient<M> {
+        RemoteRecipient{id: id, world: Some(world), origin: None, m: PhantomData}
+    }
+
+    /// Actor identity
+    pub fn id(&self) -> ActorId {
+        self.id
+    }
+
+    /// Send message to the actor
+    pub fn send(&self, msg: M) -> Box<Future<Item=M::Result, Error=RemoteError>> {
+        let world = match self.world {
+            Some(ref world) => world,
+            None => return Box::new(future::err(RemoteError::Disconnected)),
+        };
+        let data = match serde_json::to_string(&msg) {
+            Ok(data) => data,
+            Err(_) => return Box::new(future::err(RemoteError::Decode)),
+        };
+        let w = world.clone();
+        let node = self.id.node;
+        Box::new(
+            world.send(msgs::SendToActor{
+                actor: self.id, origin: self.origin, type_id: M::type_id().to_owned(),
+                version: M::version(), fingerprint: M::fingerprint(), data: data})
+                .then(move |res| match res {
+                    Ok(Ok(data)) => decode::<M::Result>(Some(&w), node, &data),
+                    Ok(Err(err)) => Err(err),
+                    Err(_) => Err(RemoteError::Disconnected),
+                }))
+    }
+
+    /// Send message to the actor, ignore result
+    pub fn do_send(&self, msg: M) {
+        Arbiter::handle().spawn(self.send(msg).then(|_| Ok::<_, ()>(())));
+    }
+}
+
+impl<M> Clone for RemoteRecipient<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    fn clone(&self) -> Self {
+        RemoteRecipient{id: self.id, world: self.world.clone(), origin: self.origin,
+                        m: PhantomData}
+    }
+}
+
+impl<M> fmt::Debug for RemoteRecipient<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RemoteRecipient({}, {})", M::type_id(), self.id)
+    }
+}
+
+/// Actor registration builder
+///
+/// ```rust,ignore
+/// let addr: Addr<Syn, Session> = Session::default().start();
+/// let remote = RemoteActor::new()
+///     .recipient::<Login>(addr.clone().recipient())
+///     .recipient::<Logout>(addr.clone().recipient())
+///     .register(&world);
+/// ```
+pub struct RemoteActor<A: Actor> {
+    handlers: HashMap<&'static str, Arc<RemoteMessageHandler>>,
+    policy: Policy,
+    alive: Option<Probe>,
+    a: PhantomData<A>,
+}
+
+impl<A: Actor> RemoteActor<A> {
+    pub fn new() -> RemoteActor<A> {
+        RemoteActor{handlers: HashMap::new(), policy: Policy::allow_all(),
+                    alive: None, a: PhantomData}
+    }
+
+    /// Actor address, registration is removed and watchers are notified
+    /// once actor stops, even if actor does not receive messages.
+    ///
+    /// Without address, stop is detected only when message can not be
+    /// delivered to actor mailbox.
+    pub fn addr(mut self, addr: Addr<Syn, A>) -> Self {
+        self.alive = Some(Arc::new(move || addr.connected()));
+        self
+    }
+
+    /// Authorization policy for messages of types registered after this call
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Accept message type, recipient is usually `addr.recipient()`
+    pub fn recipient<M>(mut self, recipient: Recipient<Syn, M>) -> Self
+        where A: Handler<M>,
+              M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+    {
+        let r = Provider{recipient: recipient, policy: self.policy.clone(),
+                         versions: (M::version(), M::version()),
+                         migrations: Arc::new(Migrations::new())};
+        self.handlers.insert(M::type_id(), Arc::new(r));
+        self
+    }
+
+    pub(crate) fn into_parts(self)
+                             -> (HashMap<&'static str, Arc<RemoteMessageHandler>>, Option<Probe>)
+    {
+        (self.handlers, self.alive)
+    }
+
+    /// Register actor in world
+    pub fn register(self, world: &Addr<Syn, World>)
+                    -> Box<Future<Item=RemoteAddr<A>, Error=RegistrationError>>
+    {
+        let addr = world.clone();
+        Box::new(
+            world.send(msgs::RegisterActor{handlers: self.handlers, alive: self.alive})
+                .then(move |res| match res {
+                    Ok(id) => Ok(RemoteAddr::new(id, addr)),
+                    Err(_) => Err(RegistrationError::Stopped),
+                }))
+    }
+}
+
+impl<A: Actor> Default for RemoteActor<A> {
+    fn default() -> Self {
+        RemoteActor::new()
+    }
+}
+
+impl World {
+    /// Register single recipient as remote actor
+    pub fn register_actor_recipient<M>(world: &Addr<Syn, World>, recipient: Recipient<Syn, M>)
+        -> Box<Future<Item=RemoteRecipient<M>, Error=RegistrationError>>
+        where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+    {
+        let mut handlers: HashMap<&'static str, Arc<RemoteMessageHandler>> = HashMap::new();
+        handlers.insert(M::type_id(), Arc::new(
+            Provider{recipient: recipient, policy: Policy::allow_all(),
+                     versions: (M::version(), M::version()),
+                     migrations: Arc::new(Migrations::new())}));
+        let addr = world.clone();
+        Box::new(
+            world.send(msgs::RegisterActor{handlers: handlers, alive: None})
+                .then(move |res| match res {
+                    Ok(id) => Ok(RemoteRecipient::new(id, addr)),
+                    Err(_) => Err(RegistrationError::Stopped),
+                }))
+    }
+
+    /// Remove registered actor, remote nodes receive actor not found error
+    pub fn unregister_actor(world: &Addr<Syn, World>, id: ActorId) {
+        world.do_send(msgs::UnregisterActor(id))
+    }
+
+    /// Remove registration of local actor or of actor spawned by this node
+    /// on remote node.
+    ///
+    /// World drops its addresses of the actor, actor stops once it has no
+    /// other addresses. Remote node accepts request from node that spawned
+    /// the actor, if node speaks protocol version 9.
+    pub fn stop_actor(world: &Addr<Syn, World>, id: ActorId) {
+        world.do_send(msgs::StopActor{actor: id, origin: None})
+    }
+}
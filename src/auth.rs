@@ -0,0 +1,159 @@
+use std::str;
+use std::sync::Arc;
+
+use byteorder::{ByteOrder, NetworkEndian};
+use hmac::{Hmac, Mac};
+use rand::{self, Rng};
+use sha2::Sha256;
+
+use node::NodeId;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Domain separation label of the answer
+const LABEL: &[u8] = b"actix-remote/auth/1";
+
+
+/// Shared secret challenge/response authentication.
+///
+/// Each side of the connection sends random challenge in handshake,
+/// peer answers with HMAC over challenge, its own node id and node id
+/// of the challenger. Answer is valid only for one direction of one pair
+/// of nodes, so it can not be reflected back to challenger or replayed
+/// on connection to other node.
+///
+/// Authentication does not protect messages after handshake, active
+/// man-in-the-middle can relay whole connection. Use TLS if network
+/// is not trusted.
+pub(crate) struct Auth {
+    secret: Arc<Vec<u8>>,
+    local: NodeId,
+    challenge: String,
+}
+
+impl Auth {
+    pub fn new(secret: Arc<Vec<u8>>, local: NodeId) -> Auth {
+        let mut buf = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut buf);
+        Auth{secret: secret, local: local, challenge: to_hex(&buf)}
+    }
+
+    /// Challenge for remote peer
+    pub fn challenge(&self) -> &str {
+        self.challenge.as_str()
+    }
+
+    /// Answer to the challenge of the remote peer
+    pub fn sign(&self, challenge: &str, peer: &NodeId) -> String {
+        let mac = self.mac(challenge, &self.local, peer);
+        to_hex(&mac.result().code())
+    }
+
+    /// Verify peer answer to our challenge
+    pub fn verify(&self, peer: &NodeId, answer: &str) -> bool {
+        if let Some(code) = from_hex(answer) {
+            self.mac(&self.challenge, peer, &self.local).verify(&code).is_ok()
+        } else {
+            false
+        }
+    }
+
+    fn mac(&self, challenge: &str, signer: &NodeId, verifier: &NodeId) -> HmacSha256 {
+        let mut mac = HmacSha256::new_varkey(&self.secret)
+            .expect("HMAC can take key of any size");
+        mac.input(LABEL);
+        input(&mut mac, challenge.as_bytes());
+        input(&mut mac, signer.to_string().as_bytes());
+        input(&mut mac, verifier.to_string().as_bytes());
+        mac
+    }
+}
+
+/// Length prefixed field, fields can not be shifted into each other
+fn input(mac: &mut HmacSha256, data: &[u8]) {
+    let mut len = [0u8; 8];
+    NetworkEndian::write_u64(&mut len, data.len() as u64);
+    mac.input(&len);
+    mac.input(data);
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None
+    }
+    s.as_bytes().chunks(2)
+        .map(|b| str::from_utf8(b).ok().and_then(|b| u8::from_str_radix(b, 16).ok()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    use node::NodeId;
+    use super::*;
+
+    fn secret() -> Arc<Vec<u8>> {
+        Arc::new(b"secret".to_vec())
+    }
+
+    fn node() -> NodeId {
+        NodeId::new(Uuid::new_v4())
+    }
+
+    #[test]
+    fn test_answer() {
+        let (a, b) = (node(), node());
+        let auth_a = Auth::new(secret(), a);
+        let auth_b = Auth::new(secret(), b);
+
+        let answer = auth_b.sign(auth_a.challenge(), &a);
+        assert!(auth_a.verify(&b, &answer));
+        // answer of b is not valid for other signer
+        assert!(!auth_a.verify(&node(), &answer));
+    }
+
+    #[test]
+    fn test_wrong_secret() {
+        let (a, b) = (node(), node());
+        let auth_a = Auth::new(secret(), a);
+        let auth_b = Auth::new(Arc::new(b"other".to_vec()), b);
+
+        let answer = auth_b.sign(auth_a.challenge(), &a);
+        assert!(!auth_a.verify(&b, &answer));
+        assert!(!auth_a.verify(&b, "not hex"));
+    }
+
+    #[test]
+    fn test_reflected_answer() {
+        let (a, b, attacker) = (node(), node(), node());
+
+        // attacker opens connection 1 to node a and reads its challenge
+        let conn1 = Auth::new(secret(), a);
+
+        // connection 2 to node a itself sends challenge of connection 1
+        let conn2 = Auth::new(secret(), a);
+        let answer = conn2.sign(conn1.challenge(), &attacker);
+        assert!(!conn1.verify(&attacker, &answer));
+        assert!(!conn1.verify(&a, &answer));
+
+        // connection 2 to node b sends challenge of connection 1
+        let conn2 = Auth::new(secret(), b);
+        let answer = conn2.sign(conn1.challenge(), &attacker);
+        assert!(!conn1.verify(&b, &answer));
+        assert!(!conn1.verify(&attacker, &answer));
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(to_hex(&[0, 15, 255]), "000fff");
+        assert_eq!(from_hex("000fff"), Some(vec![0, 15, 255]));
+        assert_eq!(from_hex("0"), None);
+        assert_eq!(from_hex("zz"), None);
+    }
+}
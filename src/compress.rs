@@ -0,0 +1,135 @@
+use std::io;
+#[cfg(feature="zstd")]
+use std::io::Read;
+
+#[cfg(feature="lz4")]
+use byteorder::{ByteOrder, LittleEndian};
+#[cfg(feature="lz4")]
+use lz4;
+#[cfg(feature="zstd")]
+use zstd;
+
+/// Frame flag, frame payload is not compressed
+pub const FLAG_NONE: u8 = 0;
+
+#[cfg(feature="zstd")]
+const ZSTD_LEVEL: i32 = 3;
+
+
+/// Frame compression algorithm
+///
+/// Algorithms are enabled with `zstd` and `lz4` cargo features. Node accepts
+/// frames compressed with any enabled algorithm, algorithms are announced
+/// in handshake.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Compression {
+    #[cfg(feature="zstd")]
+    Zstd,
+    #[cfg(feature="lz4")]
+    Lz4,
+}
+
+impl Compression {
+    /// Algorithms enabled in this build
+    pub fn supported() -> Vec<Compression> {
+        vec![
+            #[cfg(feature="zstd")]
+            Compression::Zstd,
+            #[cfg(feature="lz4")]
+            Compression::Lz4,
+        ]
+    }
+
+    /// Algorithm name announced in handshake
+    pub fn name(&self) -> &'static str {
+        match *self {
+            #[cfg(feature="zstd")]
+            Compression::Zstd => "zstd",
+            #[cfg(feature="lz4")]
+            Compression::Lz4 => "lz4",
+        }
+    }
+
+    pub(crate) fn flag(&self) -> u8 {
+        match *self {
+            #[cfg(feature="zstd")]
+            Compression::Zstd => 1,
+            #[cfg(feature="lz4")]
+            Compression::Lz4 => 2,
+        }
+    }
+
+    pub(crate) fn from_flag(flag: u8) -> Option<Compression> {
+        Compression::supported().into_iter().find(|c| c.flag() == flag)
+    }
+
+    #[cfg_attr(not(any(feature="zstd", feature="lz4")), allow(unused_variables))]
+    pub(crate) fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match *self {
+            #[cfg(feature="zstd")]
+            Compression::Zstd => zstd::encode_all(data, ZSTD_LEVEL),
+            #[cfg(feature="lz4")]
+            Compression::Lz4 => lz4::block::compress(data, None, true),
+        }
+    }
+
+    /// Decompress data, fails if decompressed data is larger than `limit`
+    #[cfg_attr(not(any(feature="zstd", feature="lz4")), allow(unused_variables))]
+    pub(crate) fn decompress(&self, data: &[u8], limit: usize) -> io::Result<Vec<u8>> {
+        match *self {
+            #[cfg(feature="zstd")]
+            Compression::Zstd => {
+                let mut buf = Vec::new();
+                zstd::stream::Decoder::new(data)?
+                    .take(limit as u64 + 1).read_to_end(&mut buf)?;
+                if buf.len() > limit {
+                    return Err(too_large())
+                }
+                Ok(buf)
+            },
+            #[cfg(feature="lz4")]
+            Compression::Lz4 => {
+                // block starts with little endian size of decompressed data
+                if data.len() < 4 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData, "Compressed frame is too short"))
+                }
+                let size = LittleEndian::read_i32(data);
+                if size < 0 || size as usize > limit {
+                    return Err(too_large())
+                }
+                lz4::block::decompress(data, None)
+            },
+        }
+    }
+}
+
+#[cfg(any(feature="zstd", feature="lz4"))]
+fn too_large() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "Decompressed frame is too large")
+}
+
+#[cfg(all(test, any(feature="zstd", feature="lz4")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let data = vec![b'a'; 4096];
+        for compression in Compression::supported() {
+            let compressed = compression.compress(&data).unwrap();
+            assert!(compressed.len() < data.len());
+            assert_eq!(Compression::from_flag(compression.flag()), Some(compression));
+            assert_eq!(compression.decompress(&compressed, data.len()).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_decompress_limit() {
+        let data = vec![0u8; 1024 * 1024];
+        for compression in Compression::supported() {
+            let compressed = compression.compress(&data).unwrap();
+            assert!(compression.decompress(&compressed, data.len() - 1).is_err());
+        }
+    }
+}
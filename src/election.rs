@@ -0,0 +1,112 @@
+use actix::prelude::*;
+
+use msgs;
+use node::NodeId;
+use world::World;
+
+
+/// Leader of the role is changed
+///
+/// `term` grows with every leadership change seen by the local node.
+/// It is not a fencing token, leaders of different partitions can use
+/// same term.
+#[derive(Message, Clone, Debug)]
+pub struct LeaderChanged {
+    pub role: String,
+    /// Current leader, `None` if leader is not known
+    pub leader: Option<NodeId>,
+    /// Election counter, not unique across partitions
+    pub term: u64,
+    /// Local node is leader
+    pub local: bool,
+}
+
+impl World {
+    /// Subscribe to leader changes among nodes with the role.
+    ///
+    /// Every node with the role takes part in election, node with highest
+    /// id among connected nodes becomes leader and announces itself with
+    /// new term. Subscriber receives current state immediately. Nodes in
+    /// different network partitions can elect different leaders, terms
+    /// of the leaders are not guaranteed to be unique across partitions.
+    ///
+    /// Roles are declared by nodes themselves, any connected node can
+    /// take part in election unless nodes are authenticated with shared
+    /// secret or TLS.
+    pub fn leader_election(world: &Addr<Syn, World>, role: &str,
+                           subscriber: Recipient<Syn, LeaderChanged>)
+    {
+        world.do_send(msgs::JoinElection{role: role.to_owned(), subscriber: subscriber})
+    }
+}
+
+/// Election state of the role
+#[derive(Default)]
+pub(crate) struct Election {
+    pub leader: Option<NodeId>,
+    pub term: u64,
+    pub subscribers: Vec<Recipient<Syn, LeaderChanged>>,
+}
+
+impl Election {
+    pub fn set_leader(&mut self, role: &str, leader: Option<NodeId>, local: NodeId) {
+        self.leader = leader;
+        for subscriber in &self.subscribers {
+            let _ = subscriber.do_send(self.state(role, local));
+        }
+    }
+
+    pub fn state(&self, role: &str, local: NodeId) -> LeaderChanged {
+        LeaderChanged{role: role.to_owned(), leader: self.leader, term: self.term,
+                      local: self.leader == Some(local)}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use actix::System;
+    use futures::Future;
+    use tokio_core::reactor::Timeout;
+    use uuid::Uuid;
+
+    use super::*;
+
+    /// Subscriber, collects leader changes
+    struct Changes(Arc<Mutex<Vec<LeaderChanged>>>);
+
+    impl Actor for Changes {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<LeaderChanged> for Changes {
+        type Result = ();
+
+        fn handle(&mut self, msg: LeaderChanged, _: &mut Context<Self>) {
+            self.0.lock().unwrap().push(msg);
+        }
+    }
+
+    #[test]
+    fn test_announcement_without_role() {
+        let mut sys = System::new("test");
+        let world = World::new("mem://election-no-role".to_owned()).unwrap().start();
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let subscriber: Addr<Syn, _> = Changes(Arc::clone(&changes)).start();
+        World::leader_election(&world, "db", subscriber.recipient());
+
+        // node is not connected, its roles are not known
+        world.do_send(msgs::LeaderAnnounced{
+            node: NodeId::new(Uuid::new_v4()), role: "db".to_owned(), term: 1});
+        sys.run_until_complete(
+            Timeout::new(Duration::from_millis(100), Arbiter::handle()).unwrap().map(|_| ()))
+            .unwrap();
+
+        let changes = changes.lock().unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].leader, None);
+        assert_eq!(changes[0].term, 0);
+    }
+}
@@ -0,0 +1,110 @@
+use protocol;
+
+
+/// Remote message delivery error
+#[derive(Fail, Debug, Clone, Copy, PartialEq)]
+pub enum RemoteError {
+    /// None of connected nodes supports message type
+    #[fail(display="Message type is not supported")]
+    NotSupported,
+    /// Remote node does not allow this node to send message type
+    #[fail(display="Permission denied")]
+    PermissionDenied,
+    /// Connected nodes do not support message version
+    #[fail(display="Message version is not supported")]
+    VersionMismatch,
+    /// Remote providers use different message type with same type id
+    #[fail(display="Message type fingerprint mismatch")]
+    TypeMismatch,
+    /// Actor is not registered on the remote node
+    #[fail(display="Remote actor is not found")]
+    ActorNotFound,
+    /// Recipient is stopped
+    #[fail(display="Remote recipient is stopped")]
+    Stopped,
+    /// Sharded message does not have routing key
+    #[fail(display="Message does not have routing key")]
+    MissingRoutingKey,
+    /// Remote node can not start actor
+    #[fail(display="Remote actor can not be started")]
+    SpawnFailed,
+    /// Name is not registered on any connected node
+    #[fail(display="Name is not registered")]
+    NameNotFound,
+    /// Remote node has been disconnected before result is received
+    #[fail(display="Remote node is disconnected")]
+    Disconnected,
+    /// Message or result can not be decoded
+    #[fail(display="Can not decode message or result")]
+    Decode,
+    /// Recipient is running but did not reply to the message
+    #[fail(display="Remote recipient did not reply")]
+    Unavailable,
+    /// Message or result is larger than maximum frame payload
+    #[fail(display="Message is too large")]
+    TooLarge,
+    /// Message delivery timed out
+    #[fail(display="Message delivery timed out")]
+    Timeout,
+    /// Other error reported by remote node
+    #[fail(display="Remote error, code: {}", _0)]
+    Remote(u16),
+}
+
+impl RemoteError {
+    /// Error for `Response::Error` code
+    pub fn from_code(code: u16) -> RemoteError {
+        match code {
+            protocol::ERROR_NOT_SUPPORTED => RemoteError::NotSupported,
+            protocol::ERROR_PERMISSION_DENIED => RemoteError::PermissionDenied,
+            protocol::ERROR_VERSION_MISMATCH => RemoteError::VersionMismatch,
+            protocol::ERROR_ACTOR_NOT_FOUND => RemoteError::ActorNotFound,
+            protocol::ERROR_SPAWN_FAILED => RemoteError::SpawnFailed,
+            protocol::ERROR_STOPPED => RemoteError::Stopped,
+            protocol::ERROR_TOO_LARGE => RemoteError::TooLarge,
+            protocol::ERROR_DECODE => RemoteError::Decode,
+            protocol::ERROR_TYPE_MISMATCH => RemoteError::TypeMismatch,
+            protocol::ERROR_UNAVAILABLE => RemoteError::Unavailable,
+            code => RemoteError::Remote(code),
+        }
+    }
+
+    /// `Response::Error` code for error of forwarded message
+    pub(crate) fn to_code(self) -> u16 {
+        match self {
+            RemoteError::NotSupported => protocol::ERROR_NOT_SUPPORTED,
+            RemoteError::PermissionDenied => protocol::ERROR_PERMISSION_DENIED,
+            RemoteError::VersionMismatch => protocol::ERROR_VERSION_MISMATCH,
+            RemoteError::TypeMismatch => protocol::ERROR_TYPE_MISMATCH,
+            RemoteError::ActorNotFound => protocol::ERROR_ACTOR_NOT_FOUND,
+            RemoteError::SpawnFailed => protocol::ERROR_SPAWN_FAILED,
+            RemoteError::Decode => protocol::ERROR_DECODE,
+            RemoteError::TooLarge => protocol::ERROR_TOO_LARGE,
+            RemoteError::Remote(code) => code,
+            // instance behind forwarding actor is stopped or not reachable
+            RemoteError::Stopped |
+            RemoteError::Unavailable |
+            RemoteError::MissingRoutingKey |
+            RemoteError::NameNotFound |
+            RemoteError::Disconnected |
+            RemoteError::Timeout => protocol::ERROR_UNAVAILABLE,
+        }
+    }
+}
+
+/// Message type registration error
+#[derive(Fail, Debug, Clone, PartialEq)]
+pub enum RegistrationError {
+    /// Type id is already used by different message type
+    #[fail(display="Type id {} is already registered for different type", _0)]
+    Duplicate(String),
+    /// Name is already registered on this node
+    #[fail(display="Name {} is already registered", _0)]
+    NameTaken(String),
+    /// Factory for actor kind is already registered
+    #[fail(display="Factory for actor kind {} is already registered", _0)]
+    KindTaken(String),
+    /// World is stopped
+    #[fail(display="World is stopped")]
+    Stopped,
+}
@@ -1,24 +1,91 @@
+#![allow(proc_macro_derive_resolution_fallback, bare_trait_objects, non_local_definitions)]
+#![allow(clippy::redundant_field_names, clippy::too_many_arguments, clippy::type_complexity,
+         clippy::io_other_error)]
+
 #[macro_use] extern crate actix;
 extern crate backoff;
 extern crate bytes;
+#[macro_use] extern crate failure;
 extern crate byteorder;
 extern crate serde;
-extern crate serde_json;
+#[cfg_attr(test, macro_use)] extern crate serde_json;
 #[macro_use] extern crate serde_derive;
 extern crate net2;
 #[macro_use] extern crate log;
 extern crate futures;
 extern crate tokio_core;
 extern crate tokio_io;
+#[cfg(unix)]
+extern crate tokio_uds;
+extern crate uuid;
+extern crate rand;
+extern crate hmac;
+extern crate sha2;
+#[macro_use] extern crate lazy_static;
+#[cfg(feature="rust-tls")]
+extern crate rustls;
+#[cfg(feature="rust-tls")]
+extern crate tokio_rustls;
+#[cfg(feature="rust-tls")]
+extern crate webpki;
+#[cfg(feature="websocket")]
+extern crate tokio_tungstenite;
+#[cfg(feature="websocket")]
+extern crate tungstenite;
+#[cfg(feature="websocket")]
+extern crate url;
+#[cfg(feature="zstd")]
+extern crate zstd;
+#[cfg(feature="lz4")]
+extern crate lz4;
 
+mod actor;
+mod auth;
+mod compress;
+mod election;
+mod error;
+mod migration;
+mod mem;
 mod msgs;
+mod policy;
 mod node;
 mod world;
 mod protocol;
 mod remote;
+mod shard;
+mod singleton;
+mod spawn;
+mod topic;
+mod transport;
 mod recipient;
+mod registry;
 mod worker;
 mod utils;
+mod watch;
+#[cfg(feature="rust-tls")]
+mod tls;
+#[cfg(feature="websocket")]
+mod ws;
 
+pub use actor::{ActorId, RemoteActor, RemoteAddr, RemoteRecipient};
 pub use world::World;
-pub use remote::{Remote, RemoteMessage};
+pub use node::{NodeId, Peer};
+pub use compress::Compression;
+pub use election::LeaderChanged;
+pub use error::{RegistrationError, RemoteError};
+pub use migration::Migrations;
+pub use policy::Policy;
+pub use remote::{fingerprint, FromPeer, Passivate, Remote, RemoteMessage};
+pub use shard::{ShardRegion, Sharding};
+pub use singleton::Singleton;
+pub use spawn::RemoteSpawn;
+pub use topic::Topic;
+pub use transport::{Incoming, Listener, MemTransport, TcpTransport, Transport};
+#[cfg(unix)]
+pub use transport::UnixTransport;
+pub use utils::Io;
+pub use watch::{Terminated, TerminationReason};
+#[cfg(feature="rust-tls")]
+pub use tls::TlsConfig;
+#[cfg(feature="websocket")]
+pub use ws::WsTransport;
@@ -0,0 +1,194 @@
+//! In-process transport
+//!
+//! Listener registers name in process wide registry, connection is a pair
+//! of byte channels. Worlds in the same process can communicate over
+//! `mem://name` addresses without opening sockets.
+use std::{cmp, io};
+use std::sync::Mutex;
+use std::collections::HashMap;
+
+use bytes::Bytes;
+use futures::{Async, Poll, Stream};
+use futures::sync::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+lazy_static! {
+    static ref LISTENERS: Mutex<HashMap<String, UnboundedSender<MemStream>>> =
+        Mutex::new(HashMap::new());
+}
+
+
+/// In-memory duplex byte stream
+pub struct MemStream {
+    tx: Option<UnboundedSender<Bytes>>,
+    rx: UnboundedReceiver<Bytes>,
+    buf: Bytes,
+}
+
+impl MemStream {
+    /// Create pair of connected streams
+    pub fn pair() -> (MemStream, MemStream) {
+        let (tx1, rx1) = unbounded();
+        let (tx2, rx2) = unbounded();
+        (MemStream{tx: Some(tx1), rx: rx2, buf: Bytes::new()},
+         MemStream{tx: Some(tx2), rx: rx1, buf: Bytes::new()})
+    }
+}
+
+impl io::Read for MemStream {
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if !self.buf.is_empty() {
+                let size = cmp::min(dst.len(), self.buf.len());
+                dst[..size].copy_from_slice(&self.buf.split_to(size));
+                return Ok(size)
+            }
+            match self.rx.poll() {
+                Ok(Async::Ready(Some(data))) => self.buf = data,
+                Ok(Async::Ready(None)) | Err(_) => return Ok(0),
+                Ok(Async::NotReady) => return Err(io::ErrorKind::WouldBlock.into()),
+            }
+        }
+    }
+}
+
+impl io::Write for MemStream {
+    fn write(&mut self, src: &[u8]) -> io::Result<usize> {
+        if let Some(ref tx) = self.tx {
+            if tx.unbounded_send(Bytes::from(src)).is_ok() {
+                return Ok(src.len())
+            }
+        }
+        Err(io::ErrorKind::BrokenPipe.into())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsyncRead for MemStream {}
+
+impl AsyncWrite for MemStream {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.tx.take();
+        Ok(Async::Ready(()))
+    }
+}
+
+/// In-memory listener, yields connected streams
+pub struct MemListener {
+    name: String,
+    rx: UnboundedReceiver<MemStream>,
+}
+
+impl MemListener {
+    /// Register listener with specified name
+    pub fn bind(name: &str) -> io::Result<MemListener> {
+        let mut listeners = LISTENERS.lock().unwrap();
+        if listeners.contains_key(name) {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse, format!("Name is in use: {}", name)))
+        }
+        let (tx, rx) = unbounded();
+        listeners.insert(name.to_owned(), tx);
+        Ok(MemListener{name: name.to_owned(), rx: rx})
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+}
+
+impl Drop for MemListener {
+    fn drop(&mut self) {
+        if let Ok(mut listeners) = LISTENERS.lock() {
+            listeners.remove(&self.name);
+        }
+    }
+}
+
+impl Stream for MemListener {
+    type Item = MemStream;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<MemStream>, io::Error> {
+        self.rx.poll().map_err(|_| io::ErrorKind::Other.into())
+    }
+}
+
+/// Connect to in-memory listener
+pub fn connect(name: &str) -> io::Result<MemStream> {
+    let listeners = LISTENERS.lock().unwrap();
+    if let Some(tx) = listeners.get(name) {
+        let (local, remote) = MemStream::pair();
+        if tx.unbounded_send(remote).is_ok() {
+            return Ok(local)
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::ConnectionRefused, format!("No listener: {}", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use futures::Future;
+    use tokio_io::io::{read_exact, read_to_end, write_all};
+
+    use super::*;
+
+    #[test]
+    fn test_pair() {
+        let (a, b) = MemStream::pair();
+        let (a, _) = write_all(a, b"hel").wait().unwrap();
+        let (a, _) = write_all(a, b"lo").wait().unwrap();
+        let (b, buf) = read_exact(b, [0u8; 5]).wait().unwrap();
+        assert_eq!(&buf, b"hello");
+
+        // other direction
+        write_all(b, b"world").wait().unwrap();
+        let (_, buf) = read_exact(a, [0u8; 5]).wait().unwrap();
+        assert_eq!(&buf, b"world");
+    }
+
+    #[test]
+    fn test_shutdown() {
+        let (mut a, b) = MemStream::pair();
+        a.write_all(b"data").unwrap();
+        a.shutdown().unwrap();
+        let (_, buf) = read_to_end(b, Vec::new()).wait().unwrap();
+        assert_eq!(buf, b"data");
+
+        let err = a.write(b"more").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn test_closed_peer() {
+        let (mut a, b) = MemStream::pair();
+        drop(b);
+        let err = a.write(b"data").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn test_listener() {
+        let listener = MemListener::bind("test-listener").unwrap();
+        assert_eq!(listener.name(), "test-listener");
+        let err = MemListener::bind("test-listener").err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+
+        let mut local = connect("test-listener").unwrap();
+        let (remote, listener) = listener.into_future().map_err(|(err, _)| err).wait().unwrap();
+        local.write_all(b"ping").unwrap();
+        let (_, buf) = read_exact(remote.unwrap(), [0u8; 4]).wait().unwrap();
+        assert_eq!(&buf, b"ping");
+
+        // name is released with listener
+        drop(listener);
+        let err = connect("test-listener").err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+        MemListener::bind("test-listener").unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{self as json, Value};
+
+use error::RemoteError;
+
+type Migration = Box<Fn(Value) -> Value + Send + Sync>;
+
+
+/// Message schema migrations of the provider
+///
+/// Upcasters convert raw message of older version to the next version
+/// before message is deserialized, downcasters convert result back
+/// for callers that use older message version. Provider handler is written
+/// against latest message version only. If any upcaster or downcaster
+/// is registered, chain has to cover every version between caller and
+/// provider, message of version with missing step is rejected.
+#[derive(Default)]
+pub struct Migrations {
+    up: HashMap<u32, Migration>,
+    down: HashMap<u32, Migration>,
+}
+
+impl Migrations {
+    pub fn new() -> Migrations {
+        Migrations::default()
+    }
+
+    /// Register upcaster, converts message of `version` to `version + 1`
+    pub fn upcast<F>(mut self, version: u32, f: F) -> Self
+        where F: Fn(Value) -> Value + Send + Sync + 'static
+    {
+        self.up.insert(version, Box::new(f));
+        self
+    }
+
+    /// Register downcaster, converts result of `version + 1` to `version`
+    pub fn downcast<F>(mut self, version: u32, f: F) -> Self
+        where F: Fn(Value) -> Value + Send + Sync + 'static
+    {
+        self.down.insert(version, Box::new(f));
+        self
+    }
+
+    /// Lowest version that can be upcasted to `version`
+    pub(crate) fn min_version(&self, version: u32) -> u32 {
+        let mut min = version;
+        while min > 0 && self.up.contains_key(&(min - 1)) {
+            min -= 1;
+        }
+        min
+    }
+
+    /// Decode message of `version`, apply upcasters up to `current` version
+    pub(crate) fn decode<M>(&self, version: u32, current: u32, msg: &str)
+                            -> Result<M, RemoteError>
+        where M: DeserializeOwned
+    {
+        let chain = chain(&self.up, version, current)?;
+        if chain.is_empty() {
+            return json::from_str(msg).map_err(decode_error)
+        }
+        let mut value: Value = json::from_str(msg).map_err(decode_error)?;
+        for f in chain {
+            value = f(value);
+        }
+        json::from_value(value).map_err(decode_error)
+    }
+
+    /// Encode result for caller of `version`, apply downcasters
+    /// from `current` version
+    pub(crate) fn encode<R>(&self, version: u32, current: u32, res: &R)
+                            -> Result<String, RemoteError>
+        where R: Serialize
+    {
+        let chain = chain(&self.down, version, current)?;
+        if chain.is_empty() {
+            return json::to_string(res).map_err(decode_error)
+        }
+        let mut value = json::to_value(res).map_err(decode_error)?;
+        for f in chain.into_iter().rev() {
+            value = f(value);
+        }
+        json::to_string(&value).map_err(decode_error)
+    }
+}
+
+/// Migrations from `version` to `current` version, without migrations
+/// every version is used as is, otherwise every step has to be registered
+fn chain(migrations: &HashMap<u32, Migration>, version: u32, current: u32)
+         -> Result<Vec<&Migration>, RemoteError>
+{
+    if version >= current || migrations.is_empty() {
+        return Ok(Vec::new())
+    }
+    (version..current)
+        .map(|ver| migrations.get(&ver).ok_or(RemoteError::VersionMismatch))
+        .collect()
+}
+
+fn decode_error(_: json::Error) -> RemoteError {
+    RemoteError::Decode
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Item {
+        name: String,
+        size: u32,
+    }
+
+    /// v1 has `title`, v2 renames it to `name`, v3 adds `size`
+    fn migrations() -> Migrations {
+        Migrations::new()
+            .upcast(1, |v| json!({"name": v["title"]}))
+            .upcast(2, |v| json!({"name": v["name"], "size": 0}))
+            .downcast(2, |v| json!({"name": v["name"]}))
+            .downcast(1, |v| json!({"title": v["name"]}))
+    }
+
+    #[test]
+    fn test_upcast() {
+        let m = migrations();
+        assert_eq!(m.min_version(3), 1);
+        let item: Item = m.decode(1, 3, r#"{"title": "a"}"#).unwrap();
+        assert_eq!(item, Item{name: "a".to_owned(), size: 0});
+        let item: Item = m.decode(3, 3, r#"{"name": "b", "size": 1}"#).unwrap();
+        assert_eq!(item, Item{name: "b".to_owned(), size: 1});
+    }
+
+    #[test]
+    fn test_downcast() {
+        let m = migrations();
+        let item = Item{name: "a".to_owned(), size: 1};
+        let v1: Value = json::from_str(&m.encode(1, 3, &item).unwrap()).unwrap();
+        assert_eq!(v1, json!({"title": "a"}));
+        let v3: Value = json::from_str(&m.encode(3, 3, &item).unwrap()).unwrap();
+        assert_eq!(v3, json!({"name": "a", "size": 1}));
+    }
+
+    #[test]
+    fn test_gap() {
+        // step from version 2 is missing
+        let m = Migrations::new()
+            .upcast(1, |v| json!({"name": v["title"]}))
+            .downcast(1, |v| json!({"title": v["name"]}));
+        assert_eq!(m.min_version(3), 3);
+        let res: Result<Item, _> = m.decode(1, 3, r#"{"title": "a"}"#);
+        assert_eq!(res.unwrap_err(), RemoteError::VersionMismatch);
+        let item = Item{name: "a".to_owned(), size: 1};
+        assert_eq!(m.encode(1, 3, &item).unwrap_err(), RemoteError::VersionMismatch);
+
+        // undecodable message is not version error
+        let res: Result<Item, _> = migrations().decode(1, 3, "[]");
+        assert_eq!(res.unwrap_err(), RemoteError::Decode);
+    }
+
+    #[test]
+    fn test_no_migrations() {
+        // provider without migrations accepts compatible versions as is
+        let m = Migrations::new();
+        let item: Item = m.decode(1, 3, r#"{"name": "a", "size": 1}"#).unwrap();
+        assert_eq!(item, Item{name: "a".to_owned(), size: 1});
+        assert_eq!(m.encode(1, 3, &item).unwrap(), r#"{"name":"a","size":1}"#);
+    }
+}
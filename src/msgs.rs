@@ -1,41 +1,109 @@
 #![allow(dead_code)]
 
 use std::{net, io};
+use std::any::TypeId;
 use std::sync::Arc;
+use std::collections::HashMap;
 use serde::Serialize;
 use serde::de::DeserializeOwned;
 use futures::sync::mpsc::Receiver;
 use futures::unsync::oneshot::Sender;
 
-use actix::{Actor, Addr, Handler, Message, Unsync};
+use actix::{Actor, Addr, Handler, Message, Recipient, Syn, Unsync};
 
-use node::NetworkNode;
+use actor::{ActorId, Probe};
+use error::{RegistrationError, RemoteError};
+use node::{NetworkNode, NodeId, Peer};
+use protocol::{NameInfo, TypeInfo};
+use utils::Io;
+use worker::NetworkWorker;
+use world::World;
 use remote::RemoteMessage;
 use recipient::RemoteMessageHandler;
+use spawn::ActorFactory;
+use watch::{Terminated, TerminationReason};
+use election::LeaderChanged;
 
 #[derive(Message)]
 pub(crate) struct RegisterNode {
     pub addr: net::SocketAddr,
 }
 
+/// Established connection to a network node
+#[derive(Clone)]
+pub(crate) struct Connection {
+    pub id: usize,
+    /// Connection is accepted by local listener
+    pub inbound: bool,
+    pub worker: Addr<Unsync, NetworkWorker>,
+}
+
+/// Worker notifies world, remote node completed handshake.
+#[derive(Message)]
+pub(crate) struct NodeConnected {
+    pub id: NodeId,
+    pub addr: String,
+    pub roles: Vec<String>,
+    pub certificates: Vec<Vec<u8>>,
+    pub conn: Connection,
+}
+
+/// NetworkNode notifies world, outbound connection is established
+#[derive(Message)]
+pub(crate) struct OutboundConnection {
+    pub addr: String,
+    pub stream: Box<Io>,
+    pub node: Addr<Unsync, NetworkNode>,
+}
+
+/// World failed to start outbound connection
+#[derive(Message)]
+pub(crate) struct ConnectFailed;
+
+/// World sends this message to NetworkNode.
+/// New connection to the node with specified id is available.
 #[derive(Message)]
-pub(crate) struct ReconnectNode;
+pub(crate) struct AttachConnection {
+    pub id: NodeId,
+    pub conn: Connection,
+}
 
+/// Stop network node of stale node incarnation
 #[derive(Message)]
-pub(crate) struct NodeConnected(pub String);
+pub(crate) struct StopNode;
+
+/// Network node passes its connection to other node of same id and stops
+#[derive(Message)]
+pub(crate) struct MoveConnection(pub Addr<Unsync, NetworkNode>);
+
+/// Connection closed notification for NetworkNode
+#[derive(Message)]
+pub(crate) struct ConnectionClosed(pub usize);
 
 /// NetworkNode notifies world.
 /// New remote recipient is available.
 #[derive(Message, Clone)]
 pub(crate) struct NodeSupportedTypes {
-    pub node: String,
-    pub types: Vec<String>,
+    pub node: NodeId,
+    pub types: Vec<TypeInfo>,
 }
 
 #[derive(Message)]
 pub(crate) struct WorkerDisconnected(pub usize);
 
-/// Register new recipient provider
+/// Register new recipient provider in world,
+/// fails if type id is used by different message type
+pub(crate) struct RegisterProvider {
+    pub type_id: &'static str,
+    pub message_type: TypeId,
+    pub handler: Arc<RemoteMessageHandler>,
+}
+
+impl Message for RegisterProvider {
+    type Result = Result<(), RegistrationError>;
+}
+
+/// World notifies workers, new recipient provider is registered
 #[derive(Message, Clone)]
 pub struct ProvideRecipient{
     pub type_id: &'static str,
@@ -49,30 +117,317 @@ pub(crate) struct GetRecipient<M>
     pub rx: Receiver<M>,
 }
 
+/// Node with specified id is gone, either disconnected or restarted
 #[derive(Message)]
-pub(crate) struct NodeGone(pub String);
+pub(crate) struct NodeGone(pub NodeId);
 
 /// World sends this message to RecipientProxy.
 /// Notifies about new node with support of specific type_id.
 #[derive(Message)]
 pub(crate) struct TypeSupported {
     pub type_id: String,
-    pub node_id: String,
-    pub node: Addr<Unsync, NetworkNode> }
+    pub node_id: NodeId,
+    pub node: Addr<Unsync, NetworkNode>,
+    /// Supported message versions
+    pub versions: (u32, u32),
+    /// Message type fingerprint
+    pub fingerprint: u64,
+}
 
 pub(crate) trait NodeOperations: Actor + Handler<NodeGone> + Handler<TypeSupported> {}
 
+/// World is started, proxies decode results with world address
+#[derive(Message)]
+pub(crate) struct WorldStarted(pub Addr<Syn, World>);
+
 
 pub(crate) struct SendRemoteMessage{
+    /// Target actor, message is routed by type if not set
+    pub actor: Option<u64>,
     pub type_id: String,
+    pub version: u32,
+    pub fingerprint: u64,
     pub data: String,
-    pub tx: Sender<String>,
+    pub tx: Sender<Result<String, RemoteError>>,
 }
 
 impl Message for SendRemoteMessage {
     type Result = Result<String, io::Error>;
 }
 
+/// Register actor handlers in world, result is actor identity
+pub(crate) struct RegisterActor {
+    pub handlers: HashMap<&'static str, Arc<RemoteMessageHandler>>,
+    pub alive: Option<Probe>,
+}
+
+impl Message for RegisterActor {
+    type Result = ActorId;
+}
+
+/// Remove actor from world
+#[derive(Message)]
+pub(crate) struct UnregisterActor(pub ActorId);
+
+/// Stop local actor or actor spawned on remote node, `origin` is node
+/// that sent actor reference, `None` for references of local node
+#[derive(Message)]
+pub(crate) struct StopActor {
+    pub actor: ActorId,
+    pub origin: Option<NodeId>,
+}
+
+/// Worker passes stop request of peer
+#[derive(Message)]
+pub(crate) struct RemoteStop {
+    pub node: NodeId,
+    pub actor: u64,
+}
+
+/// Send message to specific actor, local or remote, `origin` is node
+/// that sent actor reference, `None` for references of local node
+pub(crate) struct SendToActor {
+    pub actor: ActorId,
+    pub origin: Option<NodeId>,
+    pub type_id: String,
+    pub version: u32,
+    pub fingerprint: u64,
+    pub data: String,
+}
+
+impl Message for SendToActor {
+    type Result = Result<String, RemoteError>;
+}
+
+/// Send message to provider of message type on specific node
+pub(crate) struct SendToNode {
+    pub node: NodeId,
+    pub type_id: String,
+    pub version: u32,
+    pub fingerprint: u64,
+    pub data: String,
+}
+
+impl Message for SendToNode {
+    type Result = Result<String, RemoteError>;
+}
+
+/// Nodes that provide message type, result is local node id and providers
+pub(crate) struct TypeNodes(pub &'static str);
+
+impl Message for TypeNodes {
+    type Result = (NodeId, Vec<NodeId>);
+}
+
+/// Worker asks world to deliver message from peer to local actor,
+/// result is error code if message can not be delivered
+pub(crate) struct DeliverToActor {
+    pub peer: Peer,
+    pub actor: u64,
+    pub type_id: String,
+    pub version: u32,
+    pub fingerprint: u64,
+    pub body: String,
+    pub tx: Sender<Result<String, u16>>,
+}
+
+impl Message for DeliverToActor {
+    type Result = Result<(), u16>;
+}
+
+/// Register named actor in world, fails if name is registered on this node
+pub(crate) struct RegisterName {
+    pub name: String,
+    pub type_id: &'static str,
+    pub handler: Arc<RemoteMessageHandler>,
+}
+
+impl Message for RegisterName {
+    type Result = Result<ActorId, RegistrationError>;
+}
+
+/// Remove named actor registered on this node
+#[derive(Message)]
+pub(crate) struct UnregisterName(pub String);
+
+/// Resolve name to actor that accepts message type
+pub(crate) struct LookupName {
+    pub name: String,
+    pub type_id: &'static str,
+}
+
+impl Message for LookupName {
+    type Result = Result<ActorId, RemoteError>;
+}
+
+/// Worker notifies world, node announced its named actors
+#[derive(Message)]
+pub(crate) struct NodeNames {
+    pub node: NodeId,
+    pub names: Vec<NameInfo>,
+}
+
+/// Register actor factory in world
+pub(crate) struct RegisterFactory {
+    pub kind: &'static str,
+    pub factory: Arc<ActorFactory>,
+}
+
+impl Message for RegisterFactory {
+    type Result = Result<(), RegistrationError>;
+}
+
+/// Start actor on local or remote node
+pub(crate) struct SpawnActor {
+    pub node: NodeId,
+    pub kind: &'static str,
+    pub args: String,
+}
+
+impl Message for SpawnActor {
+    type Result = Result<ActorId, RemoteError>;
+}
+
+/// World asks network node to start actor on remote node
+#[derive(Message)]
+pub(crate) struct SpawnRemote {
+    pub kind: String,
+    pub args: String,
+    pub tx: Sender<Result<String, RemoteError>>,
+}
+
+/// Worker asks world to start actor for peer,
+/// result is actor id or error code
+pub(crate) struct SpawnLocal {
+    pub peer: Peer,
+    pub kind: String,
+    pub args: String,
+}
+
+impl Message for SpawnLocal {
+    type Result = Result<u64, u16>;
+}
+
+/// Watch actor on local or remote node
+#[derive(Message)]
+pub(crate) struct Watch {
+    pub actor: ActorId,
+    pub watcher: Recipient<Syn, Terminated>,
+}
+
+/// Worker notifies world, peer watches local actor
+#[derive(Message)]
+pub(crate) struct RemoteWatch {
+    pub node: NodeId,
+    pub actor: u64,
+}
+
+/// Watched actor is terminated
+#[derive(Message)]
+pub(crate) struct ActorTerminated {
+    pub actor: ActorId,
+    pub reason: TerminationReason,
+}
+
+/// Mailbox of local actor is closed
+#[derive(Message)]
+pub(crate) struct ActorStopped(pub u64);
+
+/// NetworkNode notifies world, connection to the node is lost
+#[derive(Message)]
+pub(crate) struct NodeUnreachable(pub NodeId);
+
+/// Subscribe handler to topic, result is subscription id
+pub(crate) struct Subscribe {
+    pub topic: String,
+    pub type_id: &'static str,
+    pub message_type: TypeId,
+    pub handler: Arc<RemoteMessageHandler>,
+}
+
+impl Message for Subscribe {
+    type Result = Result<u64, RegistrationError>;
+}
+
+/// Remove topic subscription
+#[derive(Message)]
+pub(crate) struct Unsubscribe {
+    pub topic: String,
+    pub id: u64,
+}
+
+/// Publish message to local and remote subscribers
+#[derive(Message)]
+pub(crate) struct Publish {
+    pub topic: String,
+    pub type_id: String,
+    pub version: u32,
+    pub fingerprint: u64,
+    pub data: String,
+}
+
+/// Worker passes message published by peer
+#[derive(Message)]
+pub(crate) struct TopicMessage {
+    pub peer: Peer,
+    pub topic: String,
+    pub type_id: String,
+    pub version: u32,
+    pub fingerprint: u64,
+    pub data: String,
+}
+
+/// Worker notifies world, node announced its topics
+#[derive(Message)]
+pub(crate) struct NodeTopics {
+    pub node: NodeId,
+    pub topics: Vec<String>,
+}
+
+/// Subscribe to leader changes of the role
+#[derive(Message)]
+pub(crate) struct JoinElection {
+    pub role: String,
+    pub subscriber: Recipient<Syn, LeaderChanged>,
+}
+
+/// Worker notifies world, node announced leadership
+#[derive(Message)]
+pub(crate) struct LeaderAnnounced {
+    pub node: NodeId,
+    pub role: String,
+    pub term: u64,
+}
+
+/// Message for RecipientProxy, result includes remote delivery error
+pub(crate) struct ProxyMessage<M>(pub M)
+    where M: RemoteMessage + 'static,
+          M::Result: Send + Serialize + DeserializeOwned;
+
+impl<M> Message for ProxyMessage<M>
+    where M: RemoteMessage + 'static,
+          M::Result: Send + Serialize + DeserializeOwned
+{
+    type Result = Result<M::Result, RemoteError>;
+}
+
+/// Message from remote node for actor that routes messages of the type,
+/// i.e. singleton manager or shard region
+pub(crate) struct Forwarded<M>
+    where M: RemoteMessage + 'static,
+          M::Result: Send + Serialize + DeserializeOwned
+{
+    pub msg: M,
+    pub peer: Peer,
+}
+
+impl<M> Message for Forwarded<M>
+    where M: RemoteMessage + 'static,
+          M::Result: Send + Serialize + DeserializeOwned
+{
+    type Result = Result<M::Result, RemoteError>;
+}
+
 //===================================
 // Worker messages
 //===================================
@@ -80,3 +435,8 @@ impl Message for SendRemoteMessage {
 /// Stop worker
 #[derive(Message)]
 pub(crate) struct StopWorker;
+
+/// Bind worker to the network node, worker forwards responses
+/// to the node.
+#[derive(Message)]
+pub(crate) struct AttachNode(pub Addr<Unsync, NetworkNode>);
@@ -1,22 +1,71 @@
-use std::io;
+use std::{fmt, io};
 use std::cell::Cell;
-use std::sync::Arc;
+use std::rc::Rc;
 use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
 use backoff::ExponentialBackoff;
 use backoff::backoff::Backoff;
 use futures::unsync::oneshot;
-use tokio_core::net::TcpStream;
-use tokio_io::AsyncRead;
-use tokio_io::io::WriteHalf;
-use tokio_io::codec::FramedRead;
 use actix::prelude::*;
 use actix::prelude::{Response as ActixResponse};
 
 use msgs;
+use error::RemoteError;
 use world::World;
-use protocol::{Request, Response, NetworkClientCodec};
+use protocol::{self, Request, Response};
+use transport::{self, Transport};
 
 
+/// Network node identity
+///
+/// Uuid identifies node, incarnation changes with every node start.
+/// Node with same uuid and higher incarnation is restarted node.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub struct NodeId {
+    pub uuid: Uuid,
+    pub incarnation: u64,
+}
+
+impl NodeId {
+    /// Create new node id, incarnation is current time in milliseconds
+    pub fn new(uuid: Uuid) -> NodeId {
+        let dur = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        NodeId{uuid: uuid,
+               incarnation: dur.as_secs() * 1000 + u64::from(dur.subsec_millis())}
+    }
+
+    /// Check if this id is newer incarnation of the `other` node
+    pub fn is_restart_of(&self, other: &NodeId) -> bool {
+        self.uuid == other.uuid && self.incarnation > other.incarnation
+    }
+
+    /// Check if this node started before `other` node, uuid orders
+    /// nodes started at same time
+    pub fn is_older_than(&self, other: &NodeId) -> bool {
+        (self.incarnation, self.uuid) < (other.incarnation, other.uuid)
+    }
+}
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}#{}", self.uuid, self.incarnation)
+    }
+}
+
+/// Remote peer of the connection
+#[derive(Clone, Debug)]
+pub struct Peer {
+    /// Peer node id
+    pub id: NodeId,
+    /// Address announced by peer node
+    pub addr: String,
+    /// Roles announced by peer node
+    pub roles: Vec<String>,
+    /// DER encoded certificate chain, available if connection uses TLS
+    pub certificates: Vec<Vec<u8>>,
+}
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum NodeStatus {
     New,
@@ -26,12 +75,12 @@ pub enum NodeStatus {
 }
 
 pub struct NodeInformation {
-    inner: Arc<Inner>,
+    inner: Rc<Inner>,
 }
 
 impl NodeInformation {
     pub fn new(addr: String) -> NodeInformation {
-        NodeInformation{inner: Arc::new(
+        NodeInformation{inner: Rc::new(
             Inner{addr: addr,
                   status: Cell::new(NodeStatus::New)}
         )}
@@ -52,7 +101,7 @@ impl NodeInformation {
 
 impl Clone for NodeInformation {
     fn clone(&self) -> Self {
-        NodeInformation {inner: Arc::clone(&self.inner)}
+        NodeInformation {inner: Rc::clone(&self.inner)}
     }
 }
 
@@ -62,79 +111,107 @@ struct Inner {
 }
 
 /// NetworkNode - Actor responsible for network node
+///
+/// Node uses single connection to remote node for requests and responses.
+/// Connection is either dialed by node or accepted by world. Node that
+/// is created for accepted connection does not re-connect, remote node
+/// is responsible for re-connection.
 pub struct NetworkNode {
     mid: u64,
     world: Addr<Unsync, World>,
-    addr: String,
+    local: NodeId,
+    id: Option<NodeId>,
+    reconnect: bool,
+    transport: Option<Rc<Transport>>,
     inner: NodeInformation,
     backoff: ExponentialBackoff,
-    framed: Option<actix::io::FramedWrite<WriteHalf<TcpStream>, NetworkClientCodec>>,
-    requests: HashMap<u64, oneshot::Sender<String>>,
+    conn: Option<msgs::Connection>,
+    requests: HashMap<u64, oneshot::Sender<Result<String, RemoteError>>>,
 }
 
 impl Actor for NetworkNode {
     type Context = Context<Self>;
 
     fn started(&mut self, ctx: &mut Context<Self>) {
+        // node is created for inbound connection
+        if !self.reconnect {
+            return
+        }
+
         self.inner.set_status(NodeStatus::Connecting);
 
+        let transport = match self.transport {
+            Some(ref transport) => Rc::clone(transport),
+            None => return,
+        };
+
         // Connect to actix remote server
-        actix::actors::Connector::from_registry()
-            .send(actix::actors::Connect::host(self.inner.address().clone()))
+        let (_, addr) = transport::split_addr(self.inner.address());
+        transport.dial(addr)
             .into_actor(self)
-            .map(|res, act, ctx| match res {
-                Ok(stream) => {
-                    info!("Connected to network node: {}", act.inner.address());
+            .map(|stream, act, ctx| {
+                info!("Connected to network node: {}", act.inner.address());
 
-                    let (r, w) = stream.split();
-
-                    // configure write side of the connection
-                    let mut framed =
-                        actix::io::FramedWrite::new(w, NetworkClientCodec::default(), ctx);
-                    framed.write(Request::Handshake(act.addr.clone()));
-                    act.framed = Some(framed);
-
-                    // read side of the connection
-                    ctx.add_stream(FramedRead::new(r, NetworkClientCodec::default()));
-
-                    act.backoff.reset();
-                    act.inner.set_status(NodeStatus::Ok);
-                },
-                Err(err) => act.restart(Some(err), ctx),
+                // world starts worker for the connection
+                act.world.do_send(msgs::OutboundConnection{
+                    addr: act.inner.address().to_owned(),
+                    stream: stream, node: ctx.address()});
             })
-            .map_err(|_, act, ctx| act.restart(None, ctx))
+            .map_err(|err, act, ctx| act.restart(Some(err), ctx))
             .wait(ctx);
     }
 }
 
 impl Supervised for NetworkNode {
     fn restarting(&mut self, _: &mut Self::Context) {
-        self.framed.take();
+        if let Some(conn) = self.conn.take() {
+            conn.worker.do_send(msgs::StopWorker);
+        }
         self.inner.set_status(NodeStatus::Failed);
-        //for tx in self.queue.drain(..) {
-        //let _ = tx.send(Err(Error::Disconnected));
-        //}
+
+        // responses for pending requests are lost with connection
+        self.requests.clear();
     }
 }
 
-impl actix::io::WriteHandler<io::Error> for NetworkNode {}
-
 impl NetworkNode {
-    pub fn new(addr: String, world: Addr<Unsync, World>, info: NodeInformation) -> NetworkNode {
-        info!("New network node: {}", addr);
+    pub fn new(local: NodeId, world: Addr<Unsync, World>,
+               info: NodeInformation, transport: Rc<Transport>) -> NetworkNode
+    {
+        let mut node = NetworkNode::create(local, world, info);
+        node.transport = Some(transport);
+        node
+    }
+
+    /// Create network node for accepted connection
+    pub(crate) fn inbound(local: NodeId, world: Addr<Unsync, World>,
+                          info: NodeInformation) -> NetworkNode
+    {
+        let mut node = NetworkNode::create(local, world, info);
+        node.reconnect = false;
+        node
+    }
+
+    fn create(local: NodeId, world: Addr<Unsync, World>, info: NodeInformation) -> NetworkNode {
+        info!("New network node: {}", info.address());
         NetworkNode {mid: 0,
                      world: world,
-                     addr: addr,
+                     local: local,
+                     id: None,
+                     reconnect: true,
+                     transport: None,
                      inner: info,
-                     framed: None,
+                     conn: None,
                      requests: HashMap::new(),
                      backoff: ExponentialBackoff::default(),
         }
     }
 
-    pub fn restart(&mut self, err: Option<actix::actors::ConnectorError>, ctx: &mut Context<Self>)
+    pub fn restart(&mut self, err: Option<io::Error>, ctx: &mut Context<Self>)
     {
-        self.framed.take();
+        if let Some(conn) = self.conn.take() {
+            conn.worker.do_send(msgs::StopWorker);
+        }
         self.inner.set_status(NodeStatus::Failed);
 
         if let Some(err) = err {
@@ -158,29 +235,71 @@ impl NetworkNode {
             ctx.stop()
         }
     }
-}
 
-impl StreamHandler<Response, io::Error> for NetworkNode
-{
-    fn error(&mut self, err: io::Error, _ctx: &mut Self::Context) -> Running {
-        error!("Network node has been disconnected: {}, err: {}",
-               self.inner.address(), err);
-        Running::Stop
+    /// Use new connection.
+    ///
+    /// If both nodes dial each other at the same time, node keeps
+    /// connection dialed by the node with lower id.
+    fn attach(&mut self, id: NodeId, conn: msgs::Connection, ctx: &mut Context<Self>) {
+        // different node or restarted node, pending requests are stale
+        if let Some(prev) = self.id {
+            if prev != id {
+                info!("Network node {} changed identity: {} -> {}",
+                      self.inner.address(), prev, id);
+                self.requests.clear();
+                if let Some(cur) = self.conn.take() {
+                    cur.worker.do_send(msgs::StopWorker);
+                }
+                self.world.do_send(msgs::NodeGone(prev));
+            }
+        }
+        self.id = Some(id);
+
+        let keep = if let Some(ref cur) = self.conn {
+            if cur.inbound == conn.inbound {
+                // same direction, previous connection is gone
+                true
+            } else {
+                let outbound = self.local < id;
+                conn.inbound != outbound
+            }
+        } else {
+            true
+        };
+
+        if keep {
+            debug!("Use {} connection for network node: {}",
+                   if conn.inbound {"inbound"} else {"outbound"}, self.inner.address());
+            conn.worker.do_send(msgs::AttachNode(ctx.address()));
+            if let Some(old) = self.conn.take() {
+                old.worker.do_send(msgs::StopWorker);
+            }
+            self.conn = Some(conn);
+            self.backoff.reset();
+            self.inner.set_status(NodeStatus::Ok);
+        } else {
+            debug!("Drop duplicate connection for network node: {}", self.inner.address());
+            conn.worker.do_send(msgs::StopWorker);
+        }
     }
+}
 
-    /// This is main event loop for server responses
-    fn handle(&mut self, msg: Response, _ctx: &mut Self::Context) {
+/// This is main event loop for responses from remote node
+impl Handler<Response> for NetworkNode {
+    type Result = ();
+
+    fn handle(&mut self, msg: Response, _: &mut Self::Context) {
         match msg {
-            Response::Supported(types) => {
-                self.world.do_send(msgs::NodeSupportedTypes {
-                    node: self.inner.address().to_string(),
-                    types: types
-                });
-            },
             Response::Result(id, data) => {
                 if let Some(tx) = self.requests.remove(&id) {
                     debug!("GOT REMOTE RESULT: {:?} {:?}", id, data);
-                    let _ = tx.send(data);
+                    let _ = tx.send(Ok(data));
+                }
+            },
+            Response::Error(id, code) => {
+                if let Some(tx) = self.requests.remove(&id) {
+                    error!("Remote request failed: {:?} code: {:?}", id, code);
+                    let _ = tx.send(Err(RemoteError::from_code(code)));
                 }
             },
             _ => (),
@@ -188,26 +307,130 @@ impl StreamHandler<Response, io::Error> for NetworkNode
     }
 }
 
-/// Reconnect node if required
-impl Handler<msgs::ReconnectNode> for NetworkNode {
+/// New connection to remote node
+impl Handler<msgs::AttachConnection> for NetworkNode {
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::AttachConnection, ctx: &mut Context<Self>) {
+        self.attach(msg.id, msg.conn, ctx);
+    }
+}
+
+/// Connection is established but world failed to start it
+impl Handler<msgs::ConnectFailed> for NetworkNode {
+    type Result = ();
+
+    fn handle(&mut self, _: msgs::ConnectFailed, ctx: &mut Context<Self>) {
+        if self.conn.is_none() {
+            self.restart(None, ctx);
+        }
+    }
+}
+
+/// Connection is closed, re-connect if it is current connection
+impl Handler<msgs::ConnectionClosed> for NetworkNode {
     type Result = ();
 
-    fn handle(&mut self, _: msgs::ReconnectNode, ctx: &mut Context<Self>) {
-        self.stop_actor(ctx);
+    fn handle(&mut self, msg: msgs::ConnectionClosed, ctx: &mut Context<Self>) {
+        let current = self.conn.as_ref().map(|c| c.id == msg.0).unwrap_or(false);
+        if current {
+            error!("Network node has been disconnected: {}", self.inner.address());
+            self.conn.take();
+            // node can come back with different incarnation, world
+            // forgets node until it completes handshake again
+            if let Some(id) = self.id {
+                self.world.do_send(msgs::NodeUnreachable(id));
+                self.world.do_send(msgs::NodeGone(id));
+            }
+            if self.reconnect {
+                self.restart(None, ctx);
+            } else {
+                self.requests.clear();
+                self.inner.set_status(NodeStatus::Failed);
+                ctx.stop();
+            }
+        }
+    }
+}
+
+
+/// Node of stale incarnation, node dialed by world attaches
+/// connection of new incarnation, node of inbound connection stops
+impl Handler<msgs::StopNode> for NetworkNode {
+    type Result = ();
+
+    fn handle(&mut self, _: msgs::StopNode, ctx: &mut Context<Self>) {
+        if self.reconnect {
+            return
+        }
+        if let Some(conn) = self.conn.take() {
+            conn.worker.do_send(msgs::StopWorker);
+        }
+        self.requests.clear();
+        self.inner.set_status(NodeStatus::Failed);
+        ctx.stop();
     }
 }
 
+/// Node of inbound connection is replaced by dialed node
+impl Handler<msgs::MoveConnection> for NetworkNode {
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::MoveConnection, ctx: &mut Context<Self>) {
+        if let (Some(id), Some(conn)) = (self.id, self.conn.take()) {
+            msg.0.do_send(msgs::AttachConnection{id: id, conn: conn});
+        }
+        // pending requests can not be moved, callers see disconnect
+        self.requests.clear();
+        self.inner.set_status(NodeStatus::Failed);
+        ctx.stop();
+    }
+}
 
 /// Send remote mesage
 impl Handler<msgs::SendRemoteMessage> for NetworkNode {
     type Result = ActixResponse<String, io::Error>;
 
     fn handle(&mut self, msg: msgs::SendRemoteMessage, _: &mut Context<Self>) -> Self::Result {
-        if let Some(ref mut framed) = self.framed {
+        if let Some(ref conn) = self.conn {
             self.mid += 1;
             self.requests.insert(self.mid, msg.tx);
-            framed.write(Request::Message(self.mid, msg.type_id, "1.0".to_string(), msg.data));
+            let ver = protocol::format_version(msg.version, msg.fingerprint);
+            conn.worker.do_send(match msg.actor {
+                Some(actor) => Request::ActorMessage(
+                    self.mid, actor, msg.type_id, ver, msg.data),
+                None => Request::Message(self.mid, msg.type_id, ver, msg.data),
+            });
+        } else {
+            let _ = msg.tx.send(Err(RemoteError::Disconnected));
         }
         ActixResponse::reply(Err(io::Error::new(io::ErrorKind::Other, "test")))
     }
 }
+
+/// Send request over current connection, request is dropped
+/// if node is disconnected
+impl Handler<Request> for NetworkNode {
+    type Result = ();
+
+    fn handle(&mut self, msg: Request, _: &mut Context<Self>) {
+        if let Some(ref conn) = self.conn {
+            conn.worker.do_send(msg);
+        }
+    }
+}
+
+/// Start actor on remote node
+impl Handler<msgs::SpawnRemote> for NetworkNode {
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::SpawnRemote, _: &mut Context<Self>) {
+        if let Some(ref conn) = self.conn {
+            self.mid += 1;
+            self.requests.insert(self.mid, msg.tx);
+            conn.worker.do_send(Request::Spawn(self.mid, msg.kind, msg.args));
+        } else {
+            let _ = msg.tx.send(Err(RemoteError::Disconnected));
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use node::Peer;
+
+
+/// Authorization policy for remote message type
+///
+/// Policy is checked for every incoming message, message types that are
+/// not allowed for the peer are not announced to the peer.
+#[derive(Clone)]
+pub struct Policy {
+    check: Arc<Fn(&Peer) -> bool + Send + Sync>,
+}
+
+impl Policy {
+    /// Allow all peers
+    pub fn allow_all() -> Policy {
+        Policy::custom(|_| true)
+    }
+
+    /// Allow nodes with specified uuids
+    pub fn nodes<I: IntoIterator<Item=Uuid>>(nodes: I) -> Policy {
+        let nodes: HashSet<Uuid> = nodes.into_iter().collect();
+        Policy::custom(move |peer| nodes.contains(&peer.id.uuid))
+    }
+
+    /// Allow nodes with any of specified roles
+    ///
+    /// Roles are declared by the peer itself, this policy only makes
+    /// sense if `World::secret()` or TLS client authentication is enabled.
+    pub fn roles<I, S>(roles: I) -> Policy
+        where I: IntoIterator<Item=S>, S: Into<String>
+    {
+        let roles: HashSet<String> = roles.into_iter().map(|r| r.into()).collect();
+        Policy::custom(move |peer| peer.roles.iter().any(|r| roles.contains(r)))
+    }
+
+    /// Custom predicate over peer identity
+    pub fn custom<F>(f: F) -> Policy where F: Fn(&Peer) -> bool + Send + Sync + 'static {
+        Policy{check: Arc::new(f)}
+    }
+
+    /// Check if peer is allowed
+    pub fn allows(&self, peer: &Peer) -> bool {
+        (self.check)(peer)
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Policy {
+        Policy::allow_all()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use node::NodeId;
+    use super::*;
+
+    fn peer(roles: &[&str]) -> Peer {
+        Peer{id: NodeId::new(Uuid::new_v4()), addr: "mem://peer".to_owned(),
+             roles: roles.iter().map(|r| r.to_string()).collect(), certificates: Vec::new()}
+    }
+
+    #[test]
+    fn test_nodes() {
+        let (a, b) = (peer(&[]), peer(&[]));
+        let policy = Policy::nodes(vec![a.id.uuid]);
+        assert!(policy.allows(&a));
+        assert!(!policy.allows(&b));
+        assert!(!Policy::nodes(Vec::new()).allows(&a));
+    }
+
+    #[test]
+    fn test_roles() {
+        let policy = Policy::roles(vec!["admin", "ops"]);
+        assert!(policy.allows(&peer(&["ops"])));
+        assert!(policy.allows(&peer(&["user", "admin"])));
+        assert!(!policy.allows(&peer(&["user"])));
+        assert!(!policy.allows(&peer(&[])));
+    }
+
+    #[test]
+    fn test_custom() {
+        let policy = Policy::custom(|peer| !peer.certificates.is_empty());
+        let mut p = peer(&[]);
+        assert!(!policy.allows(&p));
+        p.certificates.push(vec![1]);
+        assert!(policy.allows(&p));
+        assert!(Policy::default().allows(&peer(&[])));
+    }
+}
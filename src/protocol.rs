@@ -1,169 +1,457 @@
-use std::io;
+use std::{cmp, io, str};
+use std::cell::Cell;
+use std::rc::Rc;
 use serde_json as json;
 use byteorder::{NetworkEndian , ByteOrder};
 use bytes::{BytesMut, BufMut};
 use tokio_io::codec::{Encoder, Decoder};
 
-const PREFIX: &[u8] = b"ACTIX/1.0\r\n";
+use compress::{self, Compression};
+use node::NodeId;
 
+const PREFIX: &[u8] = b"ACTIX/";
+/// Maximum length of the connection prefix line
+const MAX_PREFIX: usize = 32;
+/// Frame format version, sent in connection prefix line `ACTIX/2.0`.
+///
+/// Format 1 is 2 bytes length and payload, format 2 is 4 bytes length,
+/// 1 byte compression flag and payload. Nodes with different frame
+/// format can not communicate.
+pub const FRAME_FORMAT: u16 = 2;
+/// Maximum size of the frame, compressed or decompressed
+pub const MAX_FRAME_SIZE: usize = 64 * 1024 * 1024;
+/// Maximum size of message payload, leaves room for frame envelope
+/// and for escaping of the payload in the envelope
+pub const MAX_PAYLOAD_SIZE: usize = MAX_FRAME_SIZE / 8;
 
-/// Client request
+/// Protocol versions supported by this node, in ascending order.
+///
+/// All versions use frame format 2, protocol version is negotiated
+/// in handshake after prefix line. Version 2 announces supported versions of message types,
+/// version 3 adds messages for specific actors, version 4 announces
+/// named actors, version 5 starts actors on remote node, version 6
+/// watches remote actors, version 7 adds publish/subscribe topics,
+/// version 8 elects role leaders, version 9 stops spawned actors.
+pub const PROTOCOL_VERSIONS: &[u16] = &[1, 2, 3, 4, 5, 6, 7, 8, 9];
+/// Default serializer of message payloads
+pub const SERIALIZER_JSON: &str = "json";
+
+/// Error code, message type is not supported
+pub const ERROR_NOT_SUPPORTED: u16 = 1;
+/// Error code, peer is not allowed to send message type
+pub const ERROR_PERMISSION_DENIED: u16 = 2;
+/// Error code, message version is not supported
+pub const ERROR_VERSION_MISMATCH: u16 = 3;
+/// Error code, actor is not registered on the node
+pub const ERROR_ACTOR_NOT_FOUND: u16 = 4;
+/// Error code, actor can not be started with provided arguments
+pub const ERROR_SPAWN_FAILED: u16 = 5;
+/// Error code, recipient mailbox is closed
+pub const ERROR_STOPPED: u16 = 6;
+/// Error code, message or result exceeds `MAX_PAYLOAD_SIZE`
+pub const ERROR_TOO_LARGE: u16 = 7;
+/// Error code, message can not be decoded or result can not be encoded
+pub const ERROR_DECODE: u16 = 8;
+/// Error code, message fingerprint does not match provider type
+pub const ERROR_TYPE_MISMATCH: u16 = 9;
+/// Error code, recipient dropped message without reply
+pub const ERROR_UNAVAILABLE: u16 = 10;
+
+
+/// Node introduction, first request on the connection
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Handshake {
+    /// Node identity
+    pub id: NodeId,
+    /// Node address
+    pub addr: String,
+    /// Authentication challenge, peer has to answer with `Request::Auth`
+    #[serde(default)]
+    pub challenge: Option<String>,
+    /// Node roles
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// Supported protocol versions
+    #[serde(default="default_versions")]
+    pub versions: Vec<u16>,
+    /// Node capabilities
+    #[serde(default)]
+    pub capabilities: Capabilities,
+}
+
+/// Optional protocol features supported by node
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Capabilities {
+    /// Payload serializers
+    #[serde(default="default_serializers")]
+    pub serializers: Vec<String>,
+    /// Compression algorithms accepted by node
+    #[serde(default)]
+    pub compression: Vec<String>,
+    /// Node answers `Ping` requests
+    #[serde(default)]
+    pub heartbeat: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Capabilities {
+        Capabilities{serializers: default_serializers(),
+                     compression: Vec::new(), heartbeat: false}
+    }
+}
+
+/// Nodes that do not announce versions speak version 1, still with
+/// frame format 2, nodes with frame format 1 are rejected before handshake
+fn default_versions() -> Vec<u16> {
+    vec![1]
+}
+
+fn default_serializers() -> Vec<String> {
+    vec![SERIALIZER_JSON.to_owned()]
+}
+
+/// Message type supported by node
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TypeInfo {
+    pub type_id: String,
+    /// Lowest supported message version
+    pub min_version: u32,
+    /// Highest supported message version
+    pub max_version: u32,
+    /// Message type fingerprint, `0` if unknown
+    #[serde(default)]
+    pub fingerprint: u64,
+}
+
+/// Named actor registered on node
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NameInfo {
+    pub name: String,
+    /// Message type accepted by the actor
+    pub type_id: String,
+    /// Actor id on the node
+    pub actor: u64,
+}
+
+/// Parse message version, `1.0` is version 1
+pub fn parse_version(ver: &str) -> Option<u32> {
+    ver.split('.').next().and_then(|v| v.parse().ok())
+}
+
+/// Message version with type fingerprint, `<version>.<hex fingerprint>`
+pub fn format_version(version: u32, fingerprint: u64) -> String {
+    format!("{}.{:x}", version, fingerprint)
+}
+
+/// Type fingerprint of message version, `0` if peer does not send it
+pub fn parse_fingerprint(ver: &str) -> u64 {
+    ver.split('.').nth(1).and_then(|f| u64::from_str_radix(f, 16).ok()).unwrap_or(0)
+}
+
+/// Pick highest protocol version supported by both nodes
+pub fn negotiate_version(local: &[u16], peer: &[u16]) -> Option<u16> {
+    local.iter().filter(|v| peer.contains(v)).max().cloned()
+}
+
+/// Request, can be sent by either side of the connection
 #[derive(Serialize, Deserialize, Debug, Message)]
 #[serde(tag="cmd", content="data")]
 pub enum Request {
-    Handshake(String),
+    Handshake(Handshake),
+    /// Answer to handshake challenge
+    Auth(String),
+    /// Connection is rejected, with reason
+    Reject(String),
     Ping,
     Pong,
     /// Message(msg_id, type_id, ver, payload)
     Message(u64, String, String, String),
+    /// ActorMessage(msg_id, actor_id, type_id, ver, payload), protocol version 3
+    ActorMessage(u64, u64, String, String, String),
+    /// Complete list of named actors of the node, protocol version 4
+    Names(Vec<NameInfo>),
+    /// Spawn(msg_id, kind, args), result is actor id, protocol version 5
+    Spawn(u64, String, String),
+    /// Watch(actor_id), peer is notified when actor stops, protocol version 6
+    Watch(u64),
+    /// Terminated(actor_id), watched actor is stopped, protocol version 6
+    Terminated(u64),
+    /// Complete list of topics with subscribers on the node, protocol version 7
+    Topics(Vec<String>),
+    /// Publish(topic, type_id, ver, payload), protocol version 7
+    Publish(String, String, String, String),
+    /// Leader(role, term), node is leader of the role, protocol version 8
+    Leader(String, u64),
+    /// Stop(actor_id), stop actor spawned by the node, protocol version 9
+    Stop(u64),
 }
 
-/// Server response
+/// Response, can be sent by either side of the connection
 #[derive(Serialize, Deserialize, Debug, Message)]
 #[serde(tag="cmd", content="data")]
 pub enum Response {
-    Handshake,
     Ping,
     Pong,
     /// Announce supported message types
     Supported(Vec<String>),
+    /// Announce supported message types with versions, protocol version 2
+    Types(Vec<TypeInfo>),
     /// Response(msg_id, payload)
     Result(u64, String),
     /// Error(msg_id, error-code)
     Error(u64, u16),
 }
 
-/// Codec for Client -> Server transport
-pub struct NetworkServerCodec {
-    prefix: bool
+/// Single frame of the multiplexed connection
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag="kind", content="frame")]
+pub enum Frame {
+    Request(Request),
+    Response(Response),
+}
+
+impl From<Request> for Frame {
+    fn from(req: Request) -> Frame {
+        Frame::Request(req)
+    }
+}
+
+impl From<Response> for Frame {
+    fn from(resp: Response) -> Frame {
+        Frame::Response(resp)
+    }
+}
+
+/// Outgoing frames compression, algorithm and minimal frame size
+pub type CompressionCell = Rc<Cell<Option<(Compression, usize)>>>;
+
+/// Codec for node to node transport
+///
+/// Connection is symmetric, both sides send `ACTIX/<frame format>.0` prefix
+/// line before first frame and both sides can send requests and responses.
+/// Protocol version is negotiated with handshake, prefix identifies
+/// actix remote connection and frame format.
+/// Frame is 4 bytes length, 1 byte compression flag and payload,
+/// frames larger than `MAX_FRAME_SIZE` are rejected.
+pub struct NetworkCodec {
+    prefix: bool,
+    handshake: bool,
+    compression: CompressionCell,
 }
 
-impl Default for NetworkServerCodec {
-    fn default() -> NetworkServerCodec {
-        NetworkServerCodec{prefix: false}
+impl NetworkCodec {
+    /// Codec that compresses outgoing frames once compression is set
+    pub fn with_compression(compression: CompressionCell) -> NetworkCodec {
+        NetworkCodec{prefix: false, handshake: false, compression: compression}
     }
 }
 
-impl Decoder for NetworkServerCodec
+impl Default for NetworkCodec {
+    fn default() -> NetworkCodec {
+        NetworkCodec::with_compression(Rc::new(Cell::new(None)))
+    }
+}
+
+impl Decoder for NetworkCodec
 {
-    type Item = Request;
+    type Item = Frame;
     type Error = io::Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
         if !self.prefix {
-            if src.len() < 11 {
-                return Ok(None)
+            let len = cmp::min(src.len(), PREFIX.len());
+            if src[..len] != PREFIX[..len] {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other, "Peer is not actix remote node"))
             }
-            if &src[..11] == PREFIX {
-                src.split_to(11);
-                self.prefix = true;
-            } else {
-                return Err(io::Error::new(io::ErrorKind::Other, "Prefix mismatch"))
+            match src.windows(2).position(|w| w == b"\r\n") {
+                Some(pos) => {
+                    let line = src.split_to(pos + 2);
+                    let format = str::from_utf8(&line[PREFIX.len()..pos]).ok()
+                        .and_then(parse_version);
+                    if format != Some(u32::from(FRAME_FORMAT)) {
+                        return Err(io::Error::new(
+                            io::ErrorKind::Other, format!(
+                                "Unsupported frame format: {}",
+                                String::from_utf8_lossy(&line[..pos]))))
+                    }
+                    self.prefix = true;
+                },
+                None if src.len() > MAX_PREFIX => return Err(io::Error::new(
+                    io::ErrorKind::Other, "Peer is not actix remote node")),
+                None => return Ok(None),
             }
         }
-        
+
         let size = {
-            if src.len() < 2 {
+            if src.len() < 5 {
                 return Ok(None)
             }
-            NetworkEndian::read_u16(src.as_ref()) as usize
+            NetworkEndian::read_u32(src.as_ref()) as usize
         };
+        if size > MAX_FRAME_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData, format!("Frame is too large: {}", size)))
+        }
 
-        if src.len() >= size + 2 {
-            src.split_to(2);
+        if src.len() >= size + 5 {
+            let flag = src.split_to(5)[4];
             let buf = src.split_to(size);
-            Ok(Some(json::from_slice::<Request>(&buf)?))
+            if flag == compress::FLAG_NONE {
+                Ok(Some(json::from_slice::<Frame>(&buf)?))
+            } else if let Some(compression) = Compression::from_flag(flag) {
+                let buf = compression.decompress(&buf, MAX_FRAME_SIZE)?;
+                Ok(Some(json::from_slice::<Frame>(&buf)?))
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::Other, format!("Unsupported compression: {}", flag)))
+            }
         } else {
             Ok(None)
         }
     }
 }
 
-impl Encoder for NetworkServerCodec
+impl Encoder for NetworkCodec
 {
-    type Item = Response;
+    type Item = Frame;
     type Error = io::Error;
 
-    fn encode(&mut self, msg: Response, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        match msg {
-            Response::Handshake => dst.extend_from_slice(PREFIX),
-            _ => {
-                let msg = json::to_string(&msg).unwrap();
-                let msg_ref: &[u8] = msg.as_ref();
+    fn encode(&mut self, msg: Frame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        if !self.handshake {
+            self.handshake = true;
+            dst.extend_from_slice(format!("ACTIX/{}.0\r\n", FRAME_FORMAT).as_bytes());
+        }
 
-                dst.reserve(msg_ref.len() + 2);
-                dst.put_u16::<NetworkEndian>(msg_ref.len() as u16);
-                dst.put(msg_ref);
-            }
+        let msg = json::to_vec(&msg).unwrap();
+        if msg.len() > MAX_FRAME_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData, format!("Frame is too large: {}", msg.len())))
         }
+        let (flag, msg) = match self.compression.get() {
+            Some((compression, threshold)) if msg.len() >= threshold => {
+                let compressed = compression.compress(&msg)?;
+                if compressed.len() < msg.len() {
+                    (compression.flag(), compressed)
+                } else {
+                    (compress::FLAG_NONE, msg)
+                }
+            },
+            _ => (compress::FLAG_NONE, msg),
+        };
 
+        dst.reserve(msg.len() + 5);
+        dst.put_u32_be(msg.len() as u32);
+        dst.put_u8(flag);
+        dst.put(&msg[..]);
         Ok(())
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use bytes::{BufMut, BytesMut};
+    use tokio_io::codec::{Decoder, Encoder};
 
-/// Codec for Server -> Client transport
-pub struct NetworkClientCodec {
-    prefix: bool,
-}
+    use super::*;
 
-impl Default for NetworkClientCodec {
-    fn default() -> NetworkClientCodec {
-        NetworkClientCodec{prefix: false}
+    fn encode(frames: Vec<Frame>) -> BytesMut {
+        let mut codec = NetworkCodec::default();
+        let mut buf = BytesMut::new();
+        for frame in frames {
+            codec.encode(frame, &mut buf).unwrap();
+        }
+        buf
     }
-}
 
-impl Decoder for NetworkClientCodec
-{
-    type Item = Response;
-    type Error = io::Error;
+    #[test]
+    fn test_codec() {
+        let mut buf = encode(vec![
+            Request::Ping.into(),
+            Request::Message(1, "type".to_owned(), "1.0".to_owned(), "{}".to_owned()).into(),
+            Response::Error(1, ERROR_STOPPED).into()]);
+        assert!(buf.starts_with(b"ACTIX/2.0\r\n"));
 
-    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        if !self.prefix {
-            if src.len() < 11 {
-                return Ok(None)
-            }
-            if &src[..11] == PREFIX {
-                src.split_to(11);
-                self.prefix = true;
-            } else {
-                return Err(io::Error::new(io::ErrorKind::Other, "Prefix mismatch"))
-            }
+        let mut codec = NetworkCodec::default();
+        match codec.decode(&mut buf).unwrap() {
+            Some(Frame::Request(Request::Ping)) => (),
+            frame => panic!("Unexpected frame: {:?}", frame),
+        }
+        match codec.decode(&mut buf).unwrap() {
+            Some(Frame::Request(Request::Message(1, ref type_id, ref ver, ref data))) => {
+                assert_eq!(type_id, "type");
+                assert_eq!(ver, "1.0");
+                assert_eq!(data, "{}");
+            },
+            frame => panic!("Unexpected frame: {:?}", frame),
         }
+        match codec.decode(&mut buf).unwrap() {
+            Some(Frame::Response(Response::Error(1, ERROR_STOPPED))) => (),
+            frame => panic!("Unexpected frame: {:?}", frame),
+        }
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+    }
 
-        let size = {
-            if src.len() < 2 {
-                return Ok(None)
-            }
-            NetworkEndian::read_u16(src.as_ref()) as usize
-        };
+    #[test]
+    fn test_partial_frame() {
+        let buf = encode(vec![Request::Ping.into()]);
 
-        if src.len() >= size + 2 {
-            src.split_to(2);
-            let buf = src.split_to(size);
-            Ok(Some(json::from_slice::<Response>(&buf)?))
-        } else {
-            Ok(None)
+        let mut codec = NetworkCodec::default();
+        let mut partial = BytesMut::new();
+        for b in &buf[..buf.len()-1] {
+            partial.extend_from_slice(&[*b]);
+            assert!(codec.decode(&mut partial).unwrap().is_none());
         }
+        partial.extend_from_slice(&buf[buf.len()-1..]);
+        assert!(codec.decode(&mut partial).unwrap().is_some());
     }
-}
 
-impl Encoder for NetworkClientCodec
-{
-    type Item = Request;
-    type Error = io::Error;
+    #[test]
+    fn test_prefix() {
+        let mut buf = BytesMut::from(&b"GET / HTTP/1.1\r\n"[..]);
+        assert!(NetworkCodec::default().decode(&mut buf).is_err());
 
-    fn encode(&mut self, msg: Request, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        if let Request::Handshake(_) = msg {
-            dst.extend_from_slice(PREFIX);
-        }
+        // frame format 1 is not supported
+        let mut buf = BytesMut::from(&b"ACTIX/1.0\r\n\x00\x02{}"[..]);
+        assert!(NetworkCodec::default().decode(&mut buf).is_err());
 
-        let msg = json::to_string(&msg).unwrap();
-        let msg_ref: &[u8] = msg.as_ref();
+        let mut buf = BytesMut::from(vec![b'A'; MAX_PREFIX + 1]);
+        assert!(NetworkCodec::default().decode(&mut buf).is_err());
+    }
 
-        dst.reserve(msg_ref.len() + 2);
-        dst.put_u16::<NetworkEndian>(msg_ref.len() as u16);
-        dst.put(msg_ref);
-        Ok(())
+    #[test]
+    fn test_frame_too_large() {
+        let mut buf = BytesMut::from(&b"ACTIX/2.0\r\n"[..]);
+        buf.reserve(5);
+        buf.put_u32_be(MAX_FRAME_SIZE as u32 + 1);
+        buf.put_u8(compress::FLAG_NONE);
+        assert!(NetworkCodec::default().decode(&mut buf).is_err());
+
+        let data = String::from_utf8(vec![b'a'; MAX_FRAME_SIZE]).unwrap();
+        let frame = Request::Message(1, "type".to_owned(), "1.0".to_owned(), data);
+        assert!(NetworkCodec::default().encode(frame.into(), &mut BytesMut::new()).is_err());
+    }
+
+    #[test]
+    fn test_unsupported_compression() {
+        let mut buf = BytesMut::from(&b"ACTIX/2.0\r\n"[..]);
+        buf.reserve(7);
+        buf.put_u32_be(2);
+        buf.put_u8(255);
+        buf.put_slice(b"{}");
+        assert!(NetworkCodec::default().decode(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("1.0"), Some(1));
+        assert_eq!(parse_version("7"), Some(7));
+        assert_eq!(parse_version("x.0"), None);
+        assert_eq!(parse_version(&format_version(3, 0xabc)), Some(3));
+        assert_eq!(parse_fingerprint(&format_version(3, 0xabc)), 0xabc);
+        assert_eq!(parse_fingerprint("1.0"), 0);
+        assert_eq!(parse_fingerprint("1"), 0);
+        assert_eq!(negotiate_version(&[1, 2, 3], &[2, 3, 4]), Some(3));
+        assert_eq!(negotiate_version(&[1], &[2]), None);
     }
 }
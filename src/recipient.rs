@@ -1,5 +1,6 @@
 #![allow(dead_code, unused_variables)]
 use std::marker::PhantomData;
+use std::sync::Arc;
 use std::collections::HashMap;
 
 use serde::Serialize;
@@ -10,13 +11,70 @@ use futures::unsync::oneshot::{self, Sender};
 
 use actix::prelude::*;
 use actix::dev::{MessageResponse, ResponseChannel, SendError};
+use actix::MailboxError;
 
 use msgs;
-use node::NetworkNode;
-use remote::{Remote, RemoteMessage};
+use actor;
+use error::RemoteError;
+use migration::Migrations;
+use policy::Policy;
+use node::{NetworkNode, NodeId, Peer};
+use protocol;
+use world::World;
+use remote::{FromPeer, Remote, RemoteMessage};
 
 pub trait RemoteMessageHandler: Send + Sync {
-    fn handle(&self, msg: String, sender: Sender<String>);
+    /// Check if peer is allowed to send messages to this handler
+    fn allows(&self, peer: &Peer) -> bool;
+
+    /// Supported message versions, lowest and highest
+    fn versions(&self) -> (u32, u32);
+
+    /// Message type fingerprint
+    fn fingerprint(&self) -> u64;
+
+    /// Handle message of specified version.
+    ///
+    /// Handler replies with serialized result or protocol error code,
+    /// returns error code if message can not be decoded.
+    fn handle(&self, peer: &Peer, version: u32, msg: String,
+              sender: Sender<Result<String, u16>>) -> Result<(), u16>;
+}
+
+/// Check peer permissions, message version and fingerprint,
+/// pass message to handler.
+///
+/// Actor references in the message are bound to `world`. Sender or
+/// handler fingerprint `0` is unknown and is not checked. Returns
+/// protocol error code if message can not be handled.
+pub(crate) fn deliver(world: Option<&Addr<Syn, World>>, handler: &Arc<RemoteMessageHandler>,
+                      peer: &Peer, version: u32, fingerprint: u64, msg: String,
+                      sender: Sender<Result<String, u16>>) -> Result<(), u16>
+{
+    if !handler.allows(peer) {
+        return Err(protocol::ERROR_PERMISSION_DENIED)
+    }
+    let (min, max) = handler.versions();
+    if version < min || version > max {
+        return Err(protocol::ERROR_VERSION_MISMATCH)
+    }
+    // fingerprint describes highest supported version, older versions are migrated
+    let expected = handler.fingerprint();
+    if fingerprint != 0 && expected != 0 && version == max && fingerprint != expected {
+        return Err(protocol::ERROR_TYPE_MISMATCH)
+    }
+    actor::with_world(world, peer.id, || handler.handle(peer, version, msg, sender))
+}
+
+/// Serialize result for reply channel
+pub(crate) fn reply<R: Serialize>(res: Result<R, MailboxError>) -> Result<String, u16> {
+    match res {
+        Ok(res) => serde_json::to_string(&res).map_err(|err| {
+            warn!("Can not encode result: {}", err);
+            protocol::ERROR_DECODE
+        }),
+        Err(_) => Err(protocol::ERROR_STOPPED),
+    }
 }
 
 /// Remote message handler
@@ -26,24 +84,142 @@ struct Provider<M>
           M::Result: Send + Serialize + DeserializeOwned
 {
     pub recipient: Recipient<Syn, M>,
+    pub policy: Policy,
+    pub versions: (u32, u32),
+    pub migrations: Arc<Migrations>,
 }
 
 impl<M> RemoteMessageHandler for Provider<M>
     where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
 {
-    fn handle(&self, msg: String, sender: Sender<String>) {
-        let msg = serde_json::from_slice::<M>(msg.as_ref()).unwrap();
+    fn allows(&self, peer: &Peer) -> bool {
+        self.policy.allows(peer)
+    }
+
+    fn versions(&self) -> (u32, u32) {
+        self.versions
+    }
+
+    fn fingerprint(&self) -> u64 {
+        M::fingerprint()
+    }
+
+    fn handle(&self, peer: &Peer, version: u32, msg: String,
+              sender: Sender<Result<String, u16>>) -> Result<(), u16>
+    {
+        let msg = self.migrations.decode::<M>(version, M::version(), &msg).map_err(|err| {
+            warn!("Can not decode message {} version {}: {}", M::type_id(), version, err);
+            err.to_code()
+        })?;
+        let migrations = Arc::clone(&self.migrations);
         Arbiter::handle().spawn(
-            self.recipient.send(msg).then(|res| {
-                match res {
-                    Ok(res) => {
-                        let body = serde_json::to_string(&res).unwrap();
-                        let _ = sender.send(body);
-                    },
-                    Err(e) => (),
-                }
+            self.recipient.send(msg).then(move |res| {
+                let _ = sender.send(match res {
+                    Ok(res) => migrations.encode(version, M::version(), &res).map_err(|err| {
+                        warn!("Can not encode result of {} version {}: {}",
+                              M::type_id(), version, err);
+                        err.to_code()
+                    }),
+                    Err(_) => Err(protocol::ERROR_STOPPED),
+                });
                 Ok::<_, ()>(())
-            }))
+            }));
+        Ok(())
+    }
+}
+
+/// Remote message handler, recipient receives peer information
+pub(crate)
+struct PeerProvider<M>
+    where M: RemoteMessage + 'static,
+          M::Result: Send + Serialize + DeserializeOwned
+{
+    pub recipient: Recipient<Syn, FromPeer<M>>,
+    pub policy: Policy,
+    pub versions: (u32, u32),
+}
+
+impl<M> RemoteMessageHandler for PeerProvider<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    fn allows(&self, peer: &Peer) -> bool {
+        self.policy.allows(peer)
+    }
+
+    fn versions(&self) -> (u32, u32) {
+        self.versions
+    }
+
+    fn fingerprint(&self) -> u64 {
+        M::fingerprint()
+    }
+
+    fn handle(&self, peer: &Peer, version: u32, msg: String,
+              sender: Sender<Result<String, u16>>) -> Result<(), u16>
+    {
+        let msg = serde_json::from_str::<M>(&msg).map_err(|err| {
+            warn!("Can not decode message {}: {}", M::type_id(), err);
+            protocol::ERROR_DECODE
+        })?;
+        Arbiter::handle().spawn(
+            self.recipient.send(FromPeer{peer: peer.clone(), msg: msg}).then(|res| {
+                let _ = sender.send(reply(res));
+                Ok::<_, ()>(())
+            }));
+        Ok(())
+    }
+}
+
+/// Remote message handler for actor that routes messages of the type,
+/// message is passed to the actor with peer information
+pub(crate)
+struct ForwardHandler<M>
+    where M: RemoteMessage + 'static,
+          M::Result: Send + Serialize + DeserializeOwned
+{
+    pub recipient: Recipient<Syn, msgs::Forwarded<M>>,
+    pub policy: Policy,
+    pub versions: (u32, u32),
+    pub migrations: Arc<Migrations>,
+}
+
+impl<M> RemoteMessageHandler for ForwardHandler<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    fn allows(&self, peer: &Peer) -> bool {
+        self.policy.allows(peer)
+    }
+
+    fn versions(&self) -> (u32, u32) {
+        self.versions
+    }
+
+    fn fingerprint(&self) -> u64 {
+        M::fingerprint()
+    }
+
+    fn handle(&self, peer: &Peer, version: u32, msg: String,
+              sender: Sender<Result<String, u16>>) -> Result<(), u16>
+    {
+        let msg = self.migrations.decode::<M>(version, M::version(), &msg).map_err(|err| {
+            warn!("Can not decode message {} version {}: {}", M::type_id(), version, err);
+            err.to_code()
+        })?;
+        let migrations = Arc::clone(&self.migrations);
+        Arbiter::handle().spawn(
+            self.recipient.send(msgs::Forwarded{msg: msg, peer: peer.clone()}).then(move |res| {
+                let _ = sender.send(match res {
+                    Ok(Ok(res)) => migrations.encode(version, M::version(), &res).map_err(|err| {
+                        warn!("Can not encode result of {} version {}: {}",
+                              M::type_id(), version, err);
+                        err.to_code()
+                    }),
+                    Ok(Err(err)) => Err(err.to_code()),
+                    Err(_) => Err(protocol::ERROR_STOPPED),
+                });
+                Ok::<_, ()>(())
+            }));
+        Ok(())
     }
 }
 
@@ -54,7 +230,15 @@ struct RecipientProxy<M>
           M::Result: Send + Serialize + DeserializeOwned
 {
     m: PhantomData<M>,
-    nodes: HashMap<String, Addr<Unsync, NetworkNode>>,
+    nodes: HashMap<NodeId, ProviderNode>,
+    world: Option<Addr<Syn, World>>,
+}
+
+/// Remote node that provides message type
+struct ProviderNode {
+    node: Addr<Unsync, NetworkNode>,
+    versions: (u32, u32),
+    fingerprint: u64,
 }
 
 impl<M> RecipientProxy<M>
@@ -62,7 +246,7 @@ impl<M> RecipientProxy<M>
           M::Result: Send + Serialize + DeserializeOwned
 {
     pub fn new() -> Self {
-        RecipientProxy{m: PhantomData, nodes: HashMap::new()}
+        RecipientProxy{m: PhantomData, nodes: HashMap::new(), world: None}
     }
 }
 
@@ -79,22 +263,52 @@ impl<M> msgs::NodeOperations for RecipientProxy<M>
           M::Result: Send + Serialize + DeserializeOwned {}
 
 /// Handler for proxied message
-impl<M> Handler<M> for RecipientProxy<M>
+impl<M> Handler<msgs::ProxyMessage<M>> for RecipientProxy<M>
     where M: RemoteMessage + 'static,
           M::Result: Send + Serialize + DeserializeOwned
 {
     type Result = RecipientProxyResult<M>;
 
-    fn handle(&mut self, msg: M, ctx: &mut Context<Self>) -> RecipientProxyResult<M> {
+    fn handle(&mut self, msg: msgs::ProxyMessage<M>, ctx: &mut Context<Self>)
+              -> RecipientProxyResult<M>
+    {
         let (tx, rx) = oneshot::channel();
-        let body = serde_json::to_string(&msg).unwrap();
+        let body = serde_json::to_string(&msg.0).unwrap();
+
+        // route to node with same message type that supports message version
+        let version = M::version();
+        let fingerprint = M::fingerprint();
+        let same_type = |p: &&ProviderNode| {
+            p.fingerprint == 0 || fingerprint == 0 || p.fingerprint == fingerprint};
+        let node = self.nodes.iter().filter(|&(_, p)| same_type(&p))
+            .find(|&(_, p)| p.versions.0 <= version && version <= p.versions.1);
+        let id = node.map(|(id, _)| *id);
 
-        for node in self.nodes.values() {
-            node.do_send(msgs::SendRemoteMessage{
-                type_id: M::type_id().to_string(), data: body, tx: tx});
-            break
+        if let Some((_, p)) = node {
+            p.node.do_send(msgs::SendRemoteMessage{
+                actor: None, type_id: M::type_id().to_string(),
+                version: version, fingerprint: fingerprint, data: body, tx: tx});
+        } else if self.nodes.is_empty() {
+            let _ = tx.send(Err(RemoteError::NotSupported));
+        } else if !self.nodes.values().any(|p| same_type(&p)) {
+            let _ = tx.send(Err(RemoteError::TypeMismatch));
+        } else {
+            let _ = tx.send(Err(RemoteError::VersionMismatch));
         }
-        RecipientProxyResult{m: PhantomData, rx: rx}
+        // results are decoded as data of the provider node
+        RecipientProxyResult{m: PhantomData, rx: rx, world: self.world.clone(), node: id}
+    }
+}
+
+/// World is started, actor references in results are bound to it
+impl<M> Handler<msgs::WorldStarted> for RecipientProxy<M>
+    where M: RemoteMessage + 'static,
+          M::Result: Send + Serialize + DeserializeOwned
+{
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::WorldStarted, ctx: &mut Context<Self>) {
+        self.world = Some(msg.0);
     }
 }
 
@@ -108,11 +322,16 @@ impl<M> Handler<msgs::TypeSupported> for RecipientProxy<M>
     type Result = ();
 
     fn handle(&mut self, msg: msgs::TypeSupported, ctx: &mut Context<Self>) {
-        debug!("Remote provider {} is registerd for {}", msg.node_id, msg.type_id);
-        self.nodes.insert(msg.node_id, msg.node);
+        debug!("Remote provider {} is registerd for {} versions {:?}",
+               msg.node_id, msg.type_id, msg.versions);
+        self.nodes.insert(msg.node_id, ProviderNode{
+            node: msg.node, versions: msg.versions, fingerprint: msg.fingerprint});
     }
 }
 
+/// Handle notification from World, node is gone.
+///
+/// RecipientProxy stops sending messages to the node
 impl<M> Handler<msgs::NodeGone> for RecipientProxy<M>
     where M: RemoteMessage + 'static,
           M::Result: Send + Serialize + DeserializeOwned
@@ -120,7 +339,9 @@ impl<M> Handler<msgs::NodeGone> for RecipientProxy<M>
     type Result = ();
 
     fn handle(&mut self, msg: msgs::NodeGone, ctx: &mut Context<Self>) {
-        unimplemented!()
+        if self.nodes.remove(&msg.0).is_some() {
+            debug!("Remote provider {} is gone for {}", msg.0, M::type_id());
+        }
     }
 }
 
@@ -130,24 +351,33 @@ pub struct RecipientProxyResult<M>
           M::Result: Send + Serialize + DeserializeOwned
 {
     m: PhantomData<M>,
-    rx: oneshot::Receiver<String>,
+    rx: oneshot::Receiver<Result<String, RemoteError>>,
+    world: Option<Addr<Syn, World>>,
+    node: Option<NodeId>,
 }
 
-impl<M> MessageResponse<RecipientProxy<M>, M> for RecipientProxyResult<M>
+impl<M> MessageResponse<RecipientProxy<M>, msgs::ProxyMessage<M>> for RecipientProxyResult<M>
     where M: RemoteMessage + 'static,
           M::Result: Send + Serialize + DeserializeOwned
 {
-    fn handle<R: ResponseChannel<M>>(self, _: &mut Context<RecipientProxy<M>>, tx: Option<R>) {
+    fn handle<R: ResponseChannel<msgs::ProxyMessage<M>>>(
+        self, _: &mut Context<RecipientProxy<M>>, tx: Option<R>)
+    {
+        let (world, node) = (self.world, self.node);
         Arbiter::handle().spawn(
-            self.rx
-                .map_err(|e| ())
-                .and_then(move |msg| {
-                    let msg = serde_json::from_slice::<M::Result>(msg.as_ref()).unwrap();
-                    if let Some(tx) = tx {
-                        let _ = tx.send(msg);
-                    }
-                    Ok(())
-                })
+            self.rx.then(move |res| {
+                let res = match (res, node) {
+                    (Ok(Ok(msg)), Some(node)) =>
+                        actor::decode::<M::Result>(world.as_ref(), node, &msg),
+                    (Ok(Ok(_)), None) => Err(RemoteError::Decode),
+                    (Ok(Err(err)), _) => Err(err),
+                    (Err(_), _) => Err(RemoteError::Disconnected),
+                };
+                if let Some(tx) = tx {
+                    tx.send(res);
+                }
+                Ok(())
+            })
         );
     }
 }
@@ -172,16 +402,19 @@ impl<M> RecipientProxySender<M>
     }
 
     pub fn do_send(&self, msg: M) -> Result<(), SendError<M>> {
-        self.tx.do_send(msg);
+        self.tx.do_send(msgs::ProxyMessage(msg));
         Ok(())
     }
 
     pub fn try_send(&self, msg: M) -> Result<(), SendError<M>> {
-        self.tx.try_send(msg)
+        self.tx.try_send(msgs::ProxyMessage(msg)).map_err(|err| match err {
+            SendError::Full(msg) => SendError::Full(msg.0),
+            SendError::Closed(msg) => SendError::Closed(msg.0),
+        })
     }
 
     pub fn send(&self, msg: M) -> RemoteRecipientRequest<Remote, M> {
-        RemoteRecipientRequest::new(self.tx.send(msg))
+        RemoteRecipientRequest::new(self.tx.send(msgs::ProxyMessage(msg)))
     }
 }
 
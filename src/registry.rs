@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use futures::Future;
+use actix::prelude::*;
+
+use msgs;
+use actor::RemoteRecipient;
+use error::{RegistrationError, RemoteError};
+use migration::Migrations;
+use policy::Policy;
+use recipient::Provider;
+use remote::RemoteMessage;
+use world::World;
+
+
+/// Cluster-wide registry of named actors
+///
+/// Every node announces its named actors to connected nodes. If several
+/// nodes register same name, registration of the oldest node wins
+/// on every node. Registrations of the node are removed when node leaves,
+/// so name resolves to the next registration.
+impl World {
+    /// Register recipient under cluster-wide name.
+    ///
+    /// Returned future fails if name is already registered on this node
+    /// or on older connected node. If older node registers same name later,
+    /// local registration is removed and watchers receive `Terminated`.
+    pub fn register_named<M>(world: &Addr<Syn, World>, name: &str, recipient: Recipient<Syn, M>)
+        -> Box<Future<Item=RemoteRecipient<M>, Error=RegistrationError>>
+        where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+    {
+        let handler = Provider{recipient: recipient, policy: Policy::allow_all(),
+                               versions: (M::version(), M::version()),
+                               migrations: Arc::new(Migrations::new())};
+        let addr = world.clone();
+        Box::new(
+            world.send(msgs::RegisterName{
+                name: name.to_owned(), type_id: M::type_id(), handler: Arc::new(handler)})
+                .then(move |res| match res {
+                    Ok(Ok(id)) => Ok(RemoteRecipient::new(id, addr)),
+                    Ok(Err(err)) => Err(err),
+                    Err(_) => Err(RegistrationError::Stopped),
+                }))
+    }
+
+    /// Remove name registered on this node
+    pub fn unregister_named(world: &Addr<Syn, World>, name: &str) {
+        world.do_send(msgs::UnregisterName(name.to_owned()))
+    }
+
+    /// Resolve name registered on any connected node
+    pub fn lookup<M>(world: &Addr<Syn, World>, name: &str)
+        -> Box<Future<Item=RemoteRecipient<M>, Error=RemoteError>>
+        where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+    {
+        let addr = world.clone();
+        Box::new(
+            world.send(msgs::LookupName{name: name.to_owned(), type_id: M::type_id()})
+                .then(move |res| match res {
+                    Ok(Ok(id)) => Ok(RemoteRecipient::new(id, addr)),
+                    Ok(Err(err)) => Err(err),
+                    Err(_) => Err(RemoteError::Disconnected),
+                }))
+    }
+}
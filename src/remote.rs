@@ -7,19 +7,79 @@ use futures::{Async, Future, Poll};
 use tokio_core::reactor::Timeout;
 
 use actix::prelude::*;
-use actix::dev::{Message, MessageRecipient, SendError, MailboxError};
+use actix::dev::{Message, MessageRecipient, SendError};
 
+use msgs::ProxyMessage;
+use node::Peer;
+use error::RemoteError;
 use recipient::RecipientProxySender;
+use utils;
 
 
 pub trait RemoteMessage: Message + Send + Serialize + DeserializeOwned
     where Self::Result: Send + Serialize + DeserializeOwned
 {
     fn type_id() -> &'static str;
+
+    /// Message schema version.
+    ///
+    /// Message is delivered only to providers that support this version.
+    fn version() -> u32 {
+        1
+    }
+
+    /// Fingerprint of the message type schema.
+    ///
+    /// Nodes compare fingerprints of message types with same type id and
+    /// reject messages of different type. Derive computes fingerprint from
+    /// type name, field names and field types, manual implementations
+    /// can use `actix_remote::fingerprint()` with schema description.
+    /// Default `0` is unknown fingerprint, message type is not checked.
+    fn fingerprint() -> u64 {
+        0
+    }
+
+    /// Message routing key, `None` if message is routed by type only
+    fn routing_key(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Fingerprint of message schema description, never `0`
+pub fn fingerprint(schema: &str) -> u64 {
+    match utils::fnv1a(schema.as_bytes()) {
+        0 => 1,
+        hash => hash,
+    }
 }
 
+/// Request to stop actor instance managed by cluster
+///
+/// Singleton instance receives it on hand-over to other node, sharded
+/// entity receives it on passivation. Actor should finish its work
+/// and stop, result is sent once actor accepted request.
+#[derive(Message)]
+pub struct Passivate;
+
 pub struct Remote;
 
+/// Remote message with information about sender node
+///
+/// Recipient registered with `World::register_peer_recipient()` receives
+/// this message, so handler can inspect peer identity.
+pub struct FromPeer<M: RemoteMessage>
+    where M::Result: Send + Serialize + DeserializeOwned
+{
+    pub peer: Peer,
+    pub msg: M,
+}
+
+impl<M: RemoteMessage> Message for FromPeer<M>
+    where M::Result: Send + Serialize + DeserializeOwned
+{
+    type Result = M::Result;
+}
+
 impl<M> MessageRecipient<M> for Remote
     where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
 {
@@ -27,7 +87,7 @@ impl<M> MessageRecipient<M> for Remote
     type Transport = RecipientProxySender<M>;
 
     type SendError = SendError<M>;
-    type MailboxError = MailboxError;
+    type MailboxError = RemoteError;
     type Request = RemoteRecipientRequest<Self, M>;
 
     fn do_send(tx: &Self::Transport, msg: M) -> Result<(), SendError<M>> {
@@ -77,16 +137,16 @@ pub struct RemoteRecipientRequest<T, M>
     where T: MessageRecipient<M>,
           M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
 {
-    rx: actix::dev::Request<Syn, RecipientProxy<M>, M>,
+    rx: actix::dev::Request<Syn, RecipientProxy<M>, ProxyMessage<M>>,
     timeout: Option<Timeout>,
     _t: PhantomData<T>,
 }
 
 impl<T, M> RemoteRecipientRequest<T, M>
-    where T: MessageRecipient<M, MailboxError=MailboxError>,
+    where T: MessageRecipient<M, MailboxError=RemoteError>,
           M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
 {
-    pub(crate) fn new(rx: actix::dev::Request<Syn, RecipientProxy<M>, M>)
+    pub(crate) fn new(rx: actix::dev::Request<Syn, RecipientProxy<M>, ProxyMessage<M>>)
                       -> RemoteRecipientRequest<T, M>
     {
         RemoteRecipientRequest{rx: rx, timeout: None, _t: PhantomData}
@@ -98,10 +158,10 @@ impl<T, M> RemoteRecipientRequest<T, M>
         self
     }
 
-    fn poll_timeout(&mut self) -> Poll<M::Result, MailboxError> {
+    fn poll_timeout(&mut self) -> Poll<M::Result, RemoteError> {
         if let Some(ref mut timeout) = self.timeout {
             match timeout.poll() {
-                Ok(Async::Ready(())) => Err(MailboxError::Timeout),
+                Ok(Async::Ready(())) => Err(RemoteError::Timeout),
                 Ok(Async::NotReady) => Ok(Async::NotReady),
                 Err(_) => unreachable!()
             }
@@ -112,7 +172,7 @@ impl<T, M> RemoteRecipientRequest<T, M>
 }
 
 impl<T, M> Future for RemoteRecipientRequest<T, M>
-    where T: MessageRecipient<M, SendError=SendError<M>, MailboxError=MailboxError>,
+    where T: MessageRecipient<M, SendError=SendError<M>, MailboxError=RemoteError>,
           M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
 {
     type Item = M::Result;
@@ -120,11 +180,12 @@ impl<T, M> Future for RemoteRecipientRequest<T, M>
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         match self.rx.poll() {
-            Ok(Async::Ready(item)) => Ok(Async::Ready(item)),
+            Ok(Async::Ready(Ok(item))) => Ok(Async::Ready(item)),
+            Ok(Async::Ready(Err(err))) => Err(err),
             Ok(Async::NotReady) => {
                 self.poll_timeout()
             }
-            Err(_) => Err(MailboxError::Closed),
+            Err(_) => Err(RemoteError::Disconnected),
         }
     }
 }
@@ -0,0 +1,434 @@
+use std::mem;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+use futures::Future;
+use futures::unsync::oneshot::{self, Sender};
+use actix::prelude::*;
+use actix::prelude::{Response as ActixResponse};
+
+use msgs::{self, Forwarded, ProxyMessage};
+use actor;
+use error::RemoteError;
+use migration::Migrations;
+use node::NodeId;
+use policy::Policy;
+use recipient::ForwardHandler;
+use remote::{Passivate, RemoteMessage};
+use utils;
+use world::World;
+
+/// Default number of shards
+const DEFAULT_SHARDS: u32 = 100;
+/// Default idle time before entity is passivated, in seconds
+const DEFAULT_PASSIVATE: u64 = 120;
+/// Interval of membership refresh and passivation, in seconds
+const REFRESH_INTERVAL: u64 = 1;
+/// Number of deliveries of a message rejected by node that is not owner
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Message waiting for delivery, received from other node flag and
+/// number of deliveries rejected by other node
+type Pending<M> = (M, bool, u32, Sender<Result<<M as Message>::Result, RemoteError>>);
+
+
+/// Sharded entity type
+///
+/// Entity id is the routing key of the message, entities are grouped
+/// into shards and shards are allocated across nodes that run region
+/// for the message type. Entity actor is started on demand on the owning
+/// node and is passivated when idle. Shards are reallocated when nodes
+/// join or leave, entities of shards that moved to other node are
+/// passivated. Region rejects messages from other nodes for shards it
+/// does not own, sender retries after next membership refresh.
+///
+/// Passivated entity receives `Passivate` and should stop, messages for
+/// the entity are buffered until it handles the request.
+///
+/// ```rust,ignore
+/// let accounts = ShardRegion::new(|id: &str| {
+///     let addr: Addr<Syn, _> = Account::new(id).start();
+///     (addr.clone().recipient(), addr.recipient())
+/// }).passivate_after(Duration::from_secs(600)).start(&world);
+///
+/// accounts.send(Deposit{account: "42".to_owned(), amount: 100});
+/// ```
+pub struct ShardRegion<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    factory: Box<Fn(&str) -> (Recipient<Syn, M>, Recipient<Syn, Passivate>)>,
+    shards: u32,
+    passivate: Duration,
+    policy: Policy,
+    migrations: Migrations,
+}
+
+impl<M> ShardRegion<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    /// Region with entity factory, factory receives entity id
+    pub fn new<F>(factory: F) -> ShardRegion<M>
+        where F: Fn(&str) -> (Recipient<Syn, M>, Recipient<Syn, Passivate>) + 'static
+    {
+        ShardRegion{factory: Box::new(factory), shards: DEFAULT_SHARDS,
+                    passivate: Duration::from_secs(DEFAULT_PASSIVATE),
+                    policy: Policy::allow_all(), migrations: Migrations::new()}
+    }
+
+    /// Number of shards, has to be same on every node
+    pub fn shards(mut self, shards: u32) -> Self {
+        self.shards = shards;
+        self
+    }
+
+    /// Idle time before entity is passivated
+    pub fn passivate_after(mut self, idle: Duration) -> Self {
+        self.passivate = idle;
+        self
+    }
+
+    /// Nodes allowed to send messages to entities on this node
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Schema migrations of messages from nodes with older message version
+    pub fn migrations(mut self, migrations: Migrations) -> Self {
+        self.migrations = migrations;
+        self
+    }
+
+    /// Start region on this node
+    pub fn start(self, world: &Addr<Syn, World>) -> Sharding<M> {
+        let region = Region{world: world.clone(), factory: self.factory,
+                            shards: if self.shards == 0 { 1 } else { self.shards },
+                            passivate: self.passivate,
+                            policy: Some(self.policy), migrations: Some(self.migrations),
+                            node: None, members: Vec::new(),
+                            entities: HashMap::new(), passivating: HashMap::new(),
+                            buffer: Vec::new()};
+        Sharding{region: region.start()}
+    }
+}
+
+/// Sender for sharded entities
+pub struct Sharding<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    region: Addr<Syn, Region<M>>,
+}
+
+impl<M> Sharding<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    /// Send message to entity
+    pub fn send(&self, msg: M) -> Box<Future<Item=M::Result, Error=RemoteError>> {
+        Box::new(
+            self.region.send(ProxyMessage(msg)).then(|res| match res {
+                Ok(res) => res,
+                Err(_) => Err(RemoteError::Disconnected),
+            }))
+    }
+
+    /// Send message to entity, ignore result
+    pub fn do_send(&self, msg: M) {
+        self.region.do_send(ProxyMessage(msg))
+    }
+}
+
+impl<M> Clone for Sharding<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    fn clone(&self) -> Self {
+        Sharding{region: self.region.clone()}
+    }
+}
+
+struct Entity<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    recipient: Recipient<Syn, M>,
+    passivate: Recipient<Syn, Passivate>,
+    used: Instant,
+}
+
+/// Shard region, runs on every node that hosts entities
+pub(crate)
+struct Region<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    world: Addr<Syn, World>,
+    factory: Box<Fn(&str) -> (Recipient<Syn, M>, Recipient<Syn, Passivate>)>,
+    shards: u32,
+    passivate: Duration,
+    /// Handler settings, moved to handler on registration
+    policy: Option<Policy>,
+    migrations: Option<Migrations>,
+    /// Local node, known after registration
+    node: Option<NodeId>,
+    /// Nodes that run region, including local node
+    members: Vec<NodeId>,
+    entities: HashMap<String, Entity<M>>,
+    /// Messages for entities that are being passivated
+    passivating: HashMap<String, Vec<Pending<M>>>,
+    /// Messages received before membership is known or rejected by owner,
+    /// routed again on membership refresh
+    buffer: Vec<Pending<M>>,
+}
+
+impl<M> Actor for Region<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        // other nodes send entity messages to owner region as messages of the type
+        let addr: Addr<Syn, _> = ctx.address();
+        let migrations = self.migrations.take().unwrap_or_default();
+        let handler = ForwardHandler{
+            recipient: addr.recipient(),
+            policy: self.policy.take().unwrap_or_else(Policy::allow_all),
+            versions: (migrations.min_version(M::version()), M::version()),
+            migrations: Arc::new(migrations)};
+        World::provide::<M>(&self.world, Arc::new(handler))
+            .into_actor(self)
+            .then(|res, act, ctx| {
+                match res {
+                    Ok(()) => act.refresh(ctx),
+                    Err(err) => error!("Can not register shard region {}: {}", M::type_id(), err),
+                }
+                actix::fut::ok(())
+            })
+            .wait(ctx);
+
+        self.refresh_later(ctx);
+    }
+}
+
+impl<M> Region<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    fn refresh_later(&self, ctx: &mut Context<Self>) {
+        ctx.run_later(Duration::from_secs(REFRESH_INTERVAL), |act, ctx| {
+            act.refresh(ctx);
+            act.passivate_idle(ctx);
+            act.refresh_later(ctx);
+        });
+    }
+
+    /// Load nodes that run region
+    fn refresh(&mut self, ctx: &mut Context<Self>) {
+        self.world.send(msgs::TypeNodes(M::type_id()))
+            .into_actor(self)
+            .then(|res, act, ctx| {
+                if let Ok((node, members)) = res {
+                    act.set_members(node, members, ctx);
+                }
+                actix::fut::ok(())
+            })
+            .spawn(ctx);
+    }
+
+    fn set_members(&mut self, node: NodeId, mut members: Vec<NodeId>, ctx: &mut Context<Self>) {
+        members.sort();
+        self.node = Some(node);
+        if members != self.members {
+            info!("Shard region {} members: {:?}", M::type_id(), members);
+            self.members = members;
+
+            // rebalance, passivate entities of shards owned by other nodes
+            let moved: Vec<String> = self.entities.keys()
+                .filter(|entity| self.owner(entity) != self.node).cloned().collect();
+            for entity in moved {
+                debug!("Entity {} of {} moved to other node", entity, M::type_id());
+                self.passivate(entity, ctx);
+            }
+        }
+
+        for (msg, remote, attempts, tx) in mem::take(&mut self.buffer) {
+            self.route(msg, remote, attempts, tx, ctx);
+        }
+    }
+
+    /// Passivate idle entities
+    fn passivate_idle(&mut self, ctx: &mut Context<Self>) {
+        let idle = self.passivate;
+        let expired: Vec<String> = self.entities.iter()
+            .filter(|&(_, entity)| entity.used.elapsed() >= idle)
+            .map(|(id, _)| id.clone()).collect();
+        for entity in expired {
+            self.passivate(entity, ctx);
+        }
+    }
+
+    /// Ask entity to stop, messages for the entity are buffered
+    /// until entity handles the request
+    fn passivate(&mut self, id: String, ctx: &mut Context<Self>) {
+        let entity = match self.entities.remove(&id) {
+            Some(entity) => entity,
+            None => return,
+        };
+        debug!("Passivate entity {} of {}", id, M::type_id());
+        self.passivating.insert(id.clone(), Vec::new());
+        entity.passivate.send(Passivate)
+            .into_actor(self)
+            .then(move |_, act, ctx| {
+                // entity is stopped or stopping, next message starts new one
+                if let Some(buffer) = act.passivating.remove(&id) {
+                    for (msg, remote, attempts, tx) in buffer {
+                        act.route(msg, remote, attempts, tx, ctx);
+                    }
+                }
+                actix::fut::ok(())
+            })
+            .spawn(ctx);
+    }
+
+    /// Node that owns shard of the entity, rendezvous hashing over members
+    fn owner(&self, entity: &str) -> Option<NodeId> {
+        let shard = utils::fnv1a(entity.as_bytes()) % u64::from(self.shards);
+        self.members.iter()
+            .max_by_key(|node| utils::fnv1a(format!("{}/{}", shard, node).as_bytes()))
+            .cloned()
+    }
+
+    /// Deliver message to local entity or forward it to owner node,
+    /// message from other node for shard of other node is rejected
+    fn route(&mut self, msg: M, remote: bool, attempts: u32,
+             tx: Sender<Result<M::Result, RemoteError>>, ctx: &mut Context<Self>)
+    {
+        let entity = match msg.routing_key() {
+            Some(entity) => entity,
+            None => {
+                let _ = tx.send(Err(RemoteError::MissingRoutingKey));
+                return
+            }
+        };
+        let owner = match self.owner(&entity) {
+            Some(owner) => owner,
+            None => return self.buffer.push((msg, remote, attempts, tx)),
+        };
+
+        if Some(owner) == self.node {
+            if let Some(buffer) = self.passivating.get_mut(&entity) {
+                return buffer.push((msg, remote, attempts, tx))
+            }
+            self.deliver(entity, msg, tx, ctx);
+        } else if remote {
+            // membership views differ, entity must not run on two nodes
+            let _ = tx.send(Err(RemoteError::Unavailable));
+        } else {
+            self.forward(owner, msg, attempts, tx, ctx);
+        }
+    }
+
+    /// Deliver message to local entity, start entity if needed
+    fn deliver(&mut self, entity: String, msg: M, tx: Sender<Result<M::Result, RemoteError>>,
+               ctx: &mut Context<Self>)
+    {
+        let now = Instant::now();
+        if !self.entities.contains_key(&entity) {
+            debug!("Start entity {} of {}", entity, M::type_id());
+            let (recipient, passivate) = (self.factory)(&entity);
+            self.entities.insert(
+                entity.clone(), Entity{recipient: recipient, passivate: passivate, used: now});
+        }
+        let fut = match self.entities.get_mut(&entity) {
+            Some(e) => {
+                e.used = now;
+                e.recipient.send(msg)
+            },
+            None => return,
+        };
+        fut.into_actor(self)
+            .then(move |res, act, _| {
+                let res = match res {
+                    Ok(res) => Ok(res),
+                    Err(_) => {
+                        // entity is stopped, next message starts new one
+                        act.entities.remove(&entity);
+                        Err(RemoteError::Stopped)
+                    }
+                };
+                let _ = tx.send(res);
+                actix::fut::ok(())
+            })
+            .spawn(ctx);
+    }
+
+    /// Send message to region on owner node, message is buffered again
+    /// if node does not own the shard
+    fn forward(&self, owner: NodeId, msg: M, attempts: u32,
+               tx: Sender<Result<M::Result, RemoteError>>, ctx: &mut Context<Self>)
+    {
+        let data = match serde_json::to_string(&msg) {
+            Ok(data) => data,
+            Err(_) => {
+                let _ = tx.send(Err(RemoteError::Decode));
+                return
+            }
+        };
+        self.world.send(msgs::SendToNode{
+            node: owner, type_id: M::type_id().to_owned(),
+            version: M::version(), fingerprint: M::fingerprint(), data: data})
+            .into_actor(self)
+            .then(move |res, act, _| {
+                let res = match res {
+                    Ok(Ok(data)) => actor::decode::<M::Result>(Some(&act.world), owner, &data),
+                    Ok(Err(RemoteError::Unavailable)) if attempts + 1 < MAX_ATTEMPTS => {
+                        // shards are moving, retry after next refresh
+                        act.buffer.push((msg, false, attempts + 1, tx));
+                        return actix::fut::ok(())
+                    },
+                    Ok(Err(err)) => Err(err),
+                    Err(_) => Err(RemoteError::Disconnected),
+                };
+                let _ = tx.send(res);
+                actix::fut::ok(())
+            })
+            .spawn(ctx);
+    }
+
+    /// Route message, result is sent when entity replies
+    fn reply(&mut self, msg: M, remote: bool, ctx: &mut Context<Self>)
+             -> ActixResponse<M::Result, RemoteError>
+    {
+        let (tx, rx) = oneshot::channel();
+        self.route(msg, remote, 0, tx, ctx);
+        ActixResponse::async(rx.then(|res| match res {
+            Ok(res) => res,
+            Err(_) => Err(RemoteError::Disconnected),
+        }))
+    }
+}
+
+impl<M> Handler<ProxyMessage<M>> for Region<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    type Result = ActixResponse<M::Result, RemoteError>;
+
+    fn handle(&mut self, msg: ProxyMessage<M>, ctx: &mut Context<Self>)
+              -> ActixResponse<M::Result, RemoteError>
+    {
+        self.reply(msg.0, false, ctx)
+    }
+}
+
+/// Entity messages from other nodes, delivered only if this node owns
+/// the shard
+impl<M> Handler<Forwarded<M>> for Region<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    type Result = ActixResponse<M::Result, RemoteError>;
+
+    fn handle(&mut self, msg: Forwarded<M>, ctx: &mut Context<Self>)
+              -> ActixResponse<M::Result, RemoteError>
+    {
+        self.reply(msg.msg, true, ctx)
+    }
+}
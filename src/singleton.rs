@@ -0,0 +1,307 @@
+use std::mem;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+use futures::Future;
+use futures::unsync::oneshot::{self, Sender};
+use actix::prelude::*;
+use actix::prelude::{Response as ActixResponse};
+
+use msgs::{self, Forwarded, ProxyMessage};
+use actor;
+use error::RemoteError;
+use migration::Migrations;
+use node::NodeId;
+use policy::Policy;
+use recipient::ForwardHandler;
+use remote::{Passivate, RemoteMessage};
+use world::World;
+
+/// Interval of singleton owner resolution, in seconds
+const RESOLVE_INTERVAL: u64 = 1;
+/// Number of equal resolutions before this node starts instance
+const STABLE_RESOLUTIONS: u32 = 2;
+/// Number of deliveries of a message rejected by node that is not owner
+const MAX_ATTEMPTS: u32 = 3;
+
+
+/// Proxy of cluster singleton actor
+///
+/// Singleton manager is registered on every node as provider of the message
+/// type, instance runs on the oldest node that runs the manager. If owner
+/// node leaves, instance is started on the next oldest node. Messages are
+/// buffered while owner is not known, manager on other node delivers
+/// message only if it runs the instance.
+///
+/// Node starts instance only after it has been the owner for
+/// a few resolutions, old owner sends `Passivate` to the instance on
+/// hand-over. Nodes that can not see each other during network partition
+/// still can run two instances.
+pub struct Singleton<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    manager: Addr<Syn, SingletonManager<M>>,
+}
+
+impl<M> Singleton<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    /// Send message to current singleton instance
+    pub fn send(&self, msg: M) -> Box<Future<Item=M::Result, Error=RemoteError>> {
+        Box::new(
+            self.manager.send(ProxyMessage(msg)).then(|res| match res {
+                Ok(res) => res,
+                Err(_) => Err(RemoteError::Disconnected),
+            }))
+    }
+
+    /// Send message to current singleton instance, ignore result
+    pub fn do_send(&self, msg: M) {
+        self.manager.do_send(ProxyMessage(msg))
+    }
+}
+
+impl<M> Clone for Singleton<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    fn clone(&self) -> Self {
+        Singleton{manager: self.manager.clone()}
+    }
+}
+
+impl World {
+    /// Register cluster singleton for message type on this node.
+    ///
+    /// `factory` starts singleton instance when this node becomes owner,
+    /// instance receives `Passivate` when other node becomes owner.
+    /// Every node that runs singleton has to register it.
+    pub fn register_singleton<M, F>(world: &Addr<Syn, World>, factory: F) -> Singleton<M>
+        where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned,
+              F: Fn() -> (Recipient<Syn, M>, Recipient<Syn, Passivate>) + 'static
+    {
+        World::register_singleton_with_policy(
+            world, factory, Policy::allow_all(), Migrations::new())
+    }
+
+    /// Register cluster singleton, only nodes allowed by policy can send
+    /// messages to instance on this node.
+    pub fn register_singleton_with_policy<M, F>(world: &Addr<Syn, World>, factory: F,
+                                                policy: Policy, migrations: Migrations)
+                                                -> Singleton<M>
+        where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned,
+              F: Fn() -> (Recipient<Syn, M>, Recipient<Syn, Passivate>) + 'static
+    {
+        let manager = SingletonManager{
+            world: world.clone(), factory: Box::new(factory),
+            policy: Some(policy), migrations: Some(migrations),
+            instance: None, owner: None, candidate: None, stable: 0,
+            buffer: Vec::new()};
+        Singleton{manager: manager.start()}
+    }
+}
+
+struct Instance<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    recipient: Recipient<Syn, M>,
+    passivate: Recipient<Syn, Passivate>,
+}
+
+/// Singleton manager, runs on every node
+pub(crate)
+struct SingletonManager<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    world: Addr<Syn, World>,
+    factory: Box<Fn() -> (Recipient<Syn, M>, Recipient<Syn, Passivate>)>,
+    /// Handler settings, moved to handler on registration
+    policy: Option<Policy>,
+    migrations: Option<Migrations>,
+    /// Instance, if this node is owner
+    instance: Option<Instance<M>>,
+    /// Owner node, if other node is owner
+    owner: Option<NodeId>,
+    /// Oldest node of last resolution and number of equal resolutions
+    candidate: Option<NodeId>,
+    stable: u32,
+    buffer: Vec<(M, u32, Sender<Result<M::Result, RemoteError>>)>,
+}
+
+impl<M> Actor for SingletonManager<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        // other nodes send messages to manager on owner node
+        let addr: Addr<Syn, _> = ctx.address();
+        let migrations = self.migrations.take().unwrap_or_default();
+        let handler = ForwardHandler{
+            recipient: addr.recipient(),
+            policy: self.policy.take().unwrap_or_else(Policy::allow_all),
+            versions: (migrations.min_version(M::version()), M::version()),
+            migrations: Arc::new(migrations)};
+        World::provide::<M>(&self.world, Arc::new(handler))
+            .into_actor(self)
+            .then(|res, act, ctx| {
+                match res {
+                    Ok(()) => act.resolve(ctx),
+                    Err(err) => error!("Can not register singleton {}: {}", M::type_id(), err),
+                }
+                actix::fut::ok(())
+            })
+            .wait(ctx);
+
+        self.resolve_later(ctx);
+    }
+}
+
+impl<M> SingletonManager<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    fn resolve_later(&self, ctx: &mut Context<Self>) {
+        ctx.run_later(Duration::from_secs(RESOLVE_INTERVAL), |act, ctx| {
+            act.resolve(ctx);
+            act.resolve_later(ctx);
+        });
+    }
+
+    /// Check nodes that run singleton manager, oldest node is owner
+    fn resolve(&mut self, ctx: &mut Context<Self>) {
+        self.world.send(msgs::TypeNodes(M::type_id()))
+            .into_actor(self)
+            .then(|res, act, ctx| {
+                if let Ok((node, nodes)) = res {
+                    let oldest = nodes.into_iter()
+                        .fold(None, |oldest: Option<NodeId>, n| match oldest {
+                            Some(o) if o.is_older_than(&n) => Some(o),
+                            _ => Some(n),
+                        });
+                    act.set_owner(node, oldest, ctx);
+                }
+                actix::fut::ok(())
+            })
+            .spawn(ctx);
+    }
+
+    fn set_owner(&mut self, node: NodeId, oldest: Option<NodeId>, ctx: &mut Context<Self>) {
+        if oldest == self.candidate {
+            self.stable = self.stable.saturating_add(1);
+        } else {
+            self.candidate = oldest;
+            self.stable = 1;
+        }
+
+        if oldest == Some(node) {
+            self.owner = None;
+            if self.instance.is_none() {
+                // other nodes may still see old owner
+                if self.stable < STABLE_RESOLUTIONS {
+                    return
+                }
+                info!("Start singleton {}", M::type_id());
+                let (recipient, passivate) = (self.factory)();
+                self.instance = Some(Instance{recipient: recipient, passivate: passivate});
+            }
+        } else {
+            if let Some(instance) = self.instance.take() {
+                info!("Singleton {} is handed over", M::type_id());
+                let _ = instance.passivate.do_send(Passivate);
+            }
+            self.owner = oldest;
+        }
+
+        // deliver messages received during hand-over
+        for (msg, attempts, tx) in mem::take(&mut self.buffer) {
+            self.route(msg, attempts, tx, ctx);
+        }
+    }
+
+    /// Send message to instance, buffer message if owner is not known
+    fn route(&mut self, msg: M, attempts: u32, tx: Sender<Result<M::Result, RemoteError>>,
+             ctx: &mut Context<Self>)
+    {
+        if let Some(ref instance) = self.instance {
+            Arbiter::handle().spawn(
+                instance.recipient.send(msg).then(move |res| {
+                    let _ = tx.send(res.map_err(|_| RemoteError::Stopped));
+                    Ok(())
+                }));
+        } else if let Some(owner) = self.owner {
+            self.forward(owner, msg, attempts, tx, ctx);
+        } else {
+            self.buffer.push((msg, attempts, tx));
+        }
+    }
+
+    /// Send message to manager on owner node, message is buffered again
+    /// if node does not run instance
+    fn forward(&self, owner: NodeId, msg: M, attempts: u32,
+               tx: Sender<Result<M::Result, RemoteError>>, ctx: &mut Context<Self>)
+    {
+        let data = match serde_json::to_string(&msg) {
+            Ok(data) => data,
+            Err(_) => {
+                let _ = tx.send(Err(RemoteError::Decode));
+                return
+            }
+        };
+        self.world.send(msgs::SendToNode{
+            node: owner, type_id: M::type_id().to_owned(),
+            version: M::version(), fingerprint: M::fingerprint(), data: data})
+            .into_actor(self)
+            .then(move |res, act, _| {
+                let res = match res {
+                    Ok(Ok(data)) => actor::decode::<M::Result>(Some(&act.world), owner, &data),
+                    Ok(Err(RemoteError::Unavailable)) if attempts + 1 < MAX_ATTEMPTS => {
+                        // owner is changing, retry after next resolution
+                        act.buffer.push((msg, attempts + 1, tx));
+                        return actix::fut::ok(())
+                    },
+                    Ok(Err(err)) => Err(err),
+                    Err(_) => Err(RemoteError::Disconnected),
+                };
+                let _ = tx.send(res);
+                actix::fut::ok(())
+            })
+            .spawn(ctx);
+    }
+}
+
+impl<M> Handler<ProxyMessage<M>> for SingletonManager<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    type Result = ActixResponse<M::Result, RemoteError>;
+
+    fn handle(&mut self, msg: ProxyMessage<M>, ctx: &mut Context<Self>)
+              -> ActixResponse<M::Result, RemoteError>
+    {
+        let (tx, rx) = oneshot::channel();
+        self.route(msg.0, 0, tx, ctx);
+        ActixResponse::async(rx.then(|res| match res {
+            Ok(res) => res,
+            Err(_) => Err(RemoteError::Disconnected),
+        }))
+    }
+}
+
+/// Message from manager on other node, delivered only if this node
+/// runs the instance
+impl<M> Handler<Forwarded<M>> for SingletonManager<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    type Result = ActixResponse<M::Result, RemoteError>;
+
+    fn handle(&mut self, msg: Forwarded<M>, _: &mut Context<Self>)
+              -> ActixResponse<M::Result, RemoteError>
+    {
+        match self.instance {
+            Some(ref instance) => ActixResponse::async(
+                instance.recipient.send(msg.msg).map_err(|_| RemoteError::Stopped)),
+            None => ActixResponse::reply(Err(RemoteError::Unavailable)),
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::sync::Arc;
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+use futures::{future, Future};
+use actix::prelude::*;
+
+use msgs;
+use actor::{ActorId, Probe, RemoteActor, RemoteAddr};
+use error::{RegistrationError, RemoteError};
+use node::{NodeId, Peer};
+use policy::Policy;
+use recipient::RemoteMessageHandler;
+use world::World;
+
+
+/// Actor kind that can be started by other nodes
+///
+/// ```rust,ignore
+/// impl RemoteSpawn for Worker {
+///     type Args = WorkerConfig;
+///
+///     fn kind() -> &'static str {
+///         "Worker"
+///     }
+/// }
+///
+/// World::register_factory(&world, |cfg: WorkerConfig| {
+///     let addr: Addr<Syn, Worker> = Worker::new(cfg).start();
+///     RemoteActor::new().addr(addr.clone()).recipient::<Job>(addr.recipient())
+/// });
+///
+/// World::spawn::<Worker>(&world, node, WorkerConfig::default())
+///     .map(|worker| worker.stop())
+/// ```
+///
+/// Spawned actor is registered until it stops, until spawner stops it
+/// with `RemoteAddr::stop()` or until spawner node is gone.
+pub trait RemoteSpawn: Actor {
+    /// Constructor arguments
+    type Args: Serialize + DeserializeOwned + Send + 'static;
+
+    /// Actor kind id, same on every node
+    fn kind() -> &'static str;
+}
+
+/// Type erased actor factory
+pub(crate) trait ActorFactory: Send + Sync {
+    /// Check if peer is allowed to start actors of this kind
+    fn allows(&self, peer: &Peer) -> bool;
+
+    /// Start actor, `None` if arguments can not be decoded
+    fn spawn(&self, args: &str)
+             -> Option<(HashMap<&'static str, Arc<RemoteMessageHandler>>, Option<Probe>)>;
+}
+
+struct Factory<A: RemoteSpawn> {
+    f: Box<Fn(A::Args) -> RemoteActor<A> + Send + Sync>,
+    policy: Policy,
+}
+
+impl<A: RemoteSpawn> ActorFactory for Factory<A> {
+    fn allows(&self, peer: &Peer) -> bool {
+        self.policy.allows(peer)
+    }
+
+    fn spawn(&self, args: &str)
+             -> Option<(HashMap<&'static str, Arc<RemoteMessageHandler>>, Option<Probe>)>
+    {
+        match serde_json::from_str::<A::Args>(args) {
+            Ok(args) => Some((self.f)(args).into_parts()),
+            Err(err) => {
+                warn!("Can not decode arguments of {}: {}", A::kind(), err);
+                None
+            }
+        }
+    }
+}
+
+impl World {
+    /// Register factory for actor kind, any node can start actor on this node.
+    pub fn register_factory<A, F>(world: &Addr<Syn, World>, f: F)
+        -> Box<Future<Item=(), Error=RegistrationError>>
+        where A: RemoteSpawn, F: Fn(A::Args) -> RemoteActor<A> + Send + Sync + 'static
+    {
+        World::register_factory_with_policy(world, Policy::allow_all(), f)
+    }
+
+    /// Register factory for actor kind, only nodes allowed by policy
+    /// can start actors.
+    pub fn register_factory_with_policy<A, F>(world: &Addr<Syn, World>, policy: Policy, f: F)
+        -> Box<Future<Item=(), Error=RegistrationError>>
+        where A: RemoteSpawn, F: Fn(A::Args) -> RemoteActor<A> + Send + Sync + 'static
+    {
+        let factory = Factory::<A>{f: Box::new(f), policy: policy};
+        Box::new(
+            world.send(msgs::RegisterFactory{kind: A::kind(), factory: Arc::new(factory)})
+                .then(|res| match res {
+                    Ok(res) => res,
+                    Err(_) => Err(RegistrationError::Stopped),
+                }))
+    }
+
+    /// Start actor on the node, node has to register factory for the actor kind.
+    pub fn spawn<A>(world: &Addr<Syn, World>, node: NodeId, args: A::Args)
+        -> Box<Future<Item=RemoteAddr<A>, Error=RemoteError>>
+        where A: RemoteSpawn
+    {
+        let args = match serde_json::to_string(&args) {
+            Ok(args) => args,
+            Err(_) => return Box::new(future::err(RemoteError::Decode)),
+        };
+        let addr = world.clone();
+        Box::new(
+            world.send(msgs::SpawnActor{node: node, kind: A::kind(), args: args})
+                .then(move |res| match res {
+                    Ok(Ok(id)) => Ok(RemoteAddr::new(id, addr)),
+                    Ok(Err(err)) => Err(err),
+                    Err(_) => Err(RemoteError::Disconnected),
+                }))
+    }
+}
+
+/// Actor id from `Spawn` request result
+pub(crate) fn parse_actor(node: NodeId, data: &str) -> Result<ActorId, RemoteError> {
+    data.parse().map(|id| ActorId{node: node, id: id}).map_err(|_| RemoteError::Decode)
+}
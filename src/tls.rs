@@ -0,0 +1,253 @@
+use std::{fs, io};
+use std::io::{BufReader, Read};
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Arc;
+
+use futures::{future, Future};
+use tokio_io::io::flush;
+use rustls::{AllowAnyAuthenticatedClient, Certificate, ClientConfig,
+             PrivateKey, RootCertStore, ServerConfig, Session};
+use rustls::internal::pemfile;
+use tokio_rustls::{ClientConfigExt, ServerConfigExt};
+use webpki::DNSNameRef;
+
+use transport;
+use utils::Io;
+
+
+/// Mutual TLS configuration for node connections
+///
+/// Both sides of the connection present certificate signed by trusted CA,
+/// connection without valid peer certificate is rejected.
+#[derive(Clone)]
+pub struct TlsConfig {
+    server: Arc<ServerConfig>,
+    client: Arc<ClientConfig>,
+    domain: Option<String>,
+}
+
+impl TlsConfig {
+    /// Create configuration from PEM encoded certificate chain,
+    /// private key and trusted CA certificates.
+    pub fn new(cert: &[u8], key: &[u8], ca: &[u8]) -> io::Result<TlsConfig> {
+        let certs = pemfile::certs(&mut BufReader::new(cert))
+            .map_err(|_| error("Can not parse certificate"))?;
+        let key = private_key(key)?;
+
+        let mut roots = RootCertStore::empty();
+        roots.add_pem_file(&mut BufReader::new(ca))
+            .map_err(|_| error("Can not parse CA certificate"))?;
+
+        let mut server = ServerConfig::new(AllowAnyAuthenticatedClient::new(roots.clone()));
+        server.set_single_cert(certs.clone(), key.clone());
+
+        let mut client = ClientConfig::new();
+        client.root_store = roots;
+        client.set_single_client_cert(certs, key);
+
+        Ok(TlsConfig{server: Arc::new(server), client: Arc::new(client), domain: None})
+    }
+
+    /// Load configuration from PEM files
+    pub fn from_files<P: AsRef<Path>>(cert: P, key: P, ca: P) -> io::Result<TlsConfig> {
+        TlsConfig::new(&read_file(cert)?, &read_file(key)?, &read_file(ca)?)
+    }
+
+    /// Server name to verify in certificates of remote nodes.
+    ///
+    /// By default host name of the node address is used. Addresses
+    /// without host name, IP addresses and `unix://` or `mem://`
+    /// addresses, require server name.
+    pub fn domain<S: Into<String>>(mut self, domain: S) -> Self {
+        self.domain = Some(domain.into());
+        self
+    }
+
+    /// Start TLS on accepted connection
+    pub(crate) fn accept(&self, stream: Box<Io>)
+                         -> Box<Future<Item=(Box<Io>, Vec<Vec<u8>>), Error=io::Error>>
+    {
+        Box::new(self.server.accept_async(stream).map(|stream| {
+            let certs = peer_certificates(stream.get_ref().1.get_peer_certificates());
+            (Box::new(stream) as Box<Io>, certs)
+        }))
+    }
+
+    /// Start TLS on outbound connection
+    pub(crate) fn connect(&self, addr: &str, stream: Box<Io>)
+                          -> Box<Future<Item=(Box<Io>, Vec<Vec<u8>>), Error=io::Error>>
+    {
+        let host = match self.server_name(addr) {
+            Ok(host) => host,
+            Err(err) => return Box::new(future::err(err)),
+        };
+        let domain = match DNSNameRef::try_from_ascii_str(&host) {
+            Ok(domain) => domain,
+            Err(_) => return Box::new(future::err(
+                error(&format!("Invalid server name: {}", host)))),
+        };
+
+        // last handshake flight can stay buffered in the session, server
+        // does not finish handshake until it is flushed
+        Box::new(self.client.connect_async(domain, stream).and_then(flush).map(|stream| {
+            let certs = peer_certificates(stream.get_ref().1.get_peer_certificates());
+            (Box::new(stream) as Box<Io>, certs)
+        }))
+    }
+
+    /// Server name for node address, fails if address does not have
+    /// host name and server name is not set
+    pub(crate) fn server_name(&self, addr: &str) -> io::Result<String> {
+        let host = match self.domain {
+            Some(ref domain) => domain.clone(),
+            None => {
+                let (scheme, rest) = transport::split_addr(addr);
+                if scheme == transport::UNIX_SCHEME || scheme == transport::MEM_SCHEME {
+                    return Err(error(&format!(
+                        "Address {} does not have host name, set TlsConfig::domain()", addr)))
+                }
+                let host = host(rest.split('/').next().unwrap_or(rest));
+                if host.parse::<IpAddr>().is_ok() {
+                    return Err(error(&format!(
+                        "IP address {} can not be verified, set TlsConfig::domain()", host)))
+                }
+                host
+            }
+        };
+        match DNSNameRef::try_from_ascii_str(&host) {
+            Ok(_) => Ok(host),
+            Err(_) => Err(error(&format!("Invalid server name: {}", host))),
+        }
+    }
+}
+
+fn peer_certificates(certs: Option<Vec<Certificate>>) -> Vec<Vec<u8>> {
+    certs.map(|certs| certs.into_iter().map(|c| c.0).collect()).unwrap_or_default()
+}
+
+/// Load PKCS8 or RSA private key
+fn private_key(key: &[u8]) -> io::Result<PrivateKey> {
+    let mut keys = pemfile::pkcs8_private_keys(&mut BufReader::new(key))
+        .map_err(|_| error("Can not parse private key"))?;
+    if keys.is_empty() {
+        keys = pemfile::rsa_private_keys(&mut BufReader::new(key))
+            .map_err(|_| error("Can not parse private key"))?;
+    }
+    keys.pop().ok_or_else(|| error("Private key is not found"))
+}
+
+fn read_file<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    fs::File::open(path)?.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// Host part of `host:port` address
+fn host(addr: &str) -> String {
+    let host = match addr.rfind(':') {
+        Some(pos) if !addr.ends_with(']') => &addr[..pos],
+        _ => addr,
+    };
+    host.trim_start_matches('[').trim_end_matches(']').to_owned()
+}
+
+fn error(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::Stream;
+    use tokio_core::net::{TcpListener, TcpStream};
+    use tokio_core::reactor::Core;
+
+    use super::*;
+
+    const CA: &[u8] = include_bytes!("../tests/certs/ca.pem");
+    const CERT: &[u8] = include_bytes!("../tests/certs/node.pem");
+    const KEY: &[u8] = include_bytes!("../tests/certs/node-key.pem");
+    // signed by CA that is not trusted by other nodes
+    const OTHER_CERT: &[u8] = include_bytes!("../tests/certs/other.pem");
+    const OTHER_KEY: &[u8] = include_bytes!("../tests/certs/other-key.pem");
+
+    fn config() -> TlsConfig {
+        TlsConfig::new(CERT, KEY, CA).unwrap()
+    }
+
+    /// Handshake over loopback connection, returns peer certificates
+    /// seen by server and client
+    fn handshake(server: &TlsConfig, client: &TlsConfig)
+                 -> io::Result<(Vec<Vec<u8>>, Vec<Vec<u8>>)>
+    {
+        let mut core = Core::new().unwrap();
+        let handle = core.handle();
+        let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap(), &handle).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let accept = listener.incoming().into_future()
+            .map_err(|(err, _)| err)
+            .and_then(move |(conn, _)| {
+                let (stream, _) = conn.unwrap();
+                server.accept(Box::new(stream))
+            });
+        let connect = TcpStream::connect(&addr, &handle)
+            .and_then(move |stream| {
+                client.connect(&format!("localhost:{}", addr.port()), Box::new(stream))
+            });
+        core.run(accept.join(connect))
+            .map(|((_, client_certs), (_, server_certs))| (client_certs, server_certs))
+    }
+
+    #[test]
+    fn test_handshake() {
+        let (client_certs, server_certs) = handshake(&config(), &config()).unwrap();
+        assert_eq!(client_certs.len(), 1);
+        assert_eq!(server_certs.len(), 1);
+    }
+
+    #[test]
+    fn test_untrusted_peer() {
+        let other = TlsConfig::new(OTHER_CERT, OTHER_KEY, CA).unwrap();
+        assert!(handshake(&config(), &other).is_err());
+        assert!(handshake(&other, &config()).is_err());
+    }
+
+    #[test]
+    fn test_domain() {
+        assert!(handshake(&config(), &config().domain("localhost")).is_ok());
+        assert!(handshake(&config(), &config().domain("example.com")).is_err());
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        assert!(TlsConfig::new(CERT, b"not a key", CA).is_err());
+        assert!(TlsConfig::new(CERT, CERT, CA).is_err());
+    }
+
+    #[test]
+    fn test_host() {
+        assert_eq!(host("localhost:8080"), "localhost");
+        assert_eq!(host("[::1]:8080"), "::1");
+        assert_eq!(host("[::1]"), "::1");
+        assert_eq!(host("localhost"), "localhost");
+    }
+
+    #[test]
+    fn test_server_name() {
+        let tls = config();
+        assert_eq!(tls.server_name("tcp://node.example.com:8080").unwrap(), "node.example.com");
+        assert_eq!(tls.server_name("localhost:8080").unwrap(), "localhost");
+        assert_eq!(tls.server_name("ws://localhost:8080/remote").unwrap(), "localhost");
+
+        // address without host name requires server name
+        assert!(tls.server_name("127.0.0.1:8080").is_err());
+        assert!(tls.server_name("tcp://[::1]:8080").is_err());
+        assert!(tls.server_name("unix:///tmp/node.sock").is_err());
+        assert!(tls.server_name("mem://node").is_err());
+        let tls = tls.domain("localhost");
+        assert_eq!(tls.server_name("127.0.0.1:8080").unwrap(), "localhost");
+        assert_eq!(tls.server_name("mem://node").unwrap(), "localhost");
+        assert!(config().domain("not a name").server_name("mem://node").is_err());
+    }
+}
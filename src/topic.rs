@@ -0,0 +1,105 @@
+use std::sync::Arc;
+use std::any::TypeId;
+use std::marker::PhantomData;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+use futures::Future;
+use actix::prelude::*;
+
+use msgs;
+use error::RegistrationError;
+use migration::Migrations;
+use policy::Policy;
+use recipient::Provider;
+use remote::RemoteMessage;
+use world::World;
+
+
+/// Cluster-wide publish/subscribe topic
+///
+/// Nodes announce subscribed topics to connected nodes. Published message
+/// is sent once to every node with subscribers, node delivers it
+/// to all local subscribers. Subscribers results are ignored.
+///
+/// ```rust,ignore
+/// let topic = World::topic::<PriceChanged>(&world, "prices");
+/// topic.subscribe(addr.recipient());
+/// topic.publish(PriceChanged{symbol: "ACTX".to_owned(), price: 42});
+/// ```
+pub struct Topic<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    name: String,
+    world: Addr<Syn, World>,
+    m: PhantomData<M>,
+}
+
+impl<M> Topic<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    /// Topic name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Subscribe recipient, result is subscription id.
+    ///
+    /// Fails if topic type id is already used by different message type.
+    pub fn subscribe(&self, recipient: Recipient<Syn, M>)
+                     -> Box<Future<Item=u64, Error=RegistrationError>>
+    {
+        self.subscribe_with_policy(recipient, Policy::allow_all())
+    }
+
+    /// Subscribe recipient, only messages published by nodes allowed
+    /// by policy are delivered.
+    pub fn subscribe_with_policy(&self, recipient: Recipient<Syn, M>, policy: Policy)
+                                 -> Box<Future<Item=u64, Error=RegistrationError>>
+    {
+        let handler = Provider{recipient: recipient, policy: policy,
+                               versions: (M::version(), M::version()),
+                               migrations: Arc::new(Migrations::new())};
+        Box::new(
+            self.world.send(msgs::Subscribe{
+                topic: self.name.clone(), type_id: M::type_id(),
+                message_type: TypeId::of::<M>(), handler: Arc::new(handler)})
+                .then(|res| match res {
+                    Ok(res) => res,
+                    Err(_) => Err(RegistrationError::Stopped),
+                }))
+    }
+
+    /// Remove subscription
+    pub fn unsubscribe(&self, id: u64) {
+        self.world.do_send(msgs::Unsubscribe{topic: self.name.clone(), id: id})
+    }
+
+    /// Publish message to all subscribers in cluster
+    pub fn publish(&self, msg: M) {
+        match serde_json::to_string(&msg) {
+            Ok(data) => self.world.do_send(msgs::Publish{
+                topic: self.name.clone(), type_id: M::type_id().to_owned(),
+                version: M::version(), fingerprint: M::fingerprint(), data: data}),
+            Err(err) => error!("Can not encode message for topic {}: {}", self.name, err),
+        }
+    }
+}
+
+impl<M> Clone for Topic<M>
+    where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+{
+    fn clone(&self) -> Self {
+        Topic{name: self.name.clone(), world: self.world.clone(), m: PhantomData}
+    }
+}
+
+impl World {
+    /// Topic for message type
+    pub fn topic<M>(world: &Addr<Syn, World>, name: &str) -> Topic<M>
+        where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+    {
+        Topic{name: name.to_owned(), world: world.clone(), m: PhantomData}
+    }
+}
@@ -0,0 +1,235 @@
+//! Pluggable node transports
+//!
+//! Transport is selected by scheme of the address, `scheme://address`.
+//! Address without scheme is tcp address.
+use std::{io, net};
+#[cfg(unix)]
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net as unix;
+
+use actix::Arbiter;
+use actix::prelude::ArbiterService;
+use actix::actors::{Connect, Connector};
+use futures::{future, stream, Future, Stream};
+use tokio_core::net::TcpListener as TokioTcpListener;
+#[cfg(unix)]
+use tokio_uds::{UnixListener as TokioUnixListener, UnixStream};
+
+use mem;
+use utils::{self, Io};
+
+/// Address scheme of tcp transport
+pub const TCP_SCHEME: &str = "tcp";
+/// Address scheme of unix domain socket transport
+pub const UNIX_SCHEME: &str = "unix";
+/// Address scheme of in-process transport
+pub const MEM_SCHEME: &str = "mem";
+
+/// Stream of accepted connections, with description of remote peer
+pub type Incoming = Box<Stream<Item=(Box<Io>, String), Error=io::Error>>;
+
+
+/// Node transport
+///
+/// Transport binds listeners and dials remote nodes for addresses with
+/// specific scheme. Custom transports are registered with
+/// `World::transport()`.
+pub trait Transport: 'static {
+    /// Address scheme handled by transport, i.e. `tcp`
+    fn scheme(&self) -> &str;
+
+    /// Bind listener to address, address does not include scheme
+    fn listen(&self, addr: &str) -> io::Result<Box<Listener>>;
+
+    /// Connect to remote node, address does not include scheme
+    fn dial(&self, addr: &str) -> Box<Future<Item=Box<Io>, Error=io::Error>>;
+}
+
+/// Bound transport listener
+pub trait Listener {
+    /// Local address of the listener, including scheme
+    fn local_addr(&self) -> String;
+
+    /// Start accepting connections.
+    ///
+    /// Method is called from world's arbiter once world is started.
+    fn incoming(self: Box<Self>) -> Incoming;
+}
+
+/// Split address to scheme and transport address
+pub fn split_addr(addr: &str) -> (&str, &str) {
+    match addr.find("://") {
+        Some(pos) => (&addr[..pos], &addr[pos+3..]),
+        None => (TCP_SCHEME, addr),
+    }
+}
+
+
+/// Tcp transport, addresses are `host:port`
+///
+/// Listener is bound to every address the host resolves to.
+pub struct TcpTransport;
+
+impl Transport for TcpTransport {
+    fn scheme(&self) -> &str {
+        TCP_SCHEME
+    }
+
+    fn listen(&self, addr: &str) -> io::Result<Box<Listener>> {
+        let mut err = None;
+        let mut listeners = Vec::new();
+        for addr in net::ToSocketAddrs::to_socket_addrs(addr)? {
+            match utils::tcp_listener(addr, 256).and_then(TcpListener::new) {
+                Ok(lst) => listeners.push(lst),
+                Err(e) => err = Some(e),
+            }
+        }
+
+        match listeners.len() {
+            0 => Err(err.unwrap_or_else(
+                || io::Error::new(io::ErrorKind::Other, "Can not resolve address."))),
+            1 => Ok(Box::new(listeners.remove(0))),
+            _ => Ok(Box::new(TcpListeners(listeners))),
+        }
+    }
+
+    fn dial(&self, addr: &str) -> Box<Future<Item=Box<Io>, Error=io::Error>> {
+        Box::new(
+            Connector::from_registry()
+                .send(Connect::host(addr))
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("{}", err)))
+                .and_then(|res| match res {
+                    Ok(stream) => Ok(Box::new(stream) as Box<Io>),
+                    Err(err) => Err(io::Error::new(io::ErrorKind::Other, format!("{}", err))),
+                }))
+    }
+}
+
+/// Bound tcp listener
+pub(crate) struct TcpListener {
+    addr: net::SocketAddr,
+    lst: net::TcpListener,
+}
+
+impl TcpListener {
+    /// Listener for already bound socket
+    pub fn new(lst: net::TcpListener) -> io::Result<TcpListener> {
+        Ok(TcpListener{addr: lst.local_addr()?, lst: lst})
+    }
+}
+
+impl Listener for TcpListener {
+    fn local_addr(&self) -> String {
+        self.addr.to_string()
+    }
+
+    fn incoming(self: Box<Self>) -> Incoming {
+        let this = *self;
+        match TokioTcpListener::from_listener(this.lst, &this.addr, Arbiter::handle()) {
+            Ok(lst) => Box::new(lst.incoming().map(
+                |(stream, addr)| (Box::new(stream) as Box<Io>, addr.to_string()))),
+            Err(err) => Box::new(future::err(err).into_stream()),
+        }
+    }
+}
+
+/// Tcp listeners bound to addresses of same host, local address is
+/// address of first listener
+struct TcpListeners(Vec<TcpListener>);
+
+impl Listener for TcpListeners {
+    fn local_addr(&self) -> String {
+        self.0[0].local_addr()
+    }
+
+    fn incoming(self: Box<Self>) -> Incoming {
+        let mut streams = self.0.into_iter().map(|lst| Box::new(lst).incoming());
+        let first = streams.next().unwrap_or_else(|| Box::new(stream::empty()));
+        streams.fold(first, |all, incoming| Box::new(all.select(incoming)))
+    }
+}
+
+
+/// Unix domain socket transport, addresses are `unix://path`
+///
+/// Stale socket file is removed on bind. Access to the socket is controlled
+/// by filesystem permissions of the socket directory.
+#[cfg(unix)]
+pub struct UnixTransport;
+
+#[cfg(unix)]
+impl Transport for UnixTransport {
+    fn scheme(&self) -> &str {
+        UNIX_SCHEME
+    }
+
+    fn listen(&self, path: &str) -> io::Result<Box<Listener>> {
+        if let Ok(meta) = fs::metadata(path) {
+            if meta.file_type().is_socket() {
+                fs::remove_file(path)?;
+            }
+        }
+        let lst = unix::UnixListener::bind(path)?;
+        Ok(Box::new(UnixListener{path: path.to_owned(), lst: lst}))
+    }
+
+    fn dial(&self, path: &str) -> Box<Future<Item=Box<Io>, Error=io::Error>> {
+        Box::new(future::result(
+            UnixStream::connect(path, Arbiter::handle())
+                .map(|stream| Box::new(stream) as Box<Io>)))
+    }
+}
+
+#[cfg(unix)]
+struct UnixListener {
+    path: String,
+    lst: unix::UnixListener,
+}
+
+#[cfg(unix)]
+impl Listener for UnixListener {
+    fn local_addr(&self) -> String {
+        format!("{}://{}", UNIX_SCHEME, self.path)
+    }
+
+    fn incoming(self: Box<Self>) -> Incoming {
+        match TokioUnixListener::from_listener(self.lst, Arbiter::handle()) {
+            Ok(lst) => Box::new(lst.incoming().map(
+                |(stream, addr)| (Box::new(stream) as Box<Io>, format!("{:?}", addr)))),
+            Err(err) => Box::new(future::err(err).into_stream()),
+        }
+    }
+}
+
+
+/// In-process transport, addresses are `mem://name`
+pub struct MemTransport;
+
+impl Transport for MemTransport {
+    fn scheme(&self) -> &str {
+        MEM_SCHEME
+    }
+
+    fn listen(&self, name: &str) -> io::Result<Box<Listener>> {
+        Ok(Box::new(mem::MemListener::bind(name)?))
+    }
+
+    fn dial(&self, name: &str) -> Box<Future<Item=Box<Io>, Error=io::Error>> {
+        Box::new(future::result(
+            mem::connect(name).map(|stream| Box::new(stream) as Box<Io>)))
+    }
+}
+
+impl Listener for mem::MemListener {
+    fn local_addr(&self) -> String {
+        format!("{}://{}", MEM_SCHEME, self.name())
+    }
+
+    fn incoming(self: Box<Self>) -> Incoming {
+        let peer = self.local_addr();
+        Box::new(self.map(move |stream| (Box::new(stream) as Box<Io>, peer.clone())))
+    }
+}
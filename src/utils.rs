@@ -1,5 +1,11 @@
 use std::{io, net};
 use net2::TcpBuilder;
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Type erased connection stream
+pub trait Io: AsyncRead + AsyncWrite {}
+
+impl<T: AsyncRead + AsyncWrite> Io for T {}
 
 
 pub fn tcp_listener(addr: net::SocketAddr, backlog: i32) -> io::Result<net::TcpListener> {
@@ -9,5 +15,95 @@ pub fn tcp_listener(addr: net::SocketAddr, backlog: i32) -> io::Result<net::TcpL
     };
     builder.bind(addr)?;
     builder.reuse_address(true)?;
-    Ok(builder.listen(backlog)?)
+    builder.listen(backlog)
+}
+
+/// FNV-1a hash, stable across nodes and builds
+pub fn fnv1a(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in data {
+        hash ^= u64::from(*b);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// Replace unspecified host of `host:port` address, i.e. `0.0.0.0`,
+/// with address of interface that routes to other hosts, loopback address
+/// is used if there is no such interface. Returns `None` if host is specified.
+pub fn specify_host(addr: &str) -> Option<String> {
+    let addr = match addr.parse::<net::SocketAddr>() {
+        Ok(addr) if addr.ip().is_unspecified() => addr,
+        _ => return None,
+    };
+    // connecting udp socket only selects route, nothing is sent
+    let (any, probe, loopback): (net::IpAddr, net::IpAddr, net::IpAddr) = match addr {
+        net::SocketAddr::V4(_) => (net::Ipv4Addr::new(0, 0, 0, 0).into(),
+                                   net::Ipv4Addr::new(192, 0, 2, 1).into(),
+                                   net::Ipv4Addr::new(127, 0, 0, 1).into()),
+        net::SocketAddr::V6(_) => (net::Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0).into(),
+                                   net::Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1).into(),
+                                   net::Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1).into()),
+    };
+    let ip = net::UdpSocket::bind((any, 0))
+        .and_then(|sock| sock.connect((probe, 9)).map(|_| sock))
+        .and_then(|sock| sock.local_addr())
+        .map(|local| local.ip())
+        .ok()
+        .filter(|ip| !ip.is_unspecified())
+        .unwrap_or(loopback);
+    Some(net::SocketAddr::new(ip, addr.port()).to_string())
+}
+
+/// Set port of the `host:port` address if port is not specified or is `0`
+pub fn with_port(addr: &str, port: u16) -> String {
+    if addr.ends_with(']') {
+        // ipv6 address without port
+        return format!("{}:{}", addr, port)
+    }
+    match addr.rfind(':') {
+        Some(pos) if addr.starts_with('[') || !addr[..pos].contains(':') => {
+            match addr[pos+1..].parse::<u16>() {
+                Ok(0) | Err(_) => format!("{}:{}", &addr[..pos], port),
+                Ok(_) => addr.to_owned(),
+            }
+        },
+        // ipv6 address without brackets
+        Some(_) => format!("[{}]:{}", addr, port),
+        None => format!("{}:{}", addr, port),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net;
+
+    use super::*;
+
+    #[test]
+    fn test_with_port() {
+        assert_eq!(with_port("127.0.0.1:0", 8080), "127.0.0.1:8080");
+        assert_eq!(with_port("127.0.0.1:9000", 8080), "127.0.0.1:9000");
+        assert_eq!(with_port("127.0.0.1", 8080), "127.0.0.1:8080");
+        assert_eq!(with_port("localhost", 8080), "localhost:8080");
+        assert_eq!(with_port("[::1]:0", 8080), "[::1]:8080");
+        assert_eq!(with_port("[::1]:9000", 8080), "[::1]:9000");
+        assert_eq!(with_port("[::1]", 8080), "[::1]:8080");
+        assert_eq!(with_port("::1", 8080), "[::1]:8080");
+        assert_eq!(with_port("fe80::1:2", 8080), "[fe80::1:2]:8080");
+    }
+
+    #[test]
+    fn test_specify_host() {
+        assert_eq!(specify_host("127.0.0.1:8080"), None);
+        assert_eq!(specify_host("localhost:8080"), None);
+        assert_eq!(specify_host("[::1]:8080"), None);
+
+        let addr: net::SocketAddr = specify_host("0.0.0.0:8080").unwrap().parse().unwrap();
+        assert!(addr.is_ipv4() && !addr.ip().is_unspecified());
+        assert_eq!(addr.port(), 8080);
+        let addr: net::SocketAddr = specify_host("[::]:8080").unwrap().parse().unwrap();
+        assert!(addr.is_ipv6() && !addr.ip().is_unspecified());
+        assert_eq!(addr.port(), 8080);
+    }
 }
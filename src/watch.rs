@@ -0,0 +1,40 @@
+use actix::prelude::*;
+
+use msgs;
+use actor::ActorId;
+use world::World;
+
+
+/// Reason of actor termination
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TerminationReason {
+    /// Actor is stopped or unregistered
+    Stopped,
+    /// Node of the actor left cluster or restarted
+    NodeGone,
+    /// Connection to the node of the actor is lost
+    Unreachable,
+}
+
+/// Watched actor is terminated
+///
+/// Watcher receives notification once, watch has to be renewed
+/// for actor on reconnected node.
+#[derive(Message, Clone, Debug)]
+pub struct Terminated {
+    pub actor: ActorId,
+    pub reason: TerminationReason,
+}
+
+impl World {
+    /// Watch local or remote actor, `watcher` receives `Terminated`
+    /// notification when actor stops or its node becomes unreachable.
+    ///
+    /// Actor stop is reported by nodes that speak protocol version 6,
+    /// for older nodes only node failures are reported. Stop of idle actor
+    /// is detected if actor is registered with `RemoteActor::addr()`,
+    /// otherwise stop is detected when message finds actor mailbox closed.
+    pub fn watch(world: &Addr<Syn, World>, actor: ActorId, watcher: Recipient<Syn, Terminated>) {
+        world.do_send(msgs::Watch{actor: actor, watcher: watcher})
+    }
+}
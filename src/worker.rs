@@ -1,102 +1,570 @@
 use std::io;
+use std::cell::Cell;
+use std::rc::Rc;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::collections::HashMap;
 
-use futures::unsync::oneshot::channel;
-use tokio_io::{AsyncRead, AsyncWrite};
+use futures::unsync::oneshot::{channel, Receiver};
+use tokio_io::AsyncRead;
 use tokio_io::io::WriteHalf;
+#[allow(deprecated)]
 use tokio_io::codec::FramedRead;
 use actix::prelude::*;
 
 use msgs;
-use msgs::NodeConnected;
+use actor::ActorId;
+use watch::TerminationReason;
+use auth::Auth;
+use compress::Compression;
+use node::{NetworkNode, NodeId, Peer};
+use utils::Io;
 use world::World;
-use recipient::RemoteMessageHandler;
-use protocol::{Request, Response, NetworkServerCodec};
+use recipient::{self, RemoteMessageHandler};
+use protocol::{self, CompressionCell, Frame, Handshake, Request, Response,
+               NetworkCodec, TypeInfo};
 
-/// Worker accepts messages from other network hosts and
-/// pass them to local recipients
-pub struct NetworkWorker<T> where T: AsyncRead + AsyncWrite {
+/// Default interval of `Ping` requests, in seconds
+pub(crate) const HEARTBEAT_INTERVAL: u64 = 5;
+/// Number of heartbeat intervals without frames from peer before
+/// connection is closed
+const MISSED_HEARTBEATS: u32 = 3;
+
+/// Worker is responsible for single connection to other network host.
+///
+/// Connection is symmetric, worker accepts messages from other network host and
+/// pass them to local recipients, and sends requests of the bound network node.
+pub struct NetworkWorker {
     id: usize,
+    local: NodeId,
+    auth: Option<Auth>,
+    authenticated: bool,
+    closing: bool,
+    version: u16,
+    certificates: Vec<Vec<u8>>,
+    peer: Option<Peer>,
     net: Addr<Unsync, World>,
+    world: Option<Addr<Syn, World>>,
+    node: Option<Addr<Unsync, NetworkNode>>,
     handlers: HashMap<&'static str, Arc<RemoteMessageHandler>>,
-    framed: actix::io::FramedWrite<WriteHalf<T>, NetworkServerCodec>,
+    compression: Option<(Compression, usize)>,
+    compress: CompressionCell,
+    heartbeat: Duration,
+    ping: bool,
+    seen: Instant,
+    framed: actix::io::FramedWrite<WriteHalf<Box<Io>>, NetworkCodec>,
 }
 
-impl<T> NetworkWorker<T>
-    where T: AsyncRead + AsyncWrite + 'static
-{
-    pub fn start(id: usize, io: T,
+impl NetworkWorker {
+    /// Start worker for the connection.
+    ///
+    /// `node` is known for outbound connections, for inbound connections
+    /// world binds network node after handshake. `certificates` is peer
+    /// certificate chain for TLS connections. If `secret` is set, peer has
+    /// to answer handshake challenge before any other request is accepted.
+    /// Outgoing frames are compressed with `compression` if peer supports it.
+    /// Peer that answers pings is pinged every `heartbeat` interval.
+    /// Actor references in received messages are bound to `world`.
+    pub fn start(id: usize, io: Box<Io>, certificates: Vec<Vec<u8>>,
+                 mut handshake: Handshake, secret: Option<Arc<Vec<u8>>>,
+                 compression: Option<(Compression, usize)>, heartbeat: Duration,
                  handlers: HashMap<&'static str, Arc<RemoteMessageHandler>>,
-                 net: Addr<Unsync, World>) -> Addr<Unsync, Self>
+                 net: Addr<Unsync, World>, world: Option<Addr<Syn, World>>,
+                 node: Option<Addr<Unsync, NetworkNode>>) -> Addr<Unsync, Self>
     {
         Actor::create(move |ctx| {
             let (r, w) = io.split();
+            let local = handshake.id;
+            let auth = secret.map(|secret| Auth::new(secret, local));
+            handshake.challenge = auth.as_ref().map(|a| a.challenge().to_owned());
 
             // read side of the connection
-            ctx.add_stream(FramedRead::new(r, NetworkServerCodec::default()));
+            #[allow(deprecated)]
+            ctx.add_stream(FramedRead::new(r, NetworkCodec::default()));
 
-            // write side of the connection
+            // write side of the connection, compression is enabled after handshake
+            let compress = Rc::new(Cell::new(None));
             let mut framed = actix::io::FramedWrite::new(
-                w, NetworkServerCodec::default(), ctx);
-            framed.write(Response::Handshake);
+                w, NetworkCodec::with_compression(Rc::clone(&compress)), ctx);
+            framed.write(Request::Handshake(handshake).into());
 
-            // send list of supported messages
-            framed.write(Response::Supported(
-                handlers.keys().map(|s| s.to_string()).collect()));
-            NetworkWorker{id: id, net: net, handlers: handlers, framed: framed}
+            NetworkWorker{id: id, local: local, auth: auth, authenticated: false,
+                          closing: false, version: 0,
+                          certificates: certificates, peer: None,
+                          net: net, world: world, node: node, handlers: handlers,
+                          compression: compression, compress: compress,
+                          heartbeat: heartbeat, ping: false, seen: Instant::now(),
+                          framed: framed}
         })
     }
+
+    /// Peer is authenticated, bind connection to network node
+    /// and send list of supported messages
+    fn authenticated(&mut self, ctx: &mut Context<Self>) {
+        self.authenticated = true;
+
+        let types = if let Some(ref peer) = self.peer {
+            self.net.do_send(msgs::NodeConnected {
+                id: peer.id,
+                addr: peer.addr.clone(),
+                roles: peer.roles.clone(),
+                certificates: peer.certificates.clone(),
+                conn: msgs::Connection {
+                    id: self.id, inbound: self.node.is_none(), worker: ctx.address()}});
+
+            // announce only types that peer is allowed to send
+            self.handlers.iter()
+                .filter(|&(_, h)| h.allows(peer))
+                .map(|(s, h)| type_info(s, h)).collect()
+        } else {
+            return
+        };
+        self.announce(types);
+        if self.ping {
+            self.ping_later(ctx);
+        }
+    }
+
+    /// Send `Ping` after heartbeat interval, close connection if peer
+    /// does not send any frame for several intervals
+    fn ping_later(&mut self, ctx: &mut Context<Self>) {
+        ctx.run_later(self.heartbeat, |act, ctx| {
+            if act.closing {
+                return
+            }
+            if act.seen.elapsed() > act.heartbeat * MISSED_HEARTBEATS {
+                warn!("Network node {:?} does not answer pings, close connection",
+                      act.peer.as_ref().map(|p| p.id));
+                return ctx.stop()
+            }
+            act.write_later(Request::Ping.into(), ctx);
+            act.ping_later(ctx);
+        });
+    }
+
+    /// Announce supported message types, versions are announced
+    /// if peer speaks protocol version 2
+    fn announce(&mut self, types: Vec<TypeInfo>) {
+        let resp = if self.version >= 2 {
+            Response::Types(types)
+        } else {
+            Response::Supported(types.into_iter().map(|info| info.type_id).collect())
+        };
+        self.framed.write(resp.into());
+    }
+
+    /// Send reason to peer and close connection, worker stops
+    /// once reason is written
+    fn reject(&mut self, reason: &str, ctx: &mut Context<Self>) {
+        warn!("Reject connection: {}", reason);
+        self.closing = true;
+        self.framed.write(Request::Reject(reason.to_owned()).into());
+        self.framed.close();
+
+        // peer does not read
+        ctx.run_later(Duration::new(5, 0), |_, ctx| ctx.stop());
+    }
+
+    /// Write frame from completed future.
+    ///
+    /// Writer is polled before spawned futures, context has to be polled
+    /// again, otherwise frame stays buffered until next event.
+    fn write_later(&mut self, frame: Frame, ctx: &mut Context<Self>) {
+        self.framed.write(frame);
+        ctx.spawn(actix::fut::ok(()));
+    }
+
+    fn handle_request(&mut self, msg: Request, ctx: &mut Context<Self>) {
+        match msg {
+            Request::Handshake(hs) => {
+                if self.peer.is_some() {
+                    return self.reject("Duplicate handshake", ctx)
+                }
+                debug!("Handshake from network node {} ({})", hs.id, hs.addr);
+                if hs.id == self.local {
+                    return self.reject("Peer uses id of local node", ctx)
+                }
+
+                let version = match protocol::negotiate_version(
+                    protocol::PROTOCOL_VERSIONS, &hs.versions)
+                {
+                    Some(version) => version,
+                    None => return self.reject(&format!(
+                        "Incompatible protocol versions, local: {:?}, network node {}: {:?}",
+                        protocol::PROTOCOL_VERSIONS, hs.id, hs.versions), ctx),
+                };
+                if !hs.capabilities.serializers.iter().any(|s| s == protocol::SERIALIZER_JSON) {
+                    return self.reject(&format!(
+                        "No common serializer, local: {:?}, network node {}: {:?}",
+                        [protocol::SERIALIZER_JSON], hs.id, hs.capabilities.serializers), ctx)
+                }
+                debug!("Use protocol version {} for network node {}", version, hs.id);
+                self.version = version;
+
+                if self.auth.is_some() && hs.challenge.is_none() {
+                    return self.reject("Peer does not authenticate", ctx)
+                }
+                let answer = self.auth.as_ref().and_then(|auth| {
+                    hs.challenge.as_ref().map(|c| auth.sign(c, &hs.id))});
+                if let Some((compression, threshold)) = self.compression {
+                    if hs.capabilities.compression.iter().any(|c| c == compression.name()) {
+                        debug!("Use {} compression for network node {}",
+                               compression.name(), hs.id);
+                        self.compress.set(Some((compression, threshold)));
+                    }
+                }
+                self.ping = hs.capabilities.heartbeat;
+                self.peer = Some(Peer{id: hs.id, addr: hs.addr, roles: hs.roles,
+                                      certificates: self.certificates.clone()});
+
+                if let Some(answer) = answer {
+                    self.framed.write(Request::Auth(answer).into());
+                } else {
+                    self.authenticated(ctx);
+                }
+            },
+            Request::Auth(answer) => {
+                let valid = match (self.auth.as_ref(), self.peer.as_ref()) {
+                    (Some(auth), Some(peer)) => !self.authenticated && auth.verify(&peer.id, &answer),
+                    _ => false,
+                };
+                if valid {
+                    self.authenticated(ctx);
+                } else {
+                    self.reject("Authentication failed", ctx);
+                }
+            },
+            Request::Message(msg_id, type_id, ver, body) => {
+                debug!("RECEIVED MESSAGE: {:?} {:?} {:?}", msg_id, type_id, body);
+                if !self.authenticated {
+                    return self.reject("Message from unauthenticated peer", ctx)
+                }
+
+                let (tx, rx) = channel();
+                let res = match (self.peer.as_ref(), self.handlers.get(type_id.as_str())) {
+                    (Some(peer), Some(handler)) => match protocol::parse_version(&ver) {
+                        Some(version) => recipient::deliver(
+                            self.world.as_ref(), handler, peer, version,
+                            protocol::parse_fingerprint(&ver), body, tx),
+                        None => Err(protocol::ERROR_VERSION_MISMATCH),
+                    },
+                    _ => Err(protocol::ERROR_NOT_SUPPORTED),
+                };
+
+                match res {
+                    Ok(()) => self.respond(msg_id, None, rx, ctx),
+                    Err(code) => {
+                        warn!("Reject message {:?} {:?}, code: {:?}", msg_id, type_id, code);
+                        self.framed.write(Response::Error(msg_id, code).into());
+                    }
+                }
+            },
+            Request::ActorMessage(msg_id, actor, type_id, ver, body) => {
+                if !self.authenticated {
+                    return self.reject("Message from unauthenticated peer", ctx)
+                }
+                let peer = match self.peer {
+                    Some(ref peer) => peer.clone(),
+                    None => return,
+                };
+                let version = match protocol::parse_version(&ver) {
+                    Some(version) => version,
+                    None => return self.framed.write(
+                        Response::Error(msg_id, protocol::ERROR_VERSION_MISMATCH).into()),
+                };
+
+                // world owns actor registry, rejected message drops result
+                // channel, error code has to be sent instead
+                let (tx, rx) = channel();
+                self.net.send(msgs::DeliverToActor{
+                    peer: peer, actor: actor, type_id: type_id, version: version,
+                    fingerprint: protocol::parse_fingerprint(&ver), body: body, tx: tx})
+                    .into_actor(self)
+                    .then(move |res, act, ctx| {
+                        match res {
+                            Ok(Err(code)) => {
+                                warn!("Reject message {:?} for actor {:?}, code: {:?}",
+                                      msg_id, actor, code);
+                                act.write_later(Response::Error(msg_id, code).into(), ctx);
+                            }
+                            _ => act.respond(msg_id, Some(actor), rx, ctx),
+                        }
+                        actix::fut::ok(())
+                    })
+                    .spawn(ctx);
+            },
+            Request::Spawn(msg_id, kind, args) => {
+                if !self.authenticated {
+                    return self.reject("Spawn request from unauthenticated peer", ctx)
+                }
+                let peer = match self.peer {
+                    Some(ref peer) => peer.clone(),
+                    None => return,
+                };
+                self.net.send(msgs::SpawnLocal{peer: peer, kind: kind, args: args})
+                    .into_actor(self)
+                    .then(move |res, act, ctx| {
+                        match res {
+                            Ok(Ok(id)) => act.write_later(
+                                Response::Result(msg_id, id.to_string()).into(), ctx),
+                            Ok(Err(code)) => act.write_later(
+                                Response::Error(msg_id, code).into(), ctx),
+                            Err(_) => (),
+                        }
+                        actix::fut::ok(())
+                    })
+                    .spawn(ctx);
+            },
+            Request::Watch(actor) => {
+                if !self.authenticated {
+                    return self.reject("Watch request from unauthenticated peer", ctx)
+                }
+                if let Some(ref peer) = self.peer {
+                    self.net.do_send(msgs::RemoteWatch{node: peer.id, actor: actor});
+                }
+            },
+            Request::Terminated(actor) => {
+                if !self.authenticated {
+                    return self.reject("Terminated from unauthenticated peer", ctx)
+                }
+                if let Some(ref peer) = self.peer {
+                    self.net.do_send(msgs::ActorTerminated{
+                        actor: ActorId{node: peer.id, id: actor},
+                        reason: TerminationReason::Stopped});
+                }
+            },
+            Request::Topics(topics) => {
+                if !self.authenticated {
+                    return self.reject("Topics from unauthenticated peer", ctx)
+                }
+                if let Some(ref peer) = self.peer {
+                    self.net.do_send(msgs::NodeTopics{node: peer.id, topics: topics});
+                }
+            },
+            Request::Publish(topic, type_id, ver, data) => {
+                if !self.authenticated {
+                    return self.reject("Message from unauthenticated peer", ctx)
+                }
+                let version = match protocol::parse_version(&ver) {
+                    Some(version) => version,
+                    None => {
+                        warn!("Invalid version {:?} of topic {}", ver, topic);
+                        return
+                    }
+                };
+                if let Some(ref peer) = self.peer {
+                    self.net.do_send(msgs::TopicMessage{
+                        peer: peer.clone(), topic: topic, type_id: type_id, version: version,
+                        fingerprint: protocol::parse_fingerprint(&ver), data: data});
+                }
+            },
+            Request::Stop(actor) => {
+                if !self.authenticated {
+                    return self.reject("Stop request from unauthenticated peer", ctx)
+                }
+                if let Some(ref peer) = self.peer {
+                    self.net.do_send(msgs::RemoteStop{node: peer.id, actor: actor});
+                }
+            },
+            Request::Leader(role, term) => {
+                if !self.authenticated {
+                    return self.reject("Leader from unauthenticated peer", ctx)
+                }
+                if let Some(ref peer) = self.peer {
+                    self.net.do_send(msgs::LeaderAnnounced{node: peer.id, role: role, term: term});
+                }
+            },
+            Request::Names(names) => {
+                if !self.authenticated {
+                    return self.reject("Names from unauthenticated peer", ctx)
+                }
+                if let Some(ref peer) = self.peer {
+                    self.net.do_send(msgs::NodeNames{node: peer.id, names: names});
+                }
+            },
+            Request::Reject(reason) => {
+                error!("Connection is rejected by network node: {}", reason);
+                ctx.stop();
+            },
+            Request::Ping => self.framed.write(Response::Pong.into()),
+            Request::Pong => debug!("Unexpected pong request from network node {:?}",
+                                    self.peer.as_ref().map(|p| p.id)),
+        }
+    }
+
+    /// Send handler result to peer.
+    ///
+    /// Handler drops result channel if recipient mailbox is closed,
+    /// stopped actor is reported to world.
+    fn respond(&mut self, msg_id: u64, actor: Option<u64>,
+               rx: Receiver<Result<String, u16>>, ctx: &mut Context<Self>)
+    {
+        rx.into_actor(self)
+            .then(move |res, act, ctx| {
+                match res {
+                    Ok(Ok(ref res)) if res.len() > protocol::MAX_PAYLOAD_SIZE => {
+                        warn!("Result of message {:?} is too large: {}", msg_id, res.len());
+                        act.write_later(
+                            Response::Error(msg_id, protocol::ERROR_TOO_LARGE).into(), ctx);
+                    },
+                    Ok(Ok(res)) => act.write_later(Response::Result(msg_id, res).into(), ctx),
+                    Ok(Err(code)) => {
+                        act.write_later(Response::Error(msg_id, code).into(), ctx);
+                        // actor mailbox is closed
+                        if code == protocol::ERROR_STOPPED {
+                            if let Some(actor) = actor {
+                                act.net.do_send(msgs::ActorStopped(actor));
+                            }
+                        }
+                    },
+                    // handler dropped reply channel, actor can still be running
+                    Err(_) => act.write_later(
+                        Response::Error(msg_id, protocol::ERROR_UNAVAILABLE).into(), ctx),
+                }
+                actix::fut::ok(())
+            })
+            .spawn(ctx);
+    }
+
+    fn handle_response(&mut self, msg: Response) {
+        if !self.authenticated {
+            return
+        }
+
+        match msg {
+            Response::Supported(types) => {
+                // peer does not announce versions, it sends version 1
+                if let Some(ref peer) = self.peer {
+                    self.net.do_send(msgs::NodeSupportedTypes {
+                        node: peer.id,
+                        types: types.into_iter().map(|type_id| TypeInfo{
+                            type_id: type_id, min_version: 1, max_version: 1,
+                            fingerprint: 0}).collect()
+                    });
+                }
+            },
+            Response::Types(types) => {
+                if let Some(ref peer) = self.peer {
+                    self.net.do_send(msgs::NodeSupportedTypes {
+                        node: peer.id,
+                        types: types
+                    });
+                }
+            },
+            // answer to heartbeat, time of the frame is already recorded
+            Response::Pong => (),
+            msg => {
+                if let Some(ref node) = self.node {
+                    node.do_send(msg);
+                }
+            }
+        }
+    }
 }
 
-impl<T> Actor for NetworkWorker<T> where T: AsyncRead + AsyncWrite + 'static {
+fn type_info(type_id: &str, handler: &Arc<RemoteMessageHandler>) -> TypeInfo {
+    let (min, max) = handler.versions();
+    TypeInfo{type_id: type_id.to_owned(), min_version: min, max_version: max,
+             fingerprint: handler.fingerprint()}
+}
+
+impl Actor for NetworkWorker {
     type Context = Context<Self>;
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        self.net.do_send(msgs::WorkerDisconnected(self.id));
+        if let Some(ref node) = self.node {
+            node.do_send(msgs::ConnectionClosed(self.id));
+        }
+    }
 }
 
-impl<T> actix::io::WriteHandler<io::Error> for NetworkWorker<T>
-    where T: AsyncRead + AsyncWrite + 'static {
+impl actix::io::WriteHandler<io::Error> for NetworkWorker {
+    /// Write side is closed, pending frames are written
+    fn finished(&mut self, ctx: &mut Self::Context) {
+        ctx.stop();
+    }
 }
 
-impl<T> StreamHandler<Request, io::Error> for NetworkWorker<T>
-    where T: AsyncRead + AsyncWrite + 'static
-{
+impl StreamHandler<Frame, io::Error> for NetworkWorker {
     fn finished(&mut self, ctx: &mut Self::Context) {
-        self.net.do_send(msgs::WorkerDisconnected(self.id));
         ctx.stop();
     }
 
-    /// This is main event loop for client connection
-    fn handle(&mut self, msg: Request, ctx: &mut Self::Context) {
+    /// This is main event loop for the connection
+    fn handle(&mut self, msg: Frame, ctx: &mut Self::Context) {
+        self.seen = Instant::now();
+        if self.closing {
+            return
+        }
         match msg {
-            Request::Handshake(addr) => self.net.do_send(NodeConnected(addr)),
-            Request::Message(msg_id, type_id, _, body) => {
-                debug!("RECEIVED MESSAGE: {:?} {:?} {:?}", msg_id, type_id, body);
-                if let Some(ref handler) = self.handlers.get(type_id.as_str()) {
-                    let (tx, rx) = channel();
-                    handler.handle(body, tx);
-
-                    rx.into_actor(self)
-                        .then(move |res, act, _| {
-                            match res {
-                                Ok(res) => act.framed.write(Response::Result(msg_id, res)),
-                                Err(_) => (),
-                            }
-                            actix::fut::ok(())
-                        })
-                        .spawn(ctx)
+            Frame::Request(req) => self.handle_request(req, ctx),
+            Frame::Response(resp) => self.handle_response(resp),
+        }
+    }
+}
+
+/// Send request to remote host
+impl Handler<Request> for NetworkWorker {
+    type Result = ();
+
+    fn handle(&mut self, msg: Request, _: &mut Self::Context) {
+        match msg {
+            // peer does not know request
+            Request::ActorMessage(msg_id, ..) if self.version < 3 => {
+                if let Some(ref node) = self.node {
+                    node.do_send(Response::Error(msg_id, protocol::ERROR_NOT_SUPPORTED));
                 }
+                return
             },
-            _ => {
-                println!("CLIENT REQ: {:?}", msg);
-            }
+            Request::Spawn(msg_id, ..) if self.version < 5 => {
+                if let Some(ref node) = self.node {
+                    node.do_send(Response::Error(msg_id, protocol::ERROR_NOT_SUPPORTED));
+                }
+                return
+            },
+            Request::Watch(_) | Request::Terminated(_) if self.version < 6 => return,
+            Request::Publish(..) if self.version < 7 => return,
+            Request::Stop(_) if self.version < 9 => return,
+            // frame with large payload would close connection
+            Request::Message(msg_id, _, _, ref data) |
+            Request::ActorMessage(msg_id, _, _, _, ref data) |
+            Request::Spawn(msg_id, _, ref data) if data.len() > protocol::MAX_PAYLOAD_SIZE => {
+                warn!("Payload of request {:?} is too large: {}", msg_id, data.len());
+                if let Some(ref node) = self.node {
+                    node.do_send(Response::Error(msg_id, protocol::ERROR_TOO_LARGE));
+                }
+                return
+            },
+            Request::Publish(ref topic, _, _, ref data)
+                if data.len() > protocol::MAX_PAYLOAD_SIZE =>
+            {
+                warn!("Message for topic {:?} is too large: {}", topic, data.len());
+                return
+            },
+            // unauthenticated peer receives names, topics and leaders after authentication
+            Request::Names(_) if self.version < 4 || !self.authenticated => return,
+            Request::Topics(_) if self.version < 7 || !self.authenticated => return,
+            Request::Leader(..) if self.version < 8 || !self.authenticated => return,
+            _ => (),
+        }
+        if !self.closing {
+            self.framed.write(msg.into());
         }
     }
 }
 
+/// Connection is bound to network node
+impl Handler<msgs::AttachNode> for NetworkWorker {
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::AttachNode, _: &mut Self::Context) {
+        self.node = Some(msg.0);
+    }
+}
+
 /// World is dead
-impl<T> Handler<msgs::StopWorker> for NetworkWorker<T>
-    where T: AsyncRead + AsyncWrite + 'static
-{
+impl Handler<msgs::StopWorker> for NetworkWorker {
     type Result = ();
 
     fn handle(&mut self, _: msgs::StopWorker, ctx: &mut Self::Context) {
@@ -105,13 +573,18 @@ impl<T> Handler<msgs::StopWorker> for NetworkWorker<T>
 }
 
 /// New recipient is registered
-impl<T> Handler<msgs::ProvideRecipient> for NetworkWorker<T>
-    where T: AsyncRead + AsyncWrite + 'static
-{
+impl Handler<msgs::ProvideRecipient> for NetworkWorker {
     type Result = ();
 
     fn handle(&mut self, msg: msgs::ProvideRecipient, _: &mut Self::Context) {
-        self.framed.write(Response::Supported(vec![msg.type_id.to_owned()]));
+        // unauthenticated peer receives full list after authentication
+        let allowed = match self.peer {
+            Some(ref peer) => self.authenticated && msg.handler.allows(peer),
+            None => false,
+        };
+        if allowed {
+            self.announce(vec![type_info(msg.type_id, &msg.handler)]);
+        }
         self.handlers.insert(msg.type_id, msg.handler);
     }
 }
@@ -1,41 +1,95 @@
 use std::{io, net};
-use std::any::Any;
+use std::any::{Any, TypeId};
+use std::path::Path;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
 use std::sync::Arc;
 use std::time::Duration;
 use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::Entry;
 
 use actix::prelude::*;
+use actix::prelude::{Response as ActixResponse};
 use actix::actors::signal;
 use futures::Future;
+use futures::unsync::oneshot;
 use serde::Serialize;
 use serde::de::DeserializeOwned;
-use tokio_core::net::{TcpStream, TcpListener};
 use tokio_core::reactor::Timeout;
+use uuid::Uuid;
 
+use actor::{self, ActorId, Probe};
+use error::{RegistrationError, RemoteError};
 use msgs;
 use utils;
-use worker::NetworkWorker;
-use node::{NetworkNode, NodeInformation};
-use remote::{Remote, RemoteMessage};
-use recipient::{Provider, RecipientProxy,
+use utils::Io;
+use worker::{NetworkWorker, HEARTBEAT_INTERVAL};
+use node::{NetworkNode, NodeId, NodeInformation, Peer};
+use compress::Compression;
+use protocol::{self, Capabilities, Handshake, NameInfo, Request};
+use transport::{self, Listener, Transport, TcpTransport, MemTransport};
+#[cfg(unix)]
+use transport::UnixTransport;
+#[cfg(feature="websocket")]
+use ws::WsTransport;
+use migration::Migrations;
+use policy::Policy;
+use remote::{FromPeer, Remote, RemoteMessage};
+use spawn::{self, ActorFactory};
+use watch::{Terminated, TerminationReason};
+use election::Election;
+use recipient::{self, PeerProvider, Provider, RecipientProxy,
                 RecipientProxySender, RemoteMessageHandler};
+#[cfg(feature="rust-tls")]
+use tls::TlsConfig;
 
 
 struct Proxy {
     addr: Box<Any>,
     service: Recipient<Unsync, msgs::TypeSupported>,
+    gone: Recipient<Unsync, msgs::NodeGone>,
+    started: Recipient<Unsync, msgs::WorldStarted>,
 }
 
 pub struct World {
+    id: NodeId,
     addr: String,
+    advertise: Option<String>,
+    secret: Option<Arc<Vec<u8>>>,
+    compression: Option<(Compression, usize)>,
+    heartbeat: Duration,
+    roles: Vec<String>,
     addrs: HashMap<String, NodeInformation>,
-    nodes: HashMap<String, Addr<Unsync, NetworkNode>>,
-    types: HashMap<String, HashSet<String>>,
-    sockets: HashMap<net::SocketAddr, net::TcpListener>,
+    peers: HashMap<String, Addr<Unsync, NetworkNode>>,
+    nodes: HashMap<NodeId, Addr<Unsync, NetworkNode>>,
+    node_peers: HashMap<NodeId, String>,
+    node_info: HashMap<NodeId, Peer>,
+    outbound: HashMap<usize, (String, Addr<Unsync, NetworkNode>)>,
+    types: HashMap<String, HashSet<NodeId>>,
+    message_types: HashMap<&'static str, TypeId>,
+    transports: HashMap<String, Rc<Transport>>,
+    listeners: Vec<Box<Listener>>,
     wid: usize,
-    workers: HashMap<usize, Addr<Unsync, NetworkWorker<TcpStream>>>,
+    workers: HashMap<usize, Addr<Unsync, NetworkWorker>>,
     handlers: HashMap<&'static str, Arc<RemoteMessageHandler>>,
     recipients: HashMap<&'static str, Proxy>,
+    saddr: Option<Addr<Syn, World>>,
+    aid: u64,
+    actors: HashMap<u64, HashMap<&'static str, Arc<RemoteMessageHandler>>>,
+    probes: HashMap<u64, Probe>,
+    spawned: HashMap<u64, NodeId>,
+    names: HashMap<String, NameInfo>,
+    node_names: HashMap<NodeId, Vec<NameInfo>>,
+    factories: HashMap<&'static str, Arc<ActorFactory>>,
+    watchers: HashMap<ActorId, Vec<Recipient<Syn, Terminated>>>,
+    remote_watchers: HashMap<u64, HashSet<NodeId>>,
+    sid: u64,
+    subscriptions: HashMap<String, HashMap<u64, (&'static str, Arc<RemoteMessageHandler>)>>,
+    node_topics: HashMap<NodeId, HashSet<String>>,
+    node_roles: HashMap<NodeId, Vec<String>>,
+    elections: HashMap<String, Election>,
+    #[cfg(feature="rust-tls")]
+    tls: Option<TlsConfig>,
     exit: bool,
 }
 
@@ -44,32 +98,127 @@ impl Actor for World {
 }
 
 impl World {
+    /// Create world and bind to address.
+    ///
+    /// Address is either `host:port`, `mem://name`, `unix://path`
+    /// on unix platforms or `ws://host:port` with `websocket` feature.
+    /// Address with custom scheme requires `World::unbound()`.
     pub fn new(addr: String) -> io::Result<World> {
-        let net = World{addr: addr.clone(),
+        World::unbound(addr).bind_addr()
+    }
+
+    /// Create world without binding to address.
+    ///
+    /// Custom transports can be registered with `World::transport()`
+    /// before address is bound with `World::bind_addr()`.
+    pub fn unbound(addr: String) -> World {
+        let mut net = World{id: NodeId::new(Uuid::new_v4()),
+                        addr: addr,
+                        advertise: None,
+                        secret: None,
+                        compression: None,
+                        heartbeat: Duration::from_secs(HEARTBEAT_INTERVAL),
+                        roles: Vec::new(),
                         addrs: HashMap::new(),
+                        peers: HashMap::new(),
                         nodes: HashMap::new(),
+                        node_peers: HashMap::new(),
+                        node_info: HashMap::new(),
+                        outbound: HashMap::new(),
                         types: HashMap::new(),
-                        sockets: HashMap::new(),
+                        message_types: HashMap::new(),
+                        transports: HashMap::new(),
+                        listeners: Vec::new(),
                         wid: 0,
                         workers: HashMap::new(),
                         handlers: HashMap::new(),
                         recipients: HashMap::new(),
+                        saddr: None,
+                        aid: 0,
+                        actors: HashMap::new(),
+                        probes: HashMap::new(),
+                        spawned: HashMap::new(),
+                        names: HashMap::new(),
+                        node_names: HashMap::new(),
+                        factories: HashMap::new(),
+                        watchers: HashMap::new(),
+                        remote_watchers: HashMap::new(),
+                        sid: 0,
+                        subscriptions: HashMap::new(),
+                        node_topics: HashMap::new(),
+                        node_roles: HashMap::new(),
+                        elections: HashMap::new(),
+                        #[cfg(feature="rust-tls")]
+                        tls: None,
                         exit: false};
-        Ok(net.bind(addr)?)
+        net = net.transport(TcpTransport).transport(MemTransport);
+        #[cfg(unix)]
+        {
+            net = net.transport(UnixTransport);
+        }
+        #[cfg(feature="websocket")]
+        {
+            net = net.transport(WsTransport);
+        }
+        net
+    }
+
+    /// Bind listener to address passed to `World::unbound()`
+    pub fn bind_addr(self) -> io::Result<Self> {
+        let addr = self.addr.clone();
+        self.listen(&addr)
+    }
+
+    /// Register transport for address scheme.
+    ///
+    /// Transport replaces previously registered transport with same scheme.
+    /// Listeners for custom transport are bound with `World::listen()`.
+    pub fn transport<T: Transport>(mut self, transport: T) -> Self {
+        self.transports.insert(transport.scheme().to_owned(), Rc::new(transport));
+        self
+    }
+
+    /// Bind listener to `scheme://address` with registered transport
+    pub fn listen(mut self, addr: &str) -> io::Result<Self> {
+        self.add_listener(addr)?;
+        Ok(self)
+    }
+
+    fn add_listener(&mut self, addr: &str) -> io::Result<()> {
+        let (scheme, addr) = transport::split_addr(addr);
+        let lst = match self.transports.get(scheme) {
+            Some(transport) => transport.listen(addr)?,
+            None => return Err(io::Error::new(
+                io::ErrorKind::Other, format!("Transport is not supported: {}", scheme))),
+        };
+        self.listeners.push(lst);
+        Ok(())
+    }
+
+    /// The unix domain socket path to bind
+    pub fn bind_unix<P: AsRef<Path>>(self, path: P) -> io::Result<Self> {
+        let addr = format!("{}://{}", transport::UNIX_SCHEME, path.as_ref().display());
+        self.listen(&addr)
+    }
+
+    /// The in-process listener name to bind
+    ///
+    /// Worlds in the same process can connect to `mem://name` address
+    /// without network sockets, name has to be unique within process.
+    pub fn bind_mem(self, name: &str) -> io::Result<Self> {
+        self.listen(&format!("{}://{}", transport::MEM_SCHEME, name))
     }
 
     /// The socket address to bind
     ///
     /// To bind multiple addresses this method can be call multiple times.
+    /// Listeners are bound with transport registered for `tcp` scheme.
     pub fn bind<S: net::ToSocketAddrs>(mut self, addr: S) -> io::Result<Self> {
         let mut err = None;
         let mut succ = false;
         for addr in addr.to_socket_addrs()? {
-            match utils::tcp_listener(addr, 256) {
-                Ok(lst) => {
-                    succ = true;
-                    self.sockets.insert(lst.local_addr().unwrap(), lst);
-                },
+            match self.add_listener(&format!("{}://{}", transport::TCP_SCHEME, addr)) {
+                Ok(()) => succ = true,
                 Err(e) => err = Some(e),
             }
         }
@@ -85,46 +234,248 @@ impl World {
         }
     }
 
+    /// Address announced to other nodes.
+    ///
+    /// By default world announces address passed to `World::new()`. If announced
+    /// address does not specify port or port is `0`, port of the bound listener
+    /// is used, so it is possible to bind to `0.0.0.0:0` and announce actual
+    /// ephemeral port. Unspecified address can not be dialed by other nodes,
+    /// if announced address is `0.0.0.0` or `[::]`, world announces address
+    /// of interface that routes to other hosts.
+    pub fn advertise_addr<S: Into<String>>(mut self, addr: S) -> Self {
+        self.advertise = Some(addr.into());
+        self
+    }
+
+    /// Local addresses of bound listeners
+    pub fn local_addrs(&self) -> Vec<net::SocketAddr> {
+        self.listeners.iter().filter_map(|lst| lst.local_addr().parse().ok()).collect()
+    }
+
+    /// Add node role.
+    ///
+    /// Roles are announced to other nodes, authorization policies
+    /// can allow message types for nodes with specific roles.
+    /// Roles are self-declared by each node, any node that can connect
+    /// can claim any role unless cluster secret or TLS client
+    /// authentication restricts who can connect.
+    pub fn role<S: Into<String>>(mut self, role: S) -> Self {
+        self.roles.push(role.into());
+        self
+    }
+
+    /// Cluster secret.
+    ///
+    /// If secret is set, node connections authenticate with challenge/response
+    /// handshake, peers that do not know secret are rejected before
+    /// any message exchange. Answers are bound to both node ids, still
+    /// secret does not protect against active man-in-the-middle, use TLS
+    /// on untrusted networks.
+    pub fn secret<S: Into<Vec<u8>>>(mut self, secret: S) -> Self {
+        self.secret = Some(Arc::new(secret.into()));
+        self
+    }
+
+    /// Compress frames larger than `threshold` bytes.
+    ///
+    /// Compression is used only for nodes that announce support
+    /// of the algorithm in handshake, other nodes receive plain frames.
+    pub fn compression(mut self, algorithm: Compression, threshold: usize) -> Self {
+        self.compression = Some((algorithm, threshold));
+        self
+    }
+
+    /// Interval of `Ping` requests, 5 seconds by default.
+    ///
+    /// Connection is closed if node that answers pings does not send
+    /// any frame for three intervals.
+    pub fn heartbeat(mut self, interval: Duration) -> Self {
+        self.heartbeat = interval;
+        self
+    }
+
+    /// Use mutual TLS for all node connections.
+    ///
+    /// Nodes with IP, `unix://` or `mem://` addresses are verified
+    /// against `TlsConfig::domain()`, without it connection fails.
+    #[cfg(feature="rust-tls")]
+    pub fn tls(mut self, config: TlsConfig) -> Self {
+        self.tls = Some(config);
+        self
+    }
+
+    /// Set node uuid.
+    ///
+    /// By default random uuid is generated on every start. Stable uuid allows
+    /// other nodes to detect restart of this node.
+    pub fn uuid(mut self, uuid: Uuid) -> Self {
+        self.id = NodeId::new(uuid);
+        self
+    }
+
+    /// Local node id
+    pub fn node_id(&self) -> NodeId {
+        self.id
+    }
+
     /// Register network node
     pub fn add_node<S: Into<String>>(mut self, addr: Option<S>) -> Self {
-        addr.map(|addr| {
+        if let Some(addr) = addr {
             let addr = addr.into();
             self.addrs.insert(addr.clone(), NodeInformation::new(addr));
-        });
+        }
         self
     }
 
     /// Create remote recipient for specific message type
+    ///
+    /// Panics if type id is already used by different message type.
     pub fn get_recipient<M>(&mut self) -> Recipient<Remote, M>
         where M: RemoteMessage + 'static,
               M::Result: Send + Serialize + DeserializeOwned
     {
+        match self.try_get_recipient() {
+            Ok(recipient) => recipient,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Create remote recipient for specific message type
+    ///
+    /// Fails if type id is already used by different message type.
+    pub fn try_get_recipient<M>(&mut self) -> Result<Recipient<Remote, M>, RegistrationError>
+        where M: RemoteMessage + 'static,
+              M::Result: Send + Serialize + DeserializeOwned
+    {
+        self.check_type(M::type_id(), TypeId::of::<M>())?;
+
         if let Some(info) = self.recipients.get(M::type_id()) {
-            if let Some(&(_, ref saddr)) = info.addr.downcast_ref
+            if let Some((_, saddr)) = info.addr.downcast_ref
                 ::<(Addr<Unsync, RecipientProxy<M>>, Addr<Syn, RecipientProxy<M>>)>()
             {
-                return Recipient::new(RecipientProxySender::new(saddr.clone()))
+                return Ok(Recipient::new(RecipientProxySender::new(saddr.clone())))
             }
         }
 
         let (addr, saddr): (Addr<Unsync, RecipientProxy<M>>,
                             Addr<Syn, RecipientProxy<M>>) = RecipientProxy::new().start();
         self.recipients.insert(
-            M::type_id(), Proxy{addr: Box::new(addr.clone()),
-                                service: addr.clone().recipient()});
+            M::type_id(), Proxy{addr: Box::new((addr.clone(), saddr.clone())),
+                                service: addr.clone().recipient(),
+                                gone: addr.clone().recipient(),
+                                started: addr.clone().recipient()});
 
-        return Recipient::new(RecipientProxySender::new(saddr))
+        Ok(Recipient::new(RecipientProxySender::new(saddr)))
+    }
+
+    /// Check that type id is not used by different message type
+    fn check_type(&mut self, type_id: &'static str, tp: TypeId)
+                  -> Result<(), RegistrationError>
+    {
+        match self.message_types.get(type_id) {
+            Some(registered) if *registered != tp =>
+                return Err(RegistrationError::Duplicate(type_id.to_owned())),
+            Some(_) => return Ok(()),
+            None => (),
+        }
+        self.message_types.insert(type_id, tp);
+        Ok(())
+    }
+
+    /// Register provider in world
+    pub(crate) fn provide<M>(world: &Addr<Syn, World>, handler: Arc<RemoteMessageHandler>)
+                  -> Box<Future<Item=(), Error=RegistrationError>>
+        where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+    {
+        Box::new(
+            world.send(msgs::RegisterProvider{
+                type_id: M::type_id(), message_type: TypeId::of::<M>(), handler: handler})
+                .then(|res| match res {
+                    Ok(res) => res,
+                    Err(_) => Err(RegistrationError::Stopped),
+                }))
     }
 
     /// Register remote recipient provider.
     ///
-    /// Announce recipient availability to all connected nodes.
+    /// Announce recipient availability to all connected nodes. Returned future
+    /// fails if type id is already registered for different message type.
     pub fn register_recipient<M>(world: &Addr<Syn, World>, recipient: Recipient<Syn, M>)
+        -> Box<Future<Item=(), Error=RegistrationError>>
         where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
     {
-        let r = Provider{recipient: recipient};
-        world.do_send(msgs::ProvideRecipient{
-            type_id: M::type_id(), handler: Arc::new(r)})
+        World::register_recipient_with_policy(world, recipient, Policy::allow_all())
+    }
+
+    /// Register remote recipient provider with authorization policy.
+    ///
+    /// Recipient availability is announced only to nodes allowed by the policy,
+    /// messages from other nodes are rejected with permission denied error.
+    pub fn register_recipient_with_policy<M>(world: &Addr<Syn, World>,
+                                             recipient: Recipient<Syn, M>, policy: Policy)
+        -> Box<Future<Item=(), Error=RegistrationError>>
+        where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+    {
+        World::register_recipient_with_versions(
+            world, recipient, policy, M::version(), M::version())
+    }
+
+    /// Register remote recipient provider that supports range of message versions.
+    ///
+    /// Versions are announced to connected nodes, nodes send messages only
+    /// to providers that support version of the message.
+    pub fn register_recipient_with_versions<M>(world: &Addr<Syn, World>,
+                                               recipient: Recipient<Syn, M>, policy: Policy,
+                                               min_version: u32, max_version: u32)
+        -> Box<Future<Item=(), Error=RegistrationError>>
+        where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+    {
+        let r = Provider{recipient: recipient, policy: policy,
+                         versions: (min_version, max_version),
+                         migrations: Arc::new(Migrations::new())};
+        World::provide::<M>(world, Arc::new(r))
+    }
+
+    /// Register remote recipient provider with schema migrations.
+    ///
+    /// Provider accepts every older message version that can be upcasted
+    /// to current version of the message, results are downcasted
+    /// to the version of the caller.
+    pub fn register_recipient_with_migrations<M>(world: &Addr<Syn, World>,
+                                                 recipient: Recipient<Syn, M>, policy: Policy,
+                                                 migrations: Migrations)
+        -> Box<Future<Item=(), Error=RegistrationError>>
+        where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+    {
+        let r = Provider{recipient: recipient, policy: policy,
+                         versions: (migrations.min_version(M::version()), M::version()),
+                         migrations: Arc::new(migrations)};
+        World::provide::<M>(world, Arc::new(r))
+    }
+
+    /// Register remote recipient provider that receives peer information
+    /// with every message.
+    ///
+    /// Announce recipient availability to all connected nodes.
+    pub fn register_peer_recipient<M>(world: &Addr<Syn, World>,
+                                      recipient: Recipient<Syn, FromPeer<M>>)
+        -> Box<Future<Item=(), Error=RegistrationError>>
+        where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+    {
+        World::register_peer_recipient_with_policy(world, recipient, Policy::allow_all())
+    }
+
+    /// Register remote recipient provider that receives peer information
+    /// with every message, only nodes allowed by policy can send messages.
+    pub fn register_peer_recipient_with_policy<M>(world: &Addr<Syn, World>,
+                                                  recipient: Recipient<Syn, FromPeer<M>>,
+                                                  policy: Policy)
+        -> Box<Future<Item=(), Error=RegistrationError>>
+        where M: RemoteMessage + 'static, M::Result: Send + Serialize + DeserializeOwned
+    {
+        let r = PeerProvider{recipient: recipient, policy: policy,
+                             versions: (M::version(), M::version())};
+        World::provide::<M>(world, Arc::new(r))
     }
 
     fn stop(&mut self, ctx: &mut Context<Self>) {
@@ -159,34 +510,430 @@ impl World {
                 }));
     }
 
+    fn handshake(&self) -> Handshake {
+        Handshake{id: self.id, addr: self.addr.clone(),
+                  challenge: None, roles: self.roles.clone(),
+                  versions: protocol::PROTOCOL_VERSIONS.to_vec(),
+                  capabilities: Capabilities{
+                      serializers: vec![protocol::SERIALIZER_JSON.to_owned()],
+                      compression: Compression::supported().iter()
+                          .map(|c| c.name().to_owned()).collect(),
+                      heartbeat: true}}
+    }
+
+    /// Start worker for accepted connection
+    fn accept(&mut self, io: Box<Io>, peer: String, ctx: &mut Context<Self>) {
+        #[cfg(feature="rust-tls")]
+        {
+            if let Some(ref tls) = self.tls {
+                tls.accept(io)
+                    .into_actor(self)
+                    .map(|(io, certs), act, ctx| act.start_worker(io, certs, None, ctx))
+                    .map_err(move |err, _, _| {
+                        error!("TLS handshake with {} failed: {}", peer, err)})
+                    .spawn(ctx);
+                return
+            }
+        }
+        debug!("Accepted connection from {}", peer);
+        self.start_worker(io, Vec::new(), None, ctx);
+    }
+
+    /// Start worker for established connection, `node` is dialed
+    /// address and network node of outbound connection
+    fn start_worker(&mut self, io: Box<Io>, certificates: Vec<Vec<u8>>,
+                    node: Option<(String, Addr<Unsync, NetworkNode>)>,
+                    ctx: &mut Context<Self>)
+    {
+        self.wid += 1;
+        let addr = NetworkWorker::start(
+            self.wid, io, certificates, self.handshake(), self.secret.clone(),
+            self.compression, self.heartbeat, self.handlers.clone(),
+            ctx.address(), self.saddr.clone(), node.as_ref().map(|n| n.1.clone()));
+        self.workers.insert(self.wid, addr);
+        if let Some(node) = node {
+            self.outbound.insert(self.wid, node);
+        }
+    }
+
+    /// Remove node from registry and notify recipient proxies
+    fn node_gone(&mut self, id: NodeId) {
+        if self.nodes.remove(&id).is_some() {
+            info!("Network node is gone: {}", id);
+        }
+        self.node_peers.remove(&id);
+        self.node_info.remove(&id);
+        for nodes in self.types.values_mut() {
+            nodes.remove(&id);
+        }
+        self.node_names.remove(&id);
+        self.node_topics.remove(&id);
+        self.node_roles.remove(&id);
+        self.elect_all();
+        for nodes in self.remote_watchers.values_mut() {
+            nodes.remove(&id);
+        }
+        self.node_terminated(id, TerminationReason::NodeGone);
+
+        // actors spawned by the node can not be stopped anymore
+        let spawned: Vec<u64> = self.spawned.iter()
+            .filter(|&(_, node)| *node == id)
+            .map(|(actor, _)| *actor).collect();
+        for actor in spawned {
+            self.actor_stopped(actor);
+        }
+        for proxy in self.recipients.values() {
+            let _ = proxy.gone.do_send(msgs::NodeGone(id));
+        }
+    }
+
+    /// Peer information of local node, used for messages to local actors
+    fn local_peer(&self) -> Peer {
+        Peer{id: self.id, addr: self.addr.clone(),
+             roles: self.roles.clone(), certificates: Vec::new()}
+    }
+
+    /// Peer information of node that sent actor reference, `None` if
+    /// node is not connected anymore
+    fn origin_peer(&self, origin: Option<NodeId>) -> Option<Peer> {
+        match origin {
+            None => Some(self.local_peer()),
+            Some(id) if id == self.id => Some(self.local_peer()),
+            Some(id) => self.node_info.get(&id).cloned(),
+        }
+    }
+
+    /// Add local actor, returns actor id
+    fn add_actor(&mut self, handlers: HashMap<&'static str, Arc<RemoteMessageHandler>>,
+                 alive: Option<Probe>) -> u64 {
+        self.aid += 1;
+        self.actors.insert(self.aid, handlers);
+        if let Some(alive) = alive {
+            self.probes.insert(self.aid, alive);
+        }
+        self.aid
+    }
+
+    fn check_actors_later(&self, ctx: &mut Context<Self>) {
+        ctx.run_later(Duration::new(1, 0), |act, ctx| {
+            act.check_actors();
+            act.check_actors_later(ctx);
+        });
+    }
+
+    /// Remove registrations of stopped actors
+    fn check_actors(&mut self) {
+        let stopped: Vec<u64> = self.probes.iter()
+            .filter(|&(_, alive)| !alive()).map(|(id, _)| *id).collect();
+        for id in stopped {
+            debug!("Actor {} is stopped", id);
+            self.actor_stopped(id);
+        }
+    }
+
+    /// Topics with local subscribers
+    fn local_topics(&self) -> Vec<String> {
+        self.subscriptions.keys().cloned().collect()
+    }
+
+    /// Send list of subscribed topics to all connected nodes
+    fn announce_topics(&self) {
+        let topics = self.local_topics();
+        for worker in self.workers.values() {
+            worker.do_send(Request::Topics(topics.clone()));
+        }
+    }
+
+    /// Connected nodes with the role, including local node
+    fn role_members(&self, role: &str) -> Vec<NodeId> {
+        let mut members: Vec<NodeId> = self.node_roles.iter()
+            .filter(|&(_, roles)| roles.iter().any(|r| r == role))
+            .map(|(id, _)| *id).collect();
+        if self.roles.iter().any(|r| r == role) {
+            members.push(self.id);
+        }
+        members
+    }
+
+    /// Bully election, node with highest id among members is leader
+    fn elect(&mut self, role: &str) {
+        let members = self.role_members(role);
+        let highest = members.iter().max().cloned();
+        let id = self.id;
+        let claim = match self.elections.get_mut(role) {
+            Some(election) => {
+                if highest == Some(id) {
+                    election.leader != Some(id)
+                } else {
+                    // local node steps down or leader is gone
+                    let gone = match election.leader {
+                        Some(leader) => leader == id || !members.contains(&leader),
+                        None => false,
+                    };
+                    if gone {
+                        election.set_leader(role, None, id);
+                    }
+                    false
+                }
+            }
+            None => false,
+        };
+        if claim {
+            self.claim(role);
+        }
+    }
+
+    fn elect_all(&mut self) {
+        let roles: Vec<String> = self.elections.keys().cloned().collect();
+        for role in roles {
+            self.elect(&role);
+        }
+    }
+
+    /// Local node becomes leader with new term, announce it to all nodes
+    fn claim(&mut self, role: &str) {
+        let id = self.id;
+        let term = match self.elections.get_mut(role) {
+            Some(election) => {
+                election.term += 1;
+                election.set_leader(role, Some(id), id);
+                election.term
+            }
+            None => return,
+        };
+        info!("Node {} is leader of {}, term: {}", id, role, term);
+        for worker in self.workers.values() {
+            worker.do_send(Request::Leader(role.to_owned(), term));
+        }
+    }
+
+    /// Deliver published message to local subscribers of the topic
+    fn fan_out(&self, peer: &Peer, topic: &str, type_id: &str,
+               version: u32, fingerprint: u64, data: &str) {
+        if let Some(subscribers) = self.subscriptions.get(topic) {
+            for (id, &(tp, ref handler)) in subscribers {
+                if tp != type_id {
+                    warn!("Message {} does not match subscriber {} of topic {}",
+                          type_id, tp, topic);
+                    continue
+                }
+                let (tx, rx) = oneshot::channel();
+                if let Err(code) = recipient::deliver(
+                    self.saddr.as_ref(), handler, peer, version, fingerprint, data.to_owned(), tx)
+                {
+                    debug!("Topic {} subscriber rejected message, code: {}", topic, code);
+                    continue
+                }
+
+                // subscriber result is not used, stopped subscriber is removed
+                if let Some(ref world) = self.saddr {
+                    let world = world.clone();
+                    let unsubscribe = msgs::Unsubscribe{topic: topic.to_owned(), id: *id};
+                    Arbiter::handle().spawn(rx.then(move |res| {
+                        if let Ok(Err(protocol::ERROR_STOPPED)) = res {
+                            debug!("Subscriber {} of topic {} is stopped",
+                                   unsubscribe.id, unsubscribe.topic);
+                            world.do_send(unsubscribe);
+                        }
+                        Ok::<_, ()>(())
+                    }));
+                }
+            }
+        }
+    }
+
+    /// Notify watchers of terminated actor
+    fn terminated(&mut self, actor: ActorId, reason: TerminationReason) {
+        if let Some(watchers) = self.watchers.remove(&actor) {
+            debug!("Actor {} is terminated: {:?}", actor, reason);
+            for watcher in watchers {
+                let _ = watcher.do_send(Terminated{actor: actor, reason: reason});
+            }
+        }
+    }
+
+    /// Notify watchers of all actors on the node
+    fn node_terminated(&mut self, node: NodeId, reason: TerminationReason) {
+        let actors: Vec<ActorId> = self.watchers.keys()
+            .filter(|actor| actor.node == node).cloned().collect();
+        for actor in actors {
+            self.terminated(actor, reason);
+        }
+    }
+
+    /// Remove stopped local actor, notify local and remote watchers
+    fn actor_stopped(&mut self, id: u64) {
+        if self.actors.remove(&id).is_none() {
+            return
+        }
+        self.probes.remove(&id);
+        self.spawned.remove(&id);
+        let names: Vec<String> = self.names.iter()
+            .filter(|&(_, info)| info.actor == id).map(|(name, _)| name.clone()).collect();
+        if !names.is_empty() {
+            for name in names {
+                self.names.remove(&name);
+            }
+            self.announce_names();
+        }
+
+        self.terminated(ActorId{node: self.id, id: id}, TerminationReason::Stopped);
+        if let Some(nodes) = self.remote_watchers.remove(&id) {
+            for node in nodes {
+                if let Some(addr) = self.nodes.get(&node) {
+                    addr.do_send(Request::Terminated(id));
+                }
+            }
+        }
+    }
+
+    /// Send message to remote node, to specific actor or to provider
+    /// of message type
+    fn send_remote(&self, node: NodeId, actor: Option<u64>, type_id: String,
+                   version: u32, fingerprint: u64, data: String)
+                   -> ActixResponse<String, RemoteError>
+    {
+        if let Some(node) = self.nodes.get(&node) {
+            let (tx, rx) = oneshot::channel();
+            node.do_send(msgs::SendRemoteMessage{
+                actor: actor, type_id: type_id, version: version,
+                fingerprint: fingerprint, data: data, tx: tx});
+            ActixResponse::async(rx.then(|res| match res {
+                Ok(res) => res,
+                Err(_) => Err(RemoteError::Disconnected),
+            }))
+        } else {
+            ActixResponse::reply(Err(RemoteError::Disconnected))
+        }
+    }
+
+    /// Start actor with registered factory, returns protocol error code
+    /// if actor can not be started
+    fn spawn_actor(&mut self, peer: &Peer, kind: &str, args: &str) -> Result<u64, u16> {
+        let handlers = match self.factories.get(kind) {
+            Some(factory) => {
+                if !factory.allows(peer) {
+                    return Err(protocol::ERROR_PERMISSION_DENIED)
+                }
+                // arguments can include actor references, factory panic
+                // must not stop world
+                let world = self.saddr.as_ref();
+                match panic::catch_unwind(AssertUnwindSafe(
+                    || actor::with_world(world, peer.id, || factory.spawn(args))))
+                {
+                    Ok(Some(parts)) => parts,
+                    Ok(None) => return Err(protocol::ERROR_SPAWN_FAILED),
+                    Err(_) => {
+                        error!("Factory of actor kind {} panicked", kind);
+                        return Err(protocol::ERROR_SPAWN_FAILED)
+                    }
+                }
+            },
+            None => return Err(protocol::ERROR_NOT_SUPPORTED),
+        };
+        let id = self.add_actor(handlers.0, handlers.1);
+        self.spawned.insert(id, peer.id);
+        debug!("Started actor {} of kind {} for node {}", id, kind, peer.id);
+        Ok(id)
+    }
+
+    /// Pass message to local actor, returns protocol error code
+    /// if actor can not handle message
+    fn deliver(&self, peer: &Peer, actor: u64, type_id: &str, version: u32, fingerprint: u64,
+               body: String, tx: oneshot::Sender<Result<String, u16>>) -> Result<(), u16>
+    {
+        match self.actors.get(&actor) {
+            Some(handlers) => match handlers.get(type_id) {
+                Some(handler) => recipient::deliver(
+                    self.saddr.as_ref(), handler, peer, version, fingerprint, body, tx),
+                None => Err(protocol::ERROR_NOT_SUPPORTED),
+            },
+            None => Err(protocol::ERROR_ACTOR_NOT_FOUND),
+        }
+    }
+
+    /// Named actors of this node
+    fn local_names(&self) -> Vec<NameInfo> {
+        self.names.values().cloned().collect()
+    }
+
+    /// Send list of named actors to all connected nodes
+    fn announce_names(&self) {
+        let names = self.local_names();
+        for worker in self.workers.values() {
+            worker.do_send(Request::Names(names.clone()));
+        }
+    }
+
     /// Create network nodes, and start listening for incoming connections
     pub fn start(mut self) -> Addr<Syn, Self> {
-        let addrs: Vec<(net::SocketAddr, net::TcpListener)> =
-            self.sockets.drain().collect();
+        let port = self.local_addrs().iter().map(|addr| addr.port()).next();
+        let addr = self.advertise.take().unwrap_or_else(|| self.addr.clone());
+        self.addr = match port {
+            Some(port) if transport::split_addr(&addr).0 == transport::TCP_SCHEME =>
+                utils::with_port(&addr, port),
+            _ => addr,
+        };
+        let host = transport::split_addr(&self.addr).1.to_owned();
+        if let Some(specified) = utils::specify_host(&host) {
+            warn!("Can not advertise unspecified address {}, use World::advertise_addr()",
+                  self.addr);
+            let prefix = self.addr.len() - host.len();
+            self.addr = format!("{}{}", &self.addr[..prefix], specified);
+        }
+        info!("Advertise address {} for node {}", self.addr, self.id);
+        #[cfg(feature="rust-tls")]
+        {
+            if let Some(ref tls) = self.tls {
+                for addr in self.addrs.keys() {
+                    if let Err(err) = tls.server_name(addr) {
+                        error!("Can not connect to {} over TLS: {}", addr, err);
+                    }
+                }
+            }
+        }
+
+        let listeners: Vec<Box<Listener>> = self.listeners.drain(..).collect();
 
         // start network
         Actor::create(move |ctx| {
-            let h = Arbiter::handle();
+            self.saddr = Some(ctx.address());
+            self.check_actors_later(ctx);
+            for proxy in self.recipients.values() {
+                let _ = proxy.started.do_send(msgs::WorldStarted(ctx.address()));
+            }
+
+            // every node with the role takes part in its election
+            for role in &self.roles {
+                self.elections.insert(role.clone(), Election::default());
+            }
+            self.elect_all();
 
             // subscribe to signals
             signal::ProcessSignals::from_registry().do_send(
                 signal::Subscribe(ctx.address::<Addr<_, _>>().recipient()));
 
-            // start workers
-            for (addr, sock) in addrs {
-                info!("Starting actix remote server on {}", addr);
-                let lst = TcpListener::from_listener(sock, &addr, h)
-                    .unwrap();
+            // start listeners
+            for lst in listeners {
+                info!("Starting actix remote server on {}", lst.local_addr());
                 ctx.add_stream(lst.incoming());
             }
 
             for info in self.addrs.values() {
                 let net = ctx.address();
                 let info2 = info.clone();
-                let addr2 = self.addr.clone();
-                let node: Addr<Unsync, _> =
-                    Supervisor::start(move |_| NetworkNode::new(addr2, net, info2));
-                self.nodes.insert(info.address().to_string(), node);
+                let id = self.id;
+                let scheme = transport::split_addr(info.address()).0;
+                let transport = match self.transports.get(scheme) {
+                    Some(transport) => Rc::clone(transport),
+                    None => {
+                        error!("Transport is not supported: {}", info.address());
+                        continue
+                    }
+                };
+                let node: Addr<Unsync, _> = Supervisor::start(
+                    move |_| NetworkNode::new(id, net, info2, transport));
+                self.peers.insert(info.address().to_string(), node);
             }
 
             self
@@ -195,27 +942,561 @@ impl World {
 }
 
 /// Register remote message recipient
-impl Handler<msgs::ProvideRecipient> for World {
-    type Result = ();
+impl Handler<msgs::RegisterProvider> for World {
+    type Result = Result<(), RegistrationError>;
+
+    fn handle(&mut self, msg: msgs::RegisterProvider, _: &mut Self::Context)
+              -> Result<(), RegistrationError>
+    {
+        if let Err(err) = self.check_type(msg.type_id, msg.message_type) {
+            error!("Can not register provider: {}", err);
+            return Err(err)
+        }
 
-    fn handle(&mut self, msg: msgs::ProvideRecipient, _: &mut Self::Context) {
         // notify all workers
+        let msg = msgs::ProvideRecipient{type_id: msg.type_id, handler: msg.handler};
         for addr in self.workers.values() {
             addr.do_send(msg.clone());
         }
 
         self.handlers.insert(msg.type_id, msg.handler);
+        Ok(())
     }
 }
 
-/// New client connection, create new downstream connection or re-connect existing
-impl StreamHandler<(TcpStream, net::SocketAddr), io::Error> for World
+/// Register actor instance
+impl Handler<msgs::RegisterActor> for World {
+    type Result = MessageResult<msgs::RegisterActor>;
+
+    fn handle(&mut self, msg: msgs::RegisterActor, _: &mut Self::Context)
+              -> MessageResult<msgs::RegisterActor>
+    {
+        let id = self.add_actor(msg.handlers, msg.alive);
+        MessageResult(ActorId{node: self.id, id: id})
+    }
+}
+
+/// Stop local actor or ask node to stop spawned actor
+impl Handler<msgs::StopActor> for World {
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::StopActor, _: &mut Self::Context) {
+        match msg.origin {
+            // reference from other node, same rule as for remote stop
+            Some(origin) if msg.actor.node == self.id && origin != self.id => {
+                if self.spawned.get(&msg.actor.id) == Some(&origin) {
+                    self.actor_stopped(msg.actor.id);
+                } else {
+                    warn!("Node {} is not allowed to stop actor {}", origin, msg.actor.id);
+                }
+            }
+            _ => if msg.actor.node == self.id {
+                self.actor_stopped(msg.actor.id);
+            } else if let Some(node) = self.nodes.get(&msg.actor.node) {
+                node.do_send(Request::Stop(msg.actor.id));
+            }
+        }
+    }
+}
+
+/// Peer stops actor, only node that spawned actor can stop it
+impl Handler<msgs::RemoteStop> for World {
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::RemoteStop, _: &mut Self::Context) {
+        if self.spawned.get(&msg.actor) == Some(&msg.node) {
+            self.actor_stopped(msg.actor);
+        } else {
+            warn!("Node {} is not allowed to stop actor {}", msg.node, msg.actor);
+        }
+    }
+}
+
+/// Remove actor instance
+impl Handler<msgs::UnregisterActor> for World {
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::UnregisterActor, _: &mut Self::Context) {
+        if msg.0.node == self.id {
+            self.actor_stopped(msg.0.id);
+        }
+    }
+}
+
+/// Send message to actor, local actors are called directly
+impl Handler<msgs::SendToActor> for World {
+    type Result = ActixResponse<String, RemoteError>;
+
+    fn handle(&mut self, msg: msgs::SendToActor, _: &mut Self::Context)
+              -> ActixResponse<String, RemoteError>
+    {
+        if msg.actor.node == self.id {
+            // reference received from other node is used with its identity
+            let peer = match self.origin_peer(msg.origin) {
+                Some(peer) => peer,
+                None => return ActixResponse::reply(Err(RemoteError::PermissionDenied)),
+            };
+            let (tx, rx) = oneshot::channel();
+            let id = msg.actor.id;
+            let world = self.saddr.clone();
+            return match self.deliver(
+                &peer, id, &msg.type_id, msg.version, msg.fingerprint, msg.data, tx)
+            {
+                Ok(()) => ActixResponse::async(rx.then(move |res| {
+                    let res = res.unwrap_or(Err(protocol::ERROR_UNAVAILABLE));
+                    if res == Err(protocol::ERROR_STOPPED) {
+                        // recipient mailbox is closed
+                        if let Some(world) = world {
+                            world.do_send(msgs::ActorStopped(id));
+                        }
+                    }
+                    res.map_err(RemoteError::from_code)
+                })),
+                Err(code) => ActixResponse::reply(Err(RemoteError::from_code(code))),
+            }
+        }
+
+        self.send_remote(msg.actor.node, Some(msg.actor.id), msg.type_id,
+                         msg.version, msg.fingerprint, msg.data)
+    }
+}
+
+/// Send message to provider of message type on specific node
+impl Handler<msgs::SendToNode> for World {
+    type Result = ActixResponse<String, RemoteError>;
+
+    fn handle(&mut self, msg: msgs::SendToNode, _: &mut Self::Context)
+              -> ActixResponse<String, RemoteError>
+    {
+        if msg.node == self.id {
+            let (tx, rx) = oneshot::channel();
+            let peer = self.local_peer();
+            let res = match self.handlers.get(msg.type_id.as_str()) {
+                Some(handler) => recipient::deliver(
+                    self.saddr.as_ref(), handler, &peer,
+                    msg.version, msg.fingerprint, msg.data, tx),
+                None => Err(protocol::ERROR_NOT_SUPPORTED),
+            };
+            return match res {
+                Ok(()) => ActixResponse::async(rx.then(|res| {
+                    res.unwrap_or(Err(protocol::ERROR_UNAVAILABLE)).map_err(RemoteError::from_code)
+                })),
+                Err(code) => ActixResponse::reply(Err(RemoteError::from_code(code))),
+            }
+        }
+
+        self.send_remote(msg.node, None, msg.type_id, msg.version, msg.fingerprint, msg.data)
+    }
+}
+
+/// Nodes with providers of message type, including local node
+impl Handler<msgs::TypeNodes> for World {
+    type Result = MessageResult<msgs::TypeNodes>;
+
+    fn handle(&mut self, msg: msgs::TypeNodes, _: &mut Self::Context)
+              -> MessageResult<msgs::TypeNodes>
+    {
+        let mut nodes: Vec<NodeId> = match self.types.get(msg.0) {
+            Some(nodes) => nodes.iter()
+                .filter(|n| self.nodes.contains_key(*n)).cloned().collect(),
+            None => Vec::new(),
+        };
+        if self.handlers.contains_key(msg.0) {
+            nodes.push(self.id);
+        }
+        MessageResult((self.id, nodes))
+    }
+}
+
+/// Register named actor
+impl Handler<msgs::RegisterName> for World {
+    type Result = Result<ActorId, RegistrationError>;
+
+    fn handle(&mut self, msg: msgs::RegisterName, _: &mut Self::Context)
+              -> Result<ActorId, RegistrationError>
+    {
+        let id = self.id;
+        let taken = self.names.contains_key(&msg.name) ||
+            self.node_names.iter().any(|(node, names)| {
+                node.is_older_than(&id) && names.iter().any(|n| n.name == msg.name)});
+        if taken {
+            return Err(RegistrationError::NameTaken(msg.name))
+        }
+        let mut handlers = HashMap::new();
+        handlers.insert(msg.type_id, msg.handler);
+        let id = self.add_actor(handlers, None);
+        self.names.insert(msg.name.clone(), NameInfo{
+            name: msg.name, type_id: msg.type_id.to_owned(), actor: id});
+        self.announce_names();
+
+        Ok(ActorId{node: self.id, id: id})
+    }
+}
+
+/// Remove named actor
+impl Handler<msgs::UnregisterName> for World {
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::UnregisterName, _: &mut Self::Context) {
+        let actor = self.names.get(&msg.0).map(|info| info.actor);
+        if let Some(actor) = actor {
+            self.actor_stopped(actor);
+        }
+    }
+}
+
+/// Resolve name, if name is registered on several nodes,
+/// registration of the oldest node wins
+impl Handler<msgs::LookupName> for World {
+    type Result = Result<ActorId, RemoteError>;
+
+    fn handle(&mut self, msg: msgs::LookupName, _: &mut Self::Context)
+              -> Result<ActorId, RemoteError>
+    {
+        let mut found = self.names.get(&msg.name).map(|info| (self.id, info));
+        for (node, names) in &self.node_names {
+            for info in names.iter().filter(|info| info.name == msg.name) {
+                if found.map(|(n, _)| node.is_older_than(&n)).unwrap_or(true) {
+                    found = Some((*node, info));
+                }
+            }
+        }
+
+        match found {
+            Some((node, info)) => if info.type_id == msg.type_id {
+                Ok(ActorId{node: node, id: info.actor})
+            } else {
+                Err(RemoteError::TypeMismatch)
+            },
+            None => Err(RemoteError::NameNotFound),
+        }
+    }
+}
+
+/// Node announced its named actors
+impl Handler<msgs::NodeNames> for World {
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::NodeNames, _: &mut Self::Context) {
+        // registration of older node wins, local registration is evicted,
+        // remote node evicts its registration once it receives our names
+        let mut evicted = Vec::new();
+        for info in &msg.names {
+            if let Some(local) = self.names.get(&info.name) {
+                if msg.node.is_older_than(&self.id) {
+                    warn!("Name {} is registered on older node {}, remove local registration",
+                          info.name, msg.node);
+                    evicted.push(local.actor);
+                }
+            }
+        }
+        self.node_names.insert(msg.node, msg.names);
+        for actor in evicted {
+            self.actor_stopped(actor);
+        }
+    }
+}
+
+/// Register actor factory
+impl Handler<msgs::RegisterFactory> for World {
+    type Result = Result<(), RegistrationError>;
+
+    fn handle(&mut self, msg: msgs::RegisterFactory, _: &mut Self::Context)
+              -> Result<(), RegistrationError>
+    {
+        if self.factories.contains_key(msg.kind) {
+            return Err(RegistrationError::KindTaken(msg.kind.to_owned()))
+        }
+        self.factories.insert(msg.kind, msg.factory);
+        Ok(())
+    }
+}
+
+/// Start actor on local or remote node
+impl Handler<msgs::SpawnActor> for World {
+    type Result = ActixResponse<ActorId, RemoteError>;
+
+    fn handle(&mut self, msg: msgs::SpawnActor, _: &mut Self::Context)
+              -> ActixResponse<ActorId, RemoteError>
+    {
+        if msg.node == self.id {
+            let peer = self.local_peer();
+            let node = self.id;
+            return ActixResponse::reply(
+                self.spawn_actor(&peer, msg.kind, &msg.args)
+                    .map(|id| ActorId{node: node, id: id})
+                    .map_err(RemoteError::from_code))
+        }
+
+        if let Some(node) = self.nodes.get(&msg.node) {
+            let (tx, rx) = oneshot::channel();
+            node.do_send(msgs::SpawnRemote{
+                kind: msg.kind.to_owned(), args: msg.args, tx: tx});
+            let id = msg.node;
+            ActixResponse::async(rx.then(move |res| match res {
+                Ok(Ok(data)) => spawn::parse_actor(id, &data),
+                Ok(Err(err)) => Err(err),
+                Err(_) => Err(RemoteError::Disconnected),
+            }))
+        } else {
+            ActixResponse::reply(Err(RemoteError::Disconnected))
+        }
+    }
+}
+
+/// Worker passes spawn request from peer
+impl Handler<msgs::SpawnLocal> for World {
+    type Result = Result<u64, u16>;
+
+    fn handle(&mut self, msg: msgs::SpawnLocal, _: &mut Self::Context) -> Result<u64, u16> {
+        self.spawn_actor(&msg.peer, &msg.kind, &msg.args)
+    }
+}
+
+/// Watch local or remote actor
+impl Handler<msgs::Watch> for World {
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::Watch, _: &mut Self::Context) {
+        let actor = msg.actor;
+        if actor.node == self.id {
+            if !self.actors.contains_key(&actor.id) {
+                let _ = msg.watcher.do_send(
+                    Terminated{actor: actor, reason: TerminationReason::Stopped});
+                return
+            }
+        } else if let Some(node) = self.nodes.get(&actor.node) {
+            // remote node notifies this node once for all watchers
+            if !self.watchers.contains_key(&actor) {
+                node.do_send(Request::Watch(actor.id));
+            }
+        } else {
+            let _ = msg.watcher.do_send(
+                Terminated{actor: actor, reason: TerminationReason::Unreachable});
+            return
+        }
+        self.watchers.entry(actor).or_default().push(msg.watcher);
+    }
+}
+
+/// Peer watches local actor
+impl Handler<msgs::RemoteWatch> for World {
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::RemoteWatch, _: &mut Self::Context) {
+        if self.actors.contains_key(&msg.actor) {
+            self.remote_watchers.entry(msg.actor)
+                .or_default().insert(msg.node);
+        } else if let Some(node) = self.nodes.get(&msg.node) {
+            node.do_send(Request::Terminated(msg.actor));
+        }
+    }
+}
+
+/// Remote node reported terminated actor
+impl Handler<msgs::ActorTerminated> for World {
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::ActorTerminated, _: &mut Self::Context) {
+        self.terminated(msg.actor, msg.reason);
+    }
+}
+
+/// Mailbox of local actor is closed
+impl Handler<msgs::ActorStopped> for World {
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::ActorStopped, _: &mut Self::Context) {
+        self.actor_stopped(msg.0);
+    }
+}
+
+/// Connection to the node is lost
+impl Handler<msgs::NodeUnreachable> for World {
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::NodeUnreachable, _: &mut Self::Context) {
+        self.node_names.remove(&msg.0);
+        self.node_info.remove(&msg.0);
+        self.node_terminated(msg.0, TerminationReason::Unreachable);
+        if self.node_roles.remove(&msg.0).is_some() {
+            self.elect_all();
+        }
+    }
+}
+
+/// Subscribe to topic
+impl Handler<msgs::Subscribe> for World {
+    type Result = Result<u64, RegistrationError>;
+
+    fn handle(&mut self, msg: msgs::Subscribe, _: &mut Self::Context)
+              -> Result<u64, RegistrationError>
+    {
+        self.check_type(msg.type_id, msg.message_type)?;
+
+        self.sid += 1;
+        let new = !self.subscriptions.contains_key(&msg.topic);
+        self.subscriptions.entry(msg.topic).or_default()
+            .insert(self.sid, (msg.type_id, msg.handler));
+        if new {
+            self.announce_topics();
+        }
+        Ok(self.sid)
+    }
+}
+
+/// Remove topic subscription
+impl Handler<msgs::Unsubscribe> for World {
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::Unsubscribe, _: &mut Self::Context) {
+        let empty = match self.subscriptions.get_mut(&msg.topic) {
+            Some(subscribers) => {
+                subscribers.remove(&msg.id);
+                subscribers.is_empty()
+            },
+            None => false,
+        };
+        if empty {
+            self.subscriptions.remove(&msg.topic);
+            self.announce_topics();
+        }
+    }
+}
+
+/// Publish message, every node with subscribers receives single copy
+impl Handler<msgs::Publish> for World {
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::Publish, _: &mut Self::Context) {
+        let peer = self.local_peer();
+        self.fan_out(&peer, &msg.topic, &msg.type_id, msg.version, msg.fingerprint, &msg.data);
+
+        let ver = protocol::format_version(msg.version, msg.fingerprint);
+        for (id, topics) in &self.node_topics {
+            if !topics.contains(&msg.topic) {
+                continue
+            }
+            if let Some(node) = self.nodes.get(id) {
+                node.do_send(Request::Publish(
+                    msg.topic.clone(), msg.type_id.clone(), ver.clone(), msg.data.clone()));
+            }
+        }
+    }
+}
+
+/// Message published by peer
+impl Handler<msgs::TopicMessage> for World {
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::TopicMessage, _: &mut Self::Context) {
+        self.fan_out(&msg.peer, &msg.topic, &msg.type_id,
+                     msg.version, msg.fingerprint, &msg.data);
+    }
+}
+
+/// Node announced its topics
+impl Handler<msgs::NodeTopics> for World {
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::NodeTopics, _: &mut Self::Context) {
+        self.node_topics.insert(msg.node, msg.topics.into_iter().collect());
+    }
+}
+
+/// Subscribe to leader changes
+impl Handler<msgs::JoinElection> for World {
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::JoinElection, _: &mut Self::Context) {
+        let id = self.id;
+        {
+            let election = self.elections.entry(msg.role.clone()).or_default();
+            let _ = msg.subscriber.do_send(election.state(&msg.role, id));
+            election.subscribers.push(msg.subscriber);
+        }
+        self.elect(&msg.role);
+    }
+}
+
+/// Remote node announced leadership, only nodes with the role are accepted
+impl Handler<msgs::LeaderAnnounced> for World {
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::LeaderAnnounced, _: &mut Self::Context) {
+        let member = self.node_roles.get(&msg.node)
+            .map(|roles| roles.contains(&msg.role)).unwrap_or(false);
+        if !member {
+            warn!("Node {} without role {} announced leadership", msg.node, msg.role);
+            return
+        }
+
+        let id = self.id;
+        // local node bullies lower nodes
+        let higher = id > msg.node && self.roles.contains(&msg.role);
+        {
+            let election = self.elections.entry(msg.role.clone()).or_default();
+            let prev = election.term;
+            if msg.term > prev {
+                election.term = msg.term;
+            }
+            if !higher && msg.term >= prev &&
+                (election.leader != Some(msg.node) || msg.term > prev)
+            {
+                info!("Node {} is leader of {}, term: {}", msg.node, msg.role, msg.term);
+                election.set_leader(&msg.role, Some(msg.node), id);
+            }
+        }
+        if higher {
+            self.claim(&msg.role);
+        }
+    }
+}
+
+/// Worker passes message from peer to local actor
+impl Handler<msgs::DeliverToActor> for World {
+    type Result = Result<(), u16>;
+
+    fn handle(&mut self, msg: msgs::DeliverToActor, _: &mut Self::Context) -> Result<(), u16> {
+        self.deliver(&msg.peer, msg.actor, &msg.type_id,
+                     msg.version, msg.fingerprint, msg.body, msg.tx)
+    }
+}
+
+/// New client connection, start worker, worker binds connection to network node
+/// after handshake
+impl StreamHandler<(Box<Io>, String), io::Error> for World
 {
-    fn handle(&mut self, msg: (TcpStream, net::SocketAddr), ctx: &mut Context<Self>) {
-        self.wid += 1;
-        let addr = NetworkWorker::start(
-            self.wid, msg.0, self.handlers.clone(), ctx.address());
-        self.workers.insert(self.wid, addr);
+    fn handle(&mut self, msg: (Box<Io>, String), ctx: &mut Context<Self>) {
+        self.accept(msg.0, msg.1, ctx);
+    }
+}
+
+/// Network node connected to remote node, start worker for outbound connection
+impl Handler<msgs::OutboundConnection> for World {
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::OutboundConnection, ctx: &mut Context<Self>) {
+        #[cfg(feature="rust-tls")]
+        {
+            if let Some(ref tls) = self.tls {
+                let node = msg.node;
+                let node2 = node.clone();
+                let addr = msg.addr;
+                let addr2 = addr.clone();
+                tls.connect(&addr, msg.stream)
+                    .into_actor(self)
+                    .map(move |(io, certs), act, ctx| {
+                        act.start_worker(io, certs, Some((addr2, node)), ctx)})
+                    .map_err(move |err, _, _| {
+                        error!("TLS handshake with {} failed: {}", addr, err);
+                        node2.do_send(msgs::ConnectFailed);
+                    })
+                    .spawn(ctx);
+                return
+            }
+        }
+        self.start_worker(msg.stream, Vec::new(), Some((msg.addr, msg.node)), ctx);
     }
 }
 
@@ -225,26 +1506,96 @@ impl Handler<msgs::WorkerDisconnected> for World {
 
     fn handle(&mut self, msg: msgs::WorkerDisconnected, _: &mut Self::Context) {
         self.workers.remove(&msg.0);
+        self.outbound.remove(&msg.0);
     }
 }
 
-/// Connected to remote node
+/// Remote node completed handshake, bind connection to network node.
+///
+/// Every node id has single network node that holds one connection.
+/// Connection is attached to known node of the id, node dialed by world
+/// is preferred as it re-connects. Inbound connection of unknown node
+/// creates new network node.
 impl Handler<msgs::NodeConnected> for World {
     type Result = ();
 
     fn handle(&mut self, msg: msgs::NodeConnected, ctx: &mut Context<Self>) {
-        if let Some(node) = self.nodes.get(&msg.0) {
-            node.do_send(msgs::ReconnectNode);
+        let id = msg.id;
+
+        // previous incarnation of restarted node connects late
+        if self.nodes.keys().any(|n| n.is_restart_of(&id)) {
+            warn!("Drop connection of stale network node {}", id);
+            msg.conn.worker.do_send(msgs::StopWorker);
+            if let Some((_, node)) = self.outbound.remove(&msg.conn.id) {
+                node.do_send(msgs::ConnectFailed);
+            }
             return
         }
 
-        let addr = msg.0.clone();
-        let naddr = self.addr.clone();
-        let net = ctx.address();
-        let info = NodeInformation::new(msg.0.clone());
-        let node: Addr<Unsync, _> =
-            Supervisor::start(move |_| NetworkNode::new(naddr, net, info));
-        self.nodes.insert(addr, node);
+        // previous incarnations of restarted node, nodes are matched
+        // by uuid, old entry is evicted and reported as gone
+        let stale: Vec<NodeId> = self.nodes.keys()
+            .filter(|n| id.is_restart_of(n)).cloned().collect();
+        for n in stale {
+            info!("Network node {} restarted as {}", n, id);
+            if let Some(node) = self.nodes.get(&n) {
+                node.do_send(msgs::StopNode);
+            }
+            self.node_gone(n);
+        }
+
+        let dialer = if msg.conn.inbound {
+            self.peers.get(&msg.addr).map(|node| (msg.addr.clone(), node.clone()))
+        } else {
+            self.outbound.remove(&msg.conn.id)
+        };
+        let node = match (self.nodes.get(&id).cloned(), dialer) {
+            (Some(node), Some((addr, dialer))) => match self.node_peers.entry(id) {
+                Entry::Occupied(_) => node,
+                Entry::Vacant(entry) => {
+                    // node was created for inbound connection from address
+                    // that is not dialed, dialer takes over its connection
+                    debug!("Network node {} is dialed as {}", id, addr);
+                    node.do_send(msgs::MoveConnection(dialer.clone()));
+                    entry.insert(addr);
+                    dialer
+                },
+            },
+            (Some(node), None) => node,
+            (None, Some((addr, dialer))) => {
+                self.node_peers.insert(id, addr);
+                dialer
+            },
+            (None, None) => {
+                let local = self.id;
+                let net = ctx.address();
+                let info = NodeInformation::new(msg.addr.clone());
+                NetworkNode::inbound(local, net, info).start()
+            },
+        };
+
+        msg.conn.worker.do_send(Request::Names(self.local_names()));
+        msg.conn.worker.do_send(Request::Topics(self.local_topics()));
+        for (role, election) in &self.elections {
+            if election.leader == Some(self.id) {
+                msg.conn.worker.do_send(Request::Leader(role.clone(), election.term));
+            }
+        }
+        node.do_send(msgs::AttachConnection{id: id, conn: msg.conn});
+        self.nodes.insert(id, node);
+        self.node_info.insert(id, Peer{id: id, addr: msg.addr, roles: msg.roles.clone(),
+                                       certificates: msg.certificates});
+        self.node_roles.insert(id, msg.roles);
+        self.elect_all();
+    }
+}
+
+/// Node is disconnected or changed identity
+impl Handler<msgs::NodeGone> for World {
+    type Result = ();
+
+    fn handle(&mut self, msg: msgs::NodeGone, _: &mut Context<Self>) {
+        self.node_gone(msg.0);
     }
 }
 
@@ -257,20 +1608,22 @@ impl Handler<msgs::NodeSupportedTypes> for World {
     fn handle(&mut self, msg: msgs::NodeSupportedTypes, _: &mut Context<Self>) {
         // register in internal registry
         for tp in &msg.types {
-            if !self.types.contains_key(tp) {
-                self.types.insert(tp.clone(), HashSet::new());
+            if !self.types.contains_key(&tp.type_id) {
+                self.types.insert(tp.type_id.clone(), HashSet::new());
             }
-            self.types.get_mut(tp).unwrap().insert(msg.node.clone());
+            self.types.get_mut(&tp.type_id).unwrap().insert(msg.node);
         }
 
         // notify all recipient proxies
         if let Some(node) = self.nodes.get(&msg.node) {
             for tp in msg.types {
-                if let Some(proxy) = self.recipients.get(tp.as_str()) {
+                if let Some(proxy) = self.recipients.get(tp.type_id.as_str()) {
                     let _ = proxy.service.do_send(
                         msgs::TypeSupported {
-                            type_id: tp,
-                            node_id: msg.node.clone(),
+                            versions: (tp.min_version, tp.max_version),
+                            fingerprint: tp.fingerprint,
+                            type_id: tp.type_id,
+                            node_id: msg.node,
                             node: node.clone(),
                         });
                 }
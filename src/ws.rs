@@ -0,0 +1,280 @@
+//! WebSocket transport
+//!
+//! Node connection is carried by binary WebSocket messages, so nodes can
+//! communicate through http load balancers and reverse proxies. Addresses
+//! are `ws://host:port/path`, path is ignored by listener.
+use std::{cmp, io, net};
+use std::time::Duration;
+
+use actix::actors::{Connect, Connector};
+use actix::prelude::{Arbiter, ArbiterService};
+use bytes::Bytes;
+use futures::{future, Async, AsyncSink, Future, Poll, Sink, Stream};
+use futures::future::Either;
+use tokio_core::reactor::Timeout;
+use tokio_io::{AsyncRead, AsyncWrite};
+use tokio_tungstenite::{accept_async, client_async, WebSocketStream};
+use tungstenite::{Error as WsError, Message};
+use url::Url;
+
+use transport::{Incoming, Listener, TcpListener, Transport};
+use utils::{self, Io};
+
+/// Address scheme of websocket transport
+pub const WS_SCHEME: &str = "ws";
+
+/// Maximum number of concurrent websocket handshakes on the listener
+const MAX_HANDSHAKES: usize = 64;
+/// Time limit of websocket handshake on the listener, in seconds
+const HANDSHAKE_TIMEOUT: u64 = 10;
+
+
+/// WebSocket transport, addresses are `ws://host:port/path`
+pub struct WsTransport;
+
+impl Transport for WsTransport {
+    fn scheme(&self) -> &str {
+        WS_SCHEME
+    }
+
+    fn listen(&self, addr: &str) -> io::Result<Box<Listener>> {
+        Ok(Box::new(WsListener::bind(addr, Duration::from_secs(HANDSHAKE_TIMEOUT))?))
+    }
+
+    fn dial(&self, addr: &str) -> Box<Future<Item=Box<Io>, Error=io::Error>> {
+        let url = match Url::parse(&format!("{}://{}", WS_SCHEME, addr)) {
+            Ok(url) => url,
+            Err(err) => return Box::new(
+                future::err(io::Error::new(io::ErrorKind::Other, err))),
+        };
+        Box::new(
+            Connector::from_registry()
+                .send(Connect::host(host_port(addr)))
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("{}", err)))
+                .and_then(|res| res.map_err(
+                    |err| io::Error::new(io::ErrorKind::Other, format!("{}", err))))
+                .and_then(move |stream| client_async(url, stream).map_err(into_io_error))
+                .map(|(ws, _)| Box::new(WsStream::new(ws)) as Box<Io>))
+    }
+}
+
+/// Tcp listener, accepted connections are upgraded to websocket
+struct WsListener {
+    lst: TcpListener,
+    timeout: Duration,
+}
+
+impl WsListener {
+    /// Listen on `host:port/path`, connections that do not finish
+    /// handshake in `timeout` are dropped
+    fn bind(addr: &str, timeout: Duration) -> io::Result<WsListener> {
+        let host = host_port(addr);
+        let addr = match host.parse::<net::SocketAddr>() {
+            Ok(addr) => addr,
+            Err(_) => net::ToSocketAddrs::to_socket_addrs(host)?.next().ok_or_else(
+                || io::Error::new(io::ErrorKind::Other, "Can not resolve address."))?,
+        };
+        let lst = TcpListener::new(utils::tcp_listener(addr, 256)?)?;
+        Ok(WsListener{lst: lst, timeout: timeout})
+    }
+}
+
+impl Listener for WsListener {
+    fn local_addr(&self) -> String {
+        format!("{}://{}", WS_SCHEME, self.lst.local_addr())
+    }
+
+    fn incoming(self: Box<Self>) -> Incoming {
+        let timeout = self.timeout;
+        Box::new(
+            Box::new(self.lst).incoming()
+                .map(move |(stream, peer)| {
+                    // slow peers must not occupy handshake slots
+                    let expired =
+                        future::result(Timeout::new(timeout, Arbiter::handle())).flatten();
+                    accept_async(stream).map_err(into_io_error).select2(expired)
+                        .then(move |res| match res {
+                            Ok(Either::A((ws, _))) => Ok::<_, io::Error>(
+                                Some((Box::new(WsStream::new(ws)) as Box<Io>, peer))),
+                            Ok(Either::B(_)) => {
+                                warn!("WebSocket handshake with {} timed out", peer);
+                                Ok(None)
+                            },
+                            Err(Either::A((err, _))) | Err(Either::B((err, _))) => {
+                                warn!("WebSocket handshake with {} failed: {}", peer, err);
+                                Ok(None)
+                            }
+                        })
+                })
+                .buffer_unordered(MAX_HANDSHAKES)
+                .filter_map(|conn| conn))
+    }
+}
+
+/// Byte stream over binary websocket messages.
+///
+/// Every write is sent as single binary message, message boundaries
+/// are not significant for reader.
+struct WsStream<S> {
+    ws: WebSocketStream<S>,
+    buf: Bytes,
+}
+
+impl<S: AsyncRead + AsyncWrite> WsStream<S> {
+    fn new(ws: WebSocketStream<S>) -> WsStream<S> {
+        WsStream{ws: ws, buf: Bytes::new()}
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite> io::Read for WsStream<S> {
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if !self.buf.is_empty() {
+                let size = cmp::min(dst.len(), self.buf.len());
+                dst[..size].copy_from_slice(&self.buf.split_to(size));
+                return Ok(size)
+            }
+            match self.ws.poll().map_err(into_io_error)? {
+                Async::Ready(Some(Message::Binary(data))) => self.buf = Bytes::from(data),
+                Async::Ready(None) => return Ok(0),
+                // ping is answered by websocket stream
+                Async::Ready(Some(_)) => (),
+                Async::NotReady => return Err(io::ErrorKind::WouldBlock.into()),
+            }
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite> io::Write for WsStream<S> {
+    fn write(&mut self, src: &[u8]) -> io::Result<usize> {
+        match self.ws.start_send(Message::Binary(src.to_vec())).map_err(into_io_error)? {
+            AsyncSink::Ready => Ok(src.len()),
+            AsyncSink::NotReady(_) => Err(io::ErrorKind::WouldBlock.into()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.ws.poll_complete().map_err(into_io_error)? {
+            Async::Ready(()) => Ok(()),
+            Async::NotReady => Err(io::ErrorKind::WouldBlock.into()),
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite> AsyncRead for WsStream<S> {}
+
+impl<S: AsyncRead + AsyncWrite> AsyncWrite for WsStream<S> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.ws.close().map_err(into_io_error)
+    }
+}
+
+/// `host:port` part of `host:port/path` address
+fn host_port(addr: &str) -> &str {
+    match addr.find('/') {
+        Some(pos) => &addr[..pos],
+        None => addr,
+    }
+}
+
+fn into_io_error(err: WsError) -> io::Error {
+    match err {
+        WsError::Io(err) => err,
+        err => io::Error::new(io::ErrorKind::Other, format!("{}", err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix::{Arbiter, System};
+    use tokio_core::net::TcpStream;
+    use tokio_io::io::{flush, read_exact, read_to_end, write_all};
+
+    use super::*;
+
+    /// Listen on loopback, returns listener and dial address
+    fn listen() -> (Box<Listener>, String) {
+        let lst = WsTransport.listen("127.0.0.1:0").unwrap();
+        let addr = lst.local_addr();
+        assert!(addr.starts_with("ws://"));
+        let addr = format!("{}/path", &addr["ws://".len()..]);
+        (lst, addr)
+    }
+
+    /// Accept one connection, read request and answer
+    fn serve(lst: Box<Listener>) -> Box<Future<Item=(), Error=io::Error>> {
+        Box::new(
+            lst.incoming().into_future()
+                .map_err(|(err, _)| err)
+                .and_then(|(conn, _)| {
+                    let (stream, _) = conn.unwrap();
+                    read_exact(stream, [0u8; 4])
+                })
+                .and_then(|(stream, buf)| {
+                    assert_eq!(&buf, b"ping");
+                    write_all(stream, b"pong")
+                })
+                .and_then(|(stream, _)| flush(stream))
+                .map(|_| ()))
+    }
+
+    fn request(addr: &str) -> Box<Future<Item=[u8; 4], Error=io::Error>> {
+        Box::new(
+            WsTransport.dial(addr)
+                .and_then(|stream| write_all(stream, b"ping"))
+                .and_then(|(stream, _)| flush(stream))
+                .and_then(|stream| read_exact(stream, [0u8; 4]))
+                .map(|(_, buf)| buf))
+    }
+
+    #[test]
+    fn test_transport() {
+        let mut sys = System::new("test");
+        let (lst, addr) = listen();
+        let (_, buf) = sys.run_until_complete(serve(lst).join(request(&addr))).unwrap();
+        assert_eq!(&buf, b"pong");
+    }
+
+    #[test]
+    fn test_failed_handshake() {
+        let mut sys = System::new("test");
+        let (lst, addr) = listen();
+        let sock: net::SocketAddr = host_port(&addr).parse().unwrap();
+
+        // plain tcp client is dropped, listener keeps accepting
+        let plain = TcpStream::connect(&sock, Arbiter::handle())
+            .and_then(|stream| write_all(stream, b"GARBAGE\r\n\r\n"))
+            .map(|_| ());
+        let (_, _, buf) = sys.run_until_complete(
+            serve(lst).join3(plain, request(&addr))).unwrap();
+        assert_eq!(&buf, b"pong");
+    }
+
+    #[test]
+    fn test_handshake_timeout() {
+        let mut sys = System::new("test");
+        let lst = WsListener::bind("127.0.0.1:0", Duration::from_millis(100)).unwrap();
+        let addr = format!("{}/path", &lst.local_addr()["ws://".len()..]);
+        let sock: net::SocketAddr = host_port(&addr).parse().unwrap();
+
+        // silent client is disconnected, listener keeps accepting
+        let silent = TcpStream::connect(&sock, Arbiter::handle())
+            .and_then(|stream| read_to_end(stream, Vec::new()))
+            .and_then(move |(_, buf)| request(&addr).map(move |res| (buf, res)));
+        let guard = Timeout::new(Duration::from_secs(5), Arbiter::handle()).unwrap();
+        let res = sys.run_until_complete(serve(Box::new(lst)).join(silent).select2(guard));
+        match res {
+            Ok(Either::A(((_, (buf, res)), _))) => {
+                assert!(buf.is_empty());
+                assert_eq!(&res, b"pong");
+            },
+            _ => panic!("Silent client is not disconnected"),
+        }
+    }
+
+    #[test]
+    fn test_host_port() {
+        assert_eq!(host_port("localhost:8080/path/to"), "localhost:8080");
+        assert_eq!(host_port("localhost:8080"), "localhost:8080");
+    }
+}
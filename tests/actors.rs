@@ -0,0 +1,272 @@
+#![allow(proc_macro_derive_resolution_fallback, bare_trait_objects, non_local_definitions)]
+#![allow(clippy::redundant_field_names)]
+
+extern crate actix;
+extern crate actix_remote;
+#[macro_use] extern crate actix_remote_derive;
+extern crate env_logger;
+extern crate futures;
+#[macro_use] extern crate serde_derive;
+extern crate tokio_core;
+extern crate tokio_io;
+
+mod common;
+
+use std::thread;
+use std::sync::{mpsc, Arc, Mutex};
+
+use actix::prelude::*;
+use futures::Future;
+
+use actix_remote::{RemoteActor, RemoteAddr, RemoteError, RemoteSpawn, Terminated,
+                   TerminationReason, World};
+
+use common::{delay, retry, run, system, wait_for, world};
+
+
+/// Add to session total, session replies with new total
+#[derive(Debug, Message, Serialize, Deserialize, RemoteMessage)]
+#[rtype(u32)]
+#[remote(id="actors.Add")]
+struct Add(u32);
+
+type SessionRef = RemoteAddr<Session>;
+
+/// Request reference to session registered on provider node
+#[derive(Debug, Message, Serialize, Deserialize, RemoteMessage)]
+#[rtype(SessionRef)]
+#[remote(id="actors.GetSession")]
+struct GetSession;
+
+/// Topic message
+#[derive(Debug, Message, Serialize, Deserialize, RemoteMessage)]
+#[remote(id="actors.News")]
+struct News(String);
+
+#[derive(Default)]
+struct Session {
+    total: u32,
+}
+
+impl Actor for Session {
+    type Context = Context<Self>;
+}
+
+impl Handler<Add> for Session {
+    type Result = u32;
+
+    fn handle(&mut self, msg: Add, _: &mut Context<Self>) -> u32 {
+        self.total += msg.0;
+        self.total
+    }
+}
+
+impl RemoteSpawn for Session {
+    type Args = String;
+
+    fn kind() -> &'static str {
+        "actors.Session"
+    }
+}
+
+/// Provider of `GetSession`
+struct Sessions(SessionRef);
+
+impl Actor for Sessions {
+    type Context = Context<Self>;
+}
+
+impl Handler<GetSession> for Sessions {
+    type Result = MessageResult<GetSession>;
+
+    fn handle(&mut self, _: GetSession, _: &mut Context<Self>) -> MessageResult<GetSession> {
+        MessageResult(self.0.clone())
+    }
+}
+
+/// Collects received topic messages
+struct Reader(Arc<Mutex<Vec<String>>>);
+
+impl Actor for Reader {
+    type Context = Context<Self>;
+}
+
+impl Handler<News> for Reader {
+    type Result = ();
+
+    fn handle(&mut self, msg: News, _: &mut Context<Self>) {
+        self.0.lock().unwrap().push(msg.0);
+    }
+}
+
+/// Watcher, keeps received notification
+struct Watcher(Arc<Mutex<Option<Terminated>>>);
+
+impl Actor for Watcher {
+    type Context = Context<Self>;
+}
+
+impl Handler<Terminated> for Watcher {
+    type Result = ();
+
+    fn handle(&mut self, msg: Terminated, _: &mut Context<Self>) {
+        *self.0.lock().unwrap() = Some(msg);
+    }
+}
+
+/// Start session, register it and provide reference to it with `GetSession`
+fn session(world: &Addr<Syn, World>) -> Box<Future<Item=SessionRef, Error=()>> {
+    let addr: Addr<Syn, _> = Session::default().start();
+    let world = world.clone();
+    Box::new(
+        RemoteActor::new().addr(addr.clone()).recipient::<Add>(addr.recipient())
+            .register(&world)
+            .and_then(move |session| {
+                let sessions: Addr<Syn, _> = Sessions(session.clone()).start();
+                World::register_recipient(&world, sessions.recipient()).map(|_| session)
+            })
+            .map_err(|err| panic!("Can not register session: {}", err)))
+}
+
+#[test]
+fn test_remote_actor() {
+    let mut sys = system();
+
+    let a = world("actors-ref-a", &[]).start();
+    let mut b = world("actors-ref-b", &["actors-ref-a"]);
+    let to_a = b.get_recipient::<GetSession>();
+    let _b = b.start();
+    let local = run(&mut sys, session(&a)).unwrap();
+
+    // reference received from other node addresses same actor instance
+    let remote = run(&mut sys, retry(move || to_a.send(GetSession))).unwrap();
+    assert_eq!(remote.id(), local.id());
+    assert_eq!(run(&mut sys, remote.send(Add(2))).unwrap(), 2);
+    assert_eq!(run(&mut sys, local.send(Add(3))).unwrap(), 5);
+    assert_eq!(run(&mut sys, remote.recipient::<Add>().send(Add(1))).unwrap(), 6);
+
+    // registration is removed, reference does not reach the actor
+    World::unregister_actor(&a, local.id());
+    match run(&mut sys, remote.send(Add(1))) {
+        Err(RemoteError::ActorNotFound) => (),
+        res => panic!("Unexpected result: {:?}", res),
+    }
+}
+
+#[test]
+fn test_spawn() {
+    let mut sys = system();
+
+    let a = world("actors-spawn-a", &[]);
+    let id_a = a.node_id();
+    let a = a.start();
+    let b = world("actors-spawn-b", &["actors-spawn-a"]).start();
+    run(&mut sys, World::register_factory(&a, |name: String| {
+        if name == "panic" {
+            panic!("Factory panic");
+        }
+        let addr: Addr<Syn, Session> = Session::default().start();
+        RemoteActor::new().addr(addr.clone()).recipient::<Add>(addr.recipient())
+    })).unwrap();
+
+    // actor is started on remote node
+    let b2 = b.clone();
+    let worker = run(&mut sys, retry(move || {
+        World::spawn::<Session>(&b2, id_a, "worker".to_owned())})).unwrap();
+    assert_eq!(worker.id().node, id_a);
+    assert_eq!(run(&mut sys, worker.send(Add(4))).unwrap(), 4);
+    assert_eq!(run(&mut sys, worker.send(Add(4))).unwrap(), 8);
+
+    // factory panic is reported to spawner, world keeps running
+    match run(&mut sys, World::spawn::<Session>(&b, id_a, "panic".to_owned())) {
+        Err(RemoteError::SpawnFailed) => (),
+        res => panic!("Unexpected result: {:?}", res.map(|addr| addr.id())),
+    }
+    let other = run(&mut sys, World::spawn::<Session>(&b, id_a, "other".to_owned())).unwrap();
+    assert_ne!(other.id(), worker.id());
+    assert_eq!(run(&mut sys, other.send(Add(1))).unwrap(), 1);
+
+    // spawner stops actor
+    worker.stop();
+    let w = worker.clone();
+    run(&mut sys, retry(move || w.send(Add(1)).then(|res| match res {
+        Err(RemoteError::ActorNotFound) => Ok(()),
+        _ => Err(()),
+    }))).unwrap();
+    assert_eq!(run(&mut sys, other.send(Add(1))).unwrap(), 2);
+}
+
+#[test]
+fn test_watch() {
+    let mut sys = system();
+
+    // a runs in its own system, so it can be stopped
+    let (tx, rx) = mpsc::channel();
+    let a_thread = thread::spawn(move || {
+        let sys = System::new("a");
+        let a = world("actors-watch-a", &[]).start();
+        Arbiter::handle().spawn(session(&a).map(|_| ()));
+        tx.send(Arbiter::system()).unwrap();
+        sys.run();
+    });
+    let a_system = rx.recv().unwrap();
+
+    let mut b = world("actors-watch-b", &["actors-watch-a"]);
+    let to_a = b.get_recipient::<GetSession>();
+    let b = b.start();
+    let remote = run(&mut sys, retry(move || to_a.send(GetSession))).unwrap();
+    let state = Arc::new(Mutex::new(None));
+    let watcher: Addr<Syn, _> = Watcher(Arc::clone(&state)).start();
+    World::watch(&b, remote.id(), watcher.recipient());
+    assert_eq!(run(&mut sys, remote.send(Add(1))).unwrap(), 1);
+
+    // watcher is notified once node of the actor is lost
+    a_system.do_send(actix::msgs::SystemExit(0));
+    a_thread.join().unwrap();
+    let s = Arc::clone(&state);
+    run(&mut sys, wait_for(move || s.lock().unwrap().is_some())).unwrap();
+    let terminated = state.lock().unwrap().take().unwrap();
+    assert_eq!(terminated.actor, remote.id());
+    match terminated.reason {
+        TerminationReason::Unreachable | TerminationReason::NodeGone => (),
+        reason => panic!("Unexpected reason: {:?}", reason),
+    }
+}
+
+#[test]
+fn test_topic() {
+    let mut sys = system();
+
+    let a = world("actors-topic-a", &[]).start();
+    let b = world("actors-topic-b", &["actors-topic-a"]).start();
+    let c = world("actors-topic-c", &["actors-topic-a", "actors-topic-b"]).start();
+    let (read_a, read_b) = (Arc::new(Mutex::new(Vec::new())), Arc::new(Mutex::new(Vec::new())));
+    let reader: Addr<Syn, _> = Reader(Arc::clone(&read_a)).start();
+    run(&mut sys, World::topic::<News>(&a, "news").subscribe(reader.recipient())).unwrap();
+    let reader: Addr<Syn, _> = Reader(Arc::clone(&read_b)).start();
+    let topic_b = World::topic::<News>(&b, "news");
+    let sub_b = run(&mut sys, topic_b.subscribe(reader.recipient())).unwrap();
+
+    // published message reaches subscribers on every node
+    let topic = World::topic::<News>(&c, "news");
+    let (t, a2, b2) = (topic.clone(), Arc::clone(&read_a), Arc::clone(&read_b));
+    run(&mut sys, wait_for(move || {
+        t.publish(News("first".to_owned()));
+        !a2.lock().unwrap().is_empty() && !b2.lock().unwrap().is_empty()
+    })).unwrap();
+    topic.publish(News("second".to_owned()));
+    let (a2, b2) = (Arc::clone(&read_a), Arc::clone(&read_b));
+    run(&mut sys, wait_for(move || {
+        a2.lock().unwrap().contains(&"second".to_owned()) &&
+            b2.lock().unwrap().contains(&"second".to_owned())
+    })).unwrap();
+    assert!(read_a.lock().unwrap().iter().all(|msg| msg == "first" || msg == "second"));
+
+    // unsubscribed recipient does not receive messages
+    topic_b.unsubscribe(sub_b);
+    topic.publish(News("third".to_owned()));
+    let a2 = Arc::clone(&read_a);
+    run(&mut sys, wait_for(move || a2.lock().unwrap().contains(&"third".to_owned()))).unwrap();
+    run(&mut sys, delay(100)).unwrap();
+    assert!(!read_b.lock().unwrap().contains(&"third".to_owned()));
+}
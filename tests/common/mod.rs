@@ -0,0 +1,256 @@
+//! Helpers for tests that run several worlds in one process
+//! over `mem://` transport
+#![allow(dead_code)]
+
+use std::io;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use actix::prelude::*;
+use actix::SystemRunner;
+use env_logger;
+use futures::{future, Future, Poll, Stream};
+use futures::future::{Either, Loop};
+use tokio_core::reactor::Timeout;
+use tokio_io::{AsyncRead, AsyncWrite};
+
+use actix_remote::{FromPeer, Incoming, Io, Listener, NodeId, Transport, World};
+
+/// Interval between attempts of `retry()`, in milliseconds
+const RETRY_INTERVAL: u64 = 50;
+/// Number of attempts of `retry()`
+const RETRY_ATTEMPTS: u32 = 200;
+/// Time limit of single test, in seconds
+const TEST_TIMEOUT: u64 = 30;
+
+
+/// Test message, provider replies with value and sender node
+#[derive(Debug, Message, Serialize, Deserialize, RemoteMessage)]
+#[rtype(Pong)]
+#[remote(id="test.Ping")]
+pub struct Ping(pub u32);
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Pong {
+    pub value: u32,
+    pub peer: NodeId,
+}
+
+/// Provider of `Ping`, registered with `World::register_peer_recipient()`
+pub struct Echo;
+
+impl Actor for Echo {
+    type Context = Context<Self>;
+}
+
+impl Handler<FromPeer<Ping>> for Echo {
+    type Result = MessageResult<FromPeer<Ping>>;
+
+    fn handle(&mut self, msg: FromPeer<Ping>, _: &mut Context<Self>)
+              -> MessageResult<FromPeer<Ping>>
+    {
+        MessageResult(Pong{value: msg.msg.0, peer: msg.peer.id})
+    }
+}
+
+/// Test message, provider replies with its name
+#[derive(Debug, Message, Serialize, Deserialize, RemoteMessage)]
+#[rtype(String)]
+#[remote(id="test.Whoami")]
+pub struct Whoami;
+
+/// Provider of `Whoami`
+pub struct Named(pub &'static str);
+
+impl Actor for Named {
+    type Context = Context<Self>;
+}
+
+impl Handler<Whoami> for Named {
+    type Result = String;
+
+    fn handle(&mut self, _: Whoami, _: &mut Context<Self>) -> String {
+        self.0.to_owned()
+    }
+}
+
+/// Transport over `mem://` listeners that counts open streams,
+/// both ends of the connection are counted
+pub struct Counted {
+    scheme: &'static str,
+    inner: Rc<Transport>,
+    open: Arc<AtomicUsize>,
+}
+
+impl Counted {
+    pub fn new<T: Transport>(scheme: &'static str, inner: T) -> Counted {
+        Counted{scheme: scheme, inner: Rc::new(inner), open: Arc::new(AtomicUsize::new(0))}
+    }
+
+    /// Number of open streams
+    pub fn open(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.open)
+    }
+}
+
+impl Clone for Counted {
+    fn clone(&self) -> Counted {
+        Counted{scheme: self.scheme, inner: Rc::clone(&self.inner), open: self.open()}
+    }
+}
+
+impl Transport for Counted {
+    fn scheme(&self) -> &str {
+        self.scheme
+    }
+
+    fn listen(&self, addr: &str) -> io::Result<Box<Listener>> {
+        let lst = self.inner.listen(addr)?;
+        Ok(Box::new(CountedListener{scheme: self.scheme, lst: lst, open: self.open()}))
+    }
+
+    fn dial(&self, addr: &str) -> Box<Future<Item=Box<Io>, Error=io::Error>> {
+        let open = self.open();
+        Box::new(self.inner.dial(addr).map(move |io| CountedIo::boxed(io, open)))
+    }
+}
+
+struct CountedListener {
+    scheme: &'static str,
+    lst: Box<Listener>,
+    open: Arc<AtomicUsize>,
+}
+
+impl Listener for CountedListener {
+    fn local_addr(&self) -> String {
+        let addr = self.lst.local_addr();
+        match addr.find("://") {
+            Some(pos) => format!("{}{}", self.scheme, &addr[pos..]),
+            None => format!("{}://{}", self.scheme, addr),
+        }
+    }
+
+    fn incoming(self: Box<Self>) -> Incoming {
+        let open = self.open;
+        Box::new(self.lst.incoming().map(
+            move |(io, peer)| (CountedIo::boxed(io, Arc::clone(&open)), peer)))
+    }
+}
+
+struct CountedIo {
+    io: Box<Io>,
+    open: Arc<AtomicUsize>,
+}
+
+impl CountedIo {
+    fn boxed(io: Box<Io>, open: Arc<AtomicUsize>) -> Box<Io> {
+        open.fetch_add(1, Ordering::SeqCst);
+        Box::new(CountedIo{io: io, open: open})
+    }
+}
+
+impl Drop for CountedIo {
+    fn drop(&mut self) {
+        self.open.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl io::Read for CountedIo {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.io.read(buf)
+    }
+}
+
+impl io::Write for CountedIo {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.io.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.io.flush()
+    }
+}
+
+impl AsyncRead for CountedIo {}
+
+impl AsyncWrite for CountedIo {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.io.shutdown()
+    }
+}
+
+/// Create world that listens on `mem://name` and connects to `nodes`
+pub fn world(name: &str, nodes: &[&str]) -> World {
+    let mut world = World::new(format!("mem://{}", name)).unwrap();
+    for node in nodes {
+        world = world.add_node(Some(format!("mem://{}", node)));
+    }
+    world
+}
+
+/// Start `Echo` and register it as `Ping` provider
+pub fn echo(world: &Addr<Syn, World>) -> Box<Future<Item=(), Error=()>> {
+    let echo: Addr<Syn, _> = Echo.start();
+    Box::new(
+        World::register_peer_recipient(world, echo.recipient())
+            .map_err(|err| panic!("Can not register provider: {}", err)))
+}
+
+/// Create system of the test, logging is configured with `RUST_LOG`
+pub fn system() -> SystemRunner {
+    let _ = env_logger::try_init();
+    System::new("test")
+}
+
+/// Start `Named` and register it as `Whoami` provider
+pub fn named(world: &Addr<Syn, World>, name: &'static str) -> Box<Future<Item=(), Error=()>> {
+    let named: Addr<Syn, _> = Named(name).start();
+    Box::new(
+        World::register_recipient(world, named.recipient())
+            .map_err(|err| panic!("Can not register provider: {}", err)))
+}
+
+/// Run future to completion, panics if it does not complete in time
+pub fn run<F>(sys: &mut SystemRunner, fut: F) -> Result<F::Item, F::Error>
+    where F: Future
+{
+    let timeout = Timeout::new(Duration::from_secs(TEST_TIMEOUT), Arbiter::handle()).unwrap();
+    match sys.run_until_complete(fut.select2(timeout)) {
+        Ok(Either::A((item, _))) => Ok(item),
+        Err(Either::A((err, _))) => Err(err),
+        _ => panic!("Test did not complete in {} seconds", TEST_TIMEOUT),
+    }
+}
+
+/// Future that resolves after `ms` milliseconds
+pub fn delay(ms: u64) -> Box<Future<Item=(), Error=()>> {
+    Box::new(
+        Timeout::new(Duration::from_millis(ms), Arbiter::handle()).unwrap()
+            .map_err(|_| ()))
+}
+
+/// Call `f` until returned future succeeds, fails with last error
+/// once attempts are exhausted
+pub fn retry<F, R>(f: F) -> Box<Future<Item=R::Item, Error=R::Error>>
+    where F: Fn() -> R + 'static, R: Future + 'static
+{
+    Box::new(future::loop_fn(0, move |attempt| {
+        f().then(move |res| match res {
+            Ok(item) => Either::A(future::ok(Loop::Break(item))),
+            Err(err) => if attempt + 1 >= RETRY_ATTEMPTS {
+                Either::A(future::err(err))
+            } else {
+                Either::B(delay(RETRY_INTERVAL).then(move |_| Ok(Loop::Continue(attempt + 1))))
+            },
+        })
+    }))
+}
+
+/// Wait until `check` returns true
+pub fn wait_for<F>(check: F) -> Box<Future<Item=(), Error=()>>
+    where F: Fn() -> bool + 'static
+{
+    retry(move || future::result(if check() { Ok(()) } else { Err(()) }))
+}
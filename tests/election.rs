@@ -0,0 +1,76 @@
+#![allow(proc_macro_derive_resolution_fallback, bare_trait_objects, non_local_definitions)]
+#![allow(clippy::redundant_field_names)]
+
+extern crate actix;
+extern crate actix_remote;
+#[macro_use] extern crate actix_remote_derive;
+extern crate env_logger;
+extern crate futures;
+#[macro_use] extern crate serde_derive;
+extern crate tokio_core;
+extern crate tokio_io;
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use actix::prelude::*;
+
+use actix_remote::{LeaderChanged, NodeId, World};
+
+use common::{run, system, wait_for, world};
+
+
+/// Subscriber, keeps last leader state
+struct Leader(Arc<Mutex<Option<LeaderChanged>>>);
+
+impl Actor for Leader {
+    type Context = Context<Self>;
+}
+
+impl Handler<LeaderChanged> for Leader {
+    type Result = ();
+
+    fn handle(&mut self, msg: LeaderChanged, _: &mut Context<Self>) {
+        *self.0.lock().unwrap() = Some(msg);
+    }
+}
+
+/// Subscribe to leader changes of `db` role
+fn subscribe(world: &Addr<Syn, World>) -> Arc<Mutex<Option<LeaderChanged>>> {
+    let state = Arc::new(Mutex::new(None));
+    let leader: Addr<Syn, _> = Leader(Arc::clone(&state)).start();
+    World::leader_election(world, "db", leader.recipient());
+    state
+}
+
+/// Leader and local flag seen by subscriber
+fn leader(state: &Arc<Mutex<Option<LeaderChanged>>>) -> Option<(NodeId, bool)> {
+    state.lock().unwrap().as_ref().and_then(|s| s.leader.map(|leader| (leader, s.local)))
+}
+
+#[test]
+fn test_bully() {
+    let mut sys = system();
+
+    let a = world("election-a", &[]).role("db");
+    let b = world("election-b", &["election-a"]).role("db");
+    let c = world("election-c", &["election-a", "election-b"]).role("db");
+    // node without role follows election, but does not take part in it
+    let d = world("election-d", &["election-a", "election-b", "election-c"]);
+    let ids = vec![a.node_id(), b.node_id(), c.node_id()];
+    let highest = *ids.iter().max().unwrap();
+    let id_d = d.node_id();
+    let states: Vec<_> = [a.start(), b.start(), c.start(), d.start()].iter()
+        .map(subscribe).collect();
+
+    // node with highest id among nodes with role is leader
+    let (s, expected) = (states.clone(), ids.clone());
+    run(&mut sys, wait_for(move || {
+        s.iter().zip(expected.iter().chain(Some(&id_d)))
+            .all(|(state, id)| leader(state) == Some((highest, *id == highest)))
+    })).unwrap();
+    for state in &states {
+        assert!(state.lock().unwrap().as_ref().unwrap().term > 0);
+    }
+}
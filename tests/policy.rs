@@ -0,0 +1,191 @@
+#![allow(proc_macro_derive_resolution_fallback, bare_trait_objects, non_local_definitions)]
+#![allow(clippy::redundant_field_names)]
+
+extern crate actix;
+extern crate actix_remote;
+#[macro_use] extern crate actix_remote_derive;
+extern crate env_logger;
+extern crate futures;
+#[macro_use] extern crate serde_derive;
+extern crate tokio_core;
+extern crate tokio_io;
+extern crate uuid;
+
+mod common;
+
+use actix::prelude::*;
+use futures::Future;
+
+use actix_remote::{Policy, RemoteActor, RemoteError, RemoteRecipient, World};
+
+use common::{delay, retry, run, system, world, Echo, Named, Ping, Whoami};
+
+
+/// Message of protected actor
+#[derive(Debug, Message, Serialize, Deserialize, RemoteMessage)]
+#[rtype(String)]
+#[remote(id="policy.Secret")]
+struct Secret;
+
+/// Protected actor
+struct Vault;
+
+impl Actor for Vault {
+    type Context = Context<Self>;
+}
+
+impl Handler<Secret> for Vault {
+    type Result = String;
+
+    fn handle(&mut self, _: Secret, _: &mut Context<Self>) -> String {
+        "secret".to_owned()
+    }
+}
+
+type Relayed = Result<String, String>;
+
+/// Peer asks provider to send `Secret` to actor reference
+#[derive(Message, Serialize, Deserialize, RemoteMessage)]
+#[rtype(Relayed)]
+#[remote(id="policy.Relay")]
+struct Relay(RemoteRecipient<Secret>);
+
+/// Provider of `Relay`, sends message with reference from the message
+struct Relayer;
+
+impl Actor for Relayer {
+    type Context = Context<Self>;
+}
+
+impl Handler<Relay> for Relayer {
+    type Result = ResponseFuture<String, String>;
+
+    fn handle(&mut self, msg: Relay, _: &mut Context<Self>) -> ResponseFuture<String, String> {
+        Box::new(msg.0.send(Secret).map_err(|err| format!("{:?}", err)))
+    }
+}
+
+type VaultRef = RemoteRecipient<Secret>;
+
+/// Peer asks for reference of protected actor
+#[derive(Message, Serialize, Deserialize, RemoteMessage)]
+#[rtype(VaultRef)]
+#[remote(id="policy.Open")]
+struct Open;
+
+/// Provider of `Open`
+struct Keeper(RemoteRecipient<Secret>);
+
+impl Actor for Keeper {
+    type Context = Context<Self>;
+}
+
+impl Handler<Open> for Keeper {
+    type Result = MessageResult<Open>;
+
+    fn handle(&mut self, _: Open, _: &mut Context<Self>) -> MessageResult<Open> {
+        MessageResult(self.0.clone())
+    }
+}
+
+/// Register `Vault` that accepts `Secret` with `policy`
+fn vault(world: &Addr<Syn, World>, policy: Policy)
+         -> Box<Future<Item=RemoteRecipient<Secret>, Error=()>>
+{
+    let vault: Addr<Syn, _> = Vault.start();
+    Box::new(
+        RemoteActor::<Vault>::new().policy(policy).recipient::<Secret>(vault.recipient())
+            .register(world)
+            .map(|addr| addr.recipient::<Secret>())
+            .map_err(|err| panic!("Can not register actor: {}", err)))
+}
+
+#[test]
+fn test_relayed_reference() {
+    let mut sys = system();
+
+    let mut a = world("policy-relay-a", &[]);
+    let b = world("policy-relay-b", &["policy-relay-a"]);
+    let (id_a, id_b) = (a.node_id(), b.node_id());
+    let to_b = a.get_recipient::<Relay>();
+    let (_a, b) = (a.start(), b.start());
+
+    let relayer: Addr<Syn, _> = Relayer.start();
+    run(&mut sys, World::register_recipient(&b, relayer.recipient())).unwrap();
+    let local = run(&mut sys, vault(&b, Policy::nodes(vec![id_b.uuid]))).unwrap();
+    let shared = run(&mut sys, vault(&b, Policy::nodes(vec![id_a.uuid]))).unwrap();
+
+    // local node is allowed to use its own reference
+    assert_eq!(run(&mut sys, local.send(Secret)).unwrap(), "secret");
+    match run(&mut sys, shared.send(Secret)) {
+        Err(RemoteError::PermissionDenied) => (),
+        res => panic!("Unexpected result: {:?}", res),
+    }
+
+    // reference to local actor received from peer is used with identity
+    // of the peer, not of the local node
+    let to = to_b.clone();
+    let res = run(&mut sys, retry(move || to.send(Relay(local.clone())))).unwrap();
+    assert_eq!(res, Err(format!("{:?}", RemoteError::PermissionDenied)));
+    let res = run(&mut sys, to_b.send(Relay(shared.clone()))).unwrap();
+    assert_eq!(res, Ok("secret".to_owned()));
+}
+
+#[test]
+fn test_permission_denied() {
+    let mut sys = system();
+
+    let mut a = world("policy-denied-a", &[]);
+    let b = world("policy-denied-b", &["policy-denied-a"]).role("admin");
+    let to_b = a.get_recipient::<Open>();
+    let (_a, b) = (a.start(), b.start());
+
+    let admin = run(&mut sys, vault(&b, Policy::roles(vec!["admin"]))).unwrap();
+    let keeper: Addr<Syn, _> = Keeper(admin).start();
+    run(&mut sys, World::register_recipient(&b, keeper.recipient())).unwrap();
+
+    // a receives reference, b checks a on delivery and replies with
+    // permission denied error code
+    let vault = run(&mut sys, retry(move || to_b.send(Open))).unwrap();
+    match run(&mut sys, vault.send(Secret)) {
+        Err(RemoteError::PermissionDenied) => (),
+        res => panic!("Unexpected result: {:?}", res),
+    }
+}
+
+#[test]
+fn test_filtered_supported() {
+    let mut sys = system();
+
+    let a = world("policy-supported-a", &[]).start();
+    let named: Addr<Syn, _> = Named("a").start();
+    run(&mut sys, World::register_recipient_with_policy(
+        &a, named.recipient(), Policy::roles(vec!["admin"]))).unwrap();
+
+    let mut b = world("policy-supported-b", &["policy-supported-a"]).role("admin");
+    let mut c = world("policy-supported-c", &["policy-supported-a"]);
+    let id_c = c.node_id();
+    let (whoami_b, ping_b) = (b.get_recipient::<Whoami>(), b.get_recipient::<Ping>());
+    let (whoami_c, ping_c) = (c.get_recipient::<Whoami>(), c.get_recipient::<Ping>());
+    let (_b, _c) = (b.start(), c.start());
+
+    // handshake announces types allowed for the peer
+    assert_eq!(run(&mut sys, retry(move || whoami_b.send(Whoami))).unwrap(), "a");
+
+    // types registered later are announced to allowed peers only
+    let echo: Addr<Syn, _> = Echo.start();
+    run(&mut sys, World::register_peer_recipient_with_policy(
+        &a, echo.recipient(), Policy::nodes(vec![id_c.uuid]))).unwrap();
+    let pong = run(&mut sys, retry(move || ping_c.send(Ping(1)))).unwrap();
+    assert_eq!(pong.peer, id_c);
+    run(&mut sys, delay(200)).unwrap();
+
+    match run(&mut sys, whoami_c.send(Whoami)) {
+        Err(RemoteError::NotSupported) => (),
+        res => panic!("Unexpected result: {:?}", res),
+    }
+    match run(&mut sys, ping_b.send(Ping(2))) {
+        Err(RemoteError::NotSupported) => (),
+        res => panic!("Unexpected result: {:?}", res.map(|p| p.value)),
+    }
+}
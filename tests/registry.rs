@@ -0,0 +1,128 @@
+#![allow(proc_macro_derive_resolution_fallback, bare_trait_objects, non_local_definitions)]
+#![allow(clippy::redundant_field_names)]
+
+extern crate actix;
+extern crate actix_remote;
+#[macro_use] extern crate actix_remote_derive;
+extern crate env_logger;
+extern crate futures;
+#[macro_use] extern crate serde_derive;
+extern crate tokio_core;
+extern crate tokio_io;
+
+mod common;
+
+use std::{thread, time};
+use std::sync::{Arc, Mutex};
+
+use actix::prelude::*;
+use futures::Future;
+
+use actix_remote::{RegistrationError, RemoteError, RemoteRecipient, Terminated,
+                   TerminationReason, World};
+
+use common::{retry, run, system, wait_for, world, Named, Ping, Whoami};
+
+
+/// Watcher, keeps received notification
+struct Watcher(Arc<Mutex<Option<Terminated>>>);
+
+impl Actor for Watcher {
+    type Context = Context<Self>;
+}
+
+impl Handler<Terminated> for Watcher {
+    type Result = ();
+
+    fn handle(&mut self, msg: Terminated, _: &mut Context<Self>) {
+        *self.0.lock().unwrap() = Some(msg);
+    }
+}
+
+/// Register `Named` provider under `name`
+fn register(world: &Addr<Syn, World>, name: &str, node: &'static str)
+            -> Box<Future<Item=RemoteRecipient<Whoami>, Error=RegistrationError>>
+{
+    let named: Addr<Syn, _> = Named(node).start();
+    World::register_named(world, name, named.recipient())
+}
+
+/// Lookup `name` until it resolves to provider of `node`
+fn resolves_to(world: &Addr<Syn, World>, name: &'static str, node: &'static str)
+               -> Box<Future<Item=String, Error=RemoteError>>
+{
+    let world = world.clone();
+    retry(move || World::lookup::<Whoami>(&world, name)
+          .and_then(|to| to.send(Whoami))
+          .and_then(move |res| if res == node { Ok(res) } else { Err(RemoteError::Unavailable) }))
+}
+
+#[test]
+fn test_lookup() {
+    let mut sys = system();
+
+    let a = world("registry-lookup-a", &[]).start();
+    let b = world("registry-lookup-b", &["registry-lookup-a"]).start();
+    run(&mut sys, register(&a, "registry.lookup", "a")).unwrap();
+
+    // name resolves on every node
+    assert_eq!(run(&mut sys, resolves_to(&a, "registry.lookup", "a")).unwrap(), "a");
+    assert_eq!(run(&mut sys, resolves_to(&b, "registry.lookup", "a")).unwrap(), "a");
+
+    // name is registered once per node
+    match run(&mut sys, register(&a, "registry.lookup", "a")) {
+        Err(RegistrationError::NameTaken(ref name)) if name == "registry.lookup" => (),
+        res => panic!("Unexpected result: {:?}", res.map(|to| to.id())),
+    }
+
+    // lookup checks message type and name
+    match run(&mut sys, World::lookup::<Ping>(&b, "registry.lookup")) {
+        Err(RemoteError::TypeMismatch) => (),
+        res => panic!("Unexpected result: {:?}", res.map(|to| to.id())),
+    }
+    match run(&mut sys, World::lookup::<Whoami>(&b, "registry.unknown")) {
+        Err(RemoteError::NameNotFound) => (),
+        res => panic!("Unexpected result: {:?}", res.map(|to| to.id())),
+    }
+
+    // removed name is not resolved on connected nodes
+    World::unregister_named(&a, "registry.lookup");
+    let b2 = b.clone();
+    run(&mut sys, retry(move || World::lookup::<Whoami>(&b2, "registry.lookup").then(
+        |res| match res {
+            Err(RemoteError::NameNotFound) => Ok(()),
+            _ => Err(()),
+        }))).unwrap();
+}
+
+#[test]
+fn test_oldest_wins() {
+    let mut sys = system();
+
+    // a is created first and is older, it starts after b registers name
+    let a = world("registry-oldest-a", &[]);
+    thread::sleep(time::Duration::from_millis(10));
+    let b = world("registry-oldest-b", &["registry-oldest-a"]).start();
+    let local = run(&mut sys, register(&b, "registry.oldest", "b")).unwrap();
+    let state = Arc::new(Mutex::new(None));
+    let watcher: Addr<Syn, _> = Watcher(Arc::clone(&state)).start();
+    World::watch(&b, local.id(), watcher.recipient());
+
+    // registration of older node wins, local registration is removed
+    // and its watchers are notified
+    let a = a.start();
+    run(&mut sys, register(&a, "registry.oldest", "a")).unwrap();
+    assert_eq!(run(&mut sys, resolves_to(&b, "registry.oldest", "a")).unwrap(), "a");
+    let s = Arc::clone(&state);
+    run(&mut sys, wait_for(move || s.lock().unwrap().is_some())).unwrap();
+    let terminated = state.lock().unwrap().take().unwrap();
+    assert_eq!(terminated.actor, local.id());
+    assert_eq!(terminated.reason, TerminationReason::Stopped);
+    assert_eq!(run(&mut sys, resolves_to(&a, "registry.oldest", "a")).unwrap(), "a");
+
+    // name of older node can not be registered on younger node
+    match run(&mut sys, register(&b, "registry.oldest", "b")) {
+        Err(RegistrationError::NameTaken(_)) => (),
+        res => panic!("Unexpected result: {:?}", res.map(|to| to.id())),
+    }
+}
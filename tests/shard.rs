@@ -0,0 +1,210 @@
+#![allow(proc_macro_derive_resolution_fallback, bare_trait_objects, non_local_definitions)]
+#![allow(clippy::redundant_field_names)]
+
+extern crate actix;
+extern crate actix_remote;
+#[macro_use] extern crate actix_remote_derive;
+extern crate env_logger;
+extern crate futures;
+#[macro_use] extern crate serde_derive;
+extern crate tokio_core;
+extern crate tokio_io;
+
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use actix::prelude::*;
+use futures::{future, Future};
+
+use actix_remote::{Passivate, RemoteError, ShardRegion, Sharding, World};
+
+use common::{retry, run, system, wait_for, world};
+
+
+/// Entity message, routed by entity id
+#[derive(Debug, Message, Serialize, Deserialize, RemoteMessage)]
+#[rtype(Visited)]
+#[remote(id="shard.Visit")]
+struct Visit(#[remote(routing_key)] String);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Visited {
+    node: String,
+    count: u32,
+}
+
+/// Entity, counts visits since start
+struct Counter {
+    node: &'static str,
+    count: u32,
+    passivated: Arc<AtomicUsize>,
+}
+
+impl Actor for Counter {
+    type Context = Context<Self>;
+}
+
+impl Handler<Visit> for Counter {
+    type Result = MessageResult<Visit>;
+
+    fn handle(&mut self, _: Visit, _: &mut Context<Self>) -> MessageResult<Visit> {
+        self.count += 1;
+        MessageResult(Visited{node: self.node.to_owned(), count: self.count})
+    }
+}
+
+impl Handler<Passivate> for Counter {
+    type Result = ();
+
+    fn handle(&mut self, _: Passivate, ctx: &mut Context<Self>) {
+        self.passivated.fetch_add(1, Ordering::SeqCst);
+        ctx.stop();
+    }
+}
+
+/// Start region of `Counter` entities, returns sender and number of
+/// passivated entities
+fn region(world: &Addr<Syn, World>, node: &'static str, idle: u64)
+          -> (Sharding<Visit>, Arc<AtomicUsize>)
+{
+    let passivated = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&passivated);
+    let region = ShardRegion::new(move |_: &str| {
+        let addr: Addr<Syn, _> = Counter{
+            node: node, count: 0, passivated: Arc::clone(&counter)}.start();
+        (addr.clone().recipient(), addr.recipient())
+    }).shards(16).passivate_after(Duration::from_secs(idle)).start(world);
+    (region, passivated)
+}
+
+fn keys(prefix: &str) -> Vec<String> {
+    (0..20).map(|i| format!("{}-{}", prefix, i)).collect()
+}
+
+/// Visit every entity, messages are sent from task, mailbox of region
+/// is smaller than number of entities
+fn visit(region: &Sharding<Visit>, keys: &[String])
+         -> Box<Future<Item=Vec<Visited>, Error=RemoteError>>
+{
+    let (region, keys) = (region.clone(), keys.to_vec());
+    Box::new(future::lazy(move || future::join_all(
+        keys.into_iter().map(|key| region.send(Visit(key))).collect::<Vec<_>>())))
+}
+
+/// Wait until all regions route probe entities to same nodes and
+/// `nodes` own some of them
+fn settled(regions: Vec<Sharding<Visit>>, nodes: &'static [&'static str])
+           -> Box<Future<Item=(), Error=RemoteError>>
+{
+    let probes = keys("probe");
+    Box::new(retry(move || {
+        future::join_all(regions.iter().map(|r| visit(r, &probes)).collect::<Vec<_>>())
+            .and_then(move |visits| {
+                let owners: Vec<Vec<String>> = visits.into_iter()
+                    .map(|v| v.into_iter().map(|v| v.node).collect()).collect();
+                if owners.iter().all(|o| *o == owners[0]) &&
+                    nodes.iter().all(|n| owners[0].iter().any(|o| o == n))
+                {
+                    Ok(())
+                } else {
+                    Err(RemoteError::Unavailable)
+                }
+            })
+    }))
+}
+
+#[test]
+fn test_routing() {
+    let mut sys = system();
+
+    let a = world("shard-route-a", &[]).start();
+    let b = world("shard-route-b", &["shard-route-a"]).start();
+    let mut c = world("shard-route-c", &["shard-route-a", "shard-route-b"]);
+    let to_region = c.get_recipient::<Visit>();
+    let _c = c.start();
+    let (region_a, _) = region(&a, "a", 600);
+    let (region_b, _) = region(&b, "b", 600);
+    run(&mut sys, settled(vec![region_a.clone(), region_b.clone()], &["a", "b"])).unwrap();
+
+    // entity is started once, on the owner node, for both senders
+    let keys = keys("key");
+    let first = run(&mut sys, visit(&region_a, &keys)).unwrap();
+    let second = run(&mut sys, visit(&region_b, &keys)).unwrap();
+    for (first, second) in first.iter().zip(second.iter()) {
+        assert_eq!(first.count, 1);
+        assert_eq!(*second, Visited{node: first.node.clone(), count: 2});
+    }
+
+    // region rejects messages for shards of other node, sender
+    // without region does not retry
+    let (mut delivered, mut rejected) = (0, 0);
+    for (key, owner) in keys.iter().zip(first.iter()) {
+        match run(&mut sys, to_region.send(Visit(key.clone()))) {
+            Ok(visited) => {
+                assert_eq!(visited, Visited{node: owner.node.clone(), count: 3});
+                delivered += 1;
+            },
+            Err(RemoteError::Unavailable) => rejected += 1,
+            Err(err) => panic!("Unexpected error: {:?}", err),
+        }
+    }
+    assert!(delivered > 0 && rejected > 0, "{} {}", delivered, rejected);
+}
+
+#[test]
+fn test_passivation() {
+    let mut sys = system();
+
+    let a = world("shard-idle-a", &[]).start();
+    let (region_a, passivated) = region(&a, "a", 1);
+
+    let visit = move || region_a.send(Visit("idle".to_owned()));
+    assert_eq!(run(&mut sys, visit()).unwrap().count, 1);
+    assert_eq!(run(&mut sys, visit()).unwrap().count, 2);
+
+    // idle entity is passivated, next message starts new entity
+    let count = Arc::clone(&passivated);
+    run(&mut sys, wait_for(move || count.load(Ordering::SeqCst) == 1)).unwrap();
+    assert_eq!(run(&mut sys, visit()).unwrap().count, 1);
+}
+
+#[test]
+fn test_rebalance() {
+    let mut sys = system();
+
+    let a = world("shard-move-a", &[]).start();
+    let b = world("shard-move-b", &["shard-move-a"]).start();
+    let (region_a, passivated_a) = region(&a, "a", 600);
+    let (region_b, passivated_b) = region(&b, "b", 600);
+    run(&mut sys, settled(vec![region_a.clone(), region_b.clone()], &["a", "b"])).unwrap();
+
+    let keys = keys("key");
+    let before = run(&mut sys, visit(&region_a, &keys)).unwrap();
+    assert!(before.iter().all(|v| v.count == 1));
+
+    // new member takes over some shards, entities of moved shards are
+    // passivated on previous owner
+    let c = world("shard-move-c", &["shard-move-a", "shard-move-b"]).start();
+    let (region_c, _) = region(&c, "c", 600);
+    run(&mut sys, settled(vec![region_a.clone(), region_b.clone(), region_c.clone()],
+                          &["a", "b", "c"])).unwrap();
+
+    let after = run(&mut sys, visit(&region_c, &keys)).unwrap();
+    let mut moved = 0;
+    for (before, after) in before.iter().zip(after.iter()) {
+        if after.node == "c" {
+            assert_eq!(after.count, 1);
+            moved += 1;
+        } else {
+            assert_eq!(*after, Visited{node: before.node.clone(), count: 2});
+        }
+    }
+    assert!(moved > 0);
+    let passivated = Arc::clone(&passivated_a);
+    run(&mut sys, wait_for(move || {
+        passivated.load(Ordering::SeqCst) + passivated_b.load(Ordering::SeqCst) >= moved
+    })).unwrap();
+}
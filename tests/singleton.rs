@@ -0,0 +1,148 @@
+#![allow(proc_macro_derive_resolution_fallback, bare_trait_objects, non_local_definitions)]
+#![allow(clippy::redundant_field_names)]
+
+extern crate actix;
+extern crate actix_remote;
+#[macro_use] extern crate actix_remote_derive;
+extern crate env_logger;
+extern crate futures;
+#[macro_use] extern crate serde_derive;
+extern crate tokio_core;
+extern crate tokio_io;
+
+mod common;
+
+use std::{thread, time};
+use std::sync::{mpsc, Arc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use actix::prelude::*;
+use futures::Future;
+
+use actix_remote::{Passivate, RemoteError, Singleton, World};
+
+use common::{retry, run, system, wait_for, world};
+
+
+/// Singleton message, instance replies with name of its node
+#[derive(Debug, Message, Serialize, Deserialize, RemoteMessage)]
+#[rtype(String)]
+#[remote(id="singleton.Owner")]
+struct Owner;
+
+/// Instance starts and passivations on one node
+#[derive(Clone, Default)]
+struct Counters {
+    started: Arc<AtomicUsize>,
+    passivated: Arc<AtomicUsize>,
+}
+
+impl Counters {
+    fn started(&self) -> usize {
+        self.started.load(Ordering::SeqCst)
+    }
+
+    fn passivated(&self) -> usize {
+        self.passivated.load(Ordering::SeqCst)
+    }
+}
+
+struct Instance {
+    node: &'static str,
+    counters: Counters,
+}
+
+impl Actor for Instance {
+    type Context = Context<Self>;
+}
+
+impl Handler<Owner> for Instance {
+    type Result = String;
+
+    fn handle(&mut self, _: Owner, _: &mut Context<Self>) -> String {
+        self.node.to_owned()
+    }
+}
+
+impl Handler<Passivate> for Instance {
+    type Result = ();
+
+    fn handle(&mut self, _: Passivate, ctx: &mut Context<Self>) {
+        self.counters.passivated.fetch_add(1, Ordering::SeqCst);
+        ctx.stop();
+    }
+}
+
+/// Register singleton, instances count starts and passivations
+fn singleton(world: &Addr<Syn, World>, node: &'static str, counters: &Counters)
+             -> Singleton<Owner>
+{
+    let counters = counters.clone();
+    World::register_singleton(world, move || {
+        counters.started.fetch_add(1, Ordering::SeqCst);
+        let addr: Addr<Syn, _> = Instance{node: node, counters: counters.clone()}.start();
+        (addr.clone().recipient(), addr.recipient())
+    })
+}
+
+/// Send `Owner` until instance on `node` replies
+fn owner_is(to: &Singleton<Owner>, node: &'static str)
+            -> Box<Future<Item=String, Error=RemoteError>>
+{
+    let to = to.clone();
+    retry(move || to.send(Owner).and_then(move |name| {
+        if name == node { Ok(name) } else { Err(RemoteError::Unavailable) }
+    }))
+}
+
+#[test]
+fn test_hand_over() {
+    let mut sys = system();
+    let (a_counters, b_counters) = (Counters::default(), Counters::default());
+
+    // a is created first and is older, it runs in its own system,
+    // so it can be stopped
+    let (tx, rx) = mpsc::channel();
+    let (start_a, started) = mpsc::channel::<()>();
+    let counters = a_counters.clone();
+    let a_thread = thread::spawn(move || {
+        let sys = System::new("a");
+        let a = world("singleton-a", &["singleton-b"]);
+        tx.send(None).unwrap();
+        started.recv().unwrap();
+        let a = a.start();
+        let _singleton = singleton(&a, "a", &counters);
+        tx.send(Some(Arbiter::system())).unwrap();
+        sys.run();
+    });
+    rx.recv().unwrap();
+    thread::sleep(time::Duration::from_millis(10));
+
+    // single node owns instance, message is buffered until owner is
+    // resolved and stable
+    let b = world("singleton-b", &[]).start();
+    let to = singleton(&b, "b", &b_counters);
+    assert_eq!(run(&mut sys, to.send(Owner)).unwrap(), "b");
+    assert_eq!(b_counters.started(), 1);
+
+    // older node joins, instance is passivated and started on older node
+    // after few resolutions, message rejected by new owner in the meantime
+    // is retried
+    start_a.send(()).unwrap();
+    let a_system = rx.recv().unwrap().unwrap();
+    let counters = b_counters.clone();
+    run(&mut sys, wait_for(move || counters.passivated() == 1)).unwrap();
+    assert_eq!(run(&mut sys, to.send(Owner)).unwrap(), "a");
+    assert_eq!(a_counters.started(), 1);
+    for _ in 0..5 {
+        assert_eq!(run(&mut sys, to.send(Owner)).unwrap(), "a");
+    }
+
+    // owner node stops, instance moves to remaining node
+    a_system.do_send(actix::msgs::SystemExit(0));
+    a_thread.join().unwrap();
+    assert_eq!(run(&mut sys, owner_is(&to, "b")).unwrap(), "b");
+    assert_eq!(b_counters.started(), 2);
+    assert_eq!(a_counters.started(), 1);
+    assert_eq!(b_counters.passivated(), 1);
+}
@@ -0,0 +1,80 @@
+#![allow(proc_macro_derive_resolution_fallback, bare_trait_objects, non_local_definitions)]
+#![allow(clippy::redundant_field_names)]
+
+extern crate actix;
+extern crate actix_remote;
+#[macro_use] extern crate actix_remote_derive;
+extern crate env_logger;
+extern crate futures;
+#[macro_use] extern crate serde_derive;
+extern crate tokio_core;
+extern crate tokio_io;
+
+mod common;
+
+use std::sync::atomic::Ordering;
+
+use futures::Future;
+
+use actix_remote::{MemTransport, World};
+
+use common::{echo, retry, run, system, Counted, Ping};
+
+
+#[test]
+fn test_unsupported_scheme() {
+    match World::unbound("none://transport-none".to_owned()).bind_addr() {
+        Err(ref err) => assert!(format!("{}", err).contains("none"), "{}", err),
+        Ok(_) => panic!("Listener is bound for unknown scheme"),
+    }
+}
+
+#[test]
+fn test_tcp_scheme() {
+    let mut sys = system();
+
+    // transport registered for tcp scheme replaces default tcp transport
+    // for listeners and for dialed nodes
+    let transport = Counted::new("tcp", MemTransport);
+    let open = transport.open();
+    let a = World::unbound("tcp://transport-tcp-a".to_owned())
+        .transport(transport.clone()).bind_addr().unwrap().start();
+    let mut b = World::unbound("tcp://transport-tcp-b".to_owned())
+        .transport(transport.clone()).bind_addr().unwrap()
+        .add_node(Some("transport-tcp-a"));
+    let id_b = b.node_id();
+    let to_a = b.get_recipient::<Ping>();
+    let _b = b.start();
+    run(&mut sys, echo(&a)).unwrap();
+
+    let pong = run(&mut sys, retry(move || to_a.send(Ping(1)))).unwrap();
+    assert_eq!(pong.peer, id_b);
+    assert_eq!(open.load(Ordering::SeqCst), 2);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unix() {
+    use std::{env, fs, process};
+
+    let mut sys = system();
+    let dir = env::temp_dir().join(format!("actix-remote-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("a.sock");
+    let addr = format!("unix://{}", path.display());
+
+    let a = World::unbound(addr.clone()).bind_addr().unwrap().start();
+    let mut b = World::unbound(format!("unix://{}", dir.join("b.sock").display()))
+        .bind_addr().unwrap()
+        .add_node(Some(addr));
+    let id_b = b.node_id();
+    let to_a = b.get_recipient::<Ping>();
+    let _b = b.start();
+    run(&mut sys, echo(&a)).unwrap();
+
+    let res = run(&mut sys, retry(move || to_a.send(Ping(2))).then(Ok::<_, ()>));
+    let _ = fs::remove_dir_all(&dir);
+    let pong = res.unwrap().unwrap();
+    assert_eq!(pong.value, 2);
+    assert_eq!(pong.peer, id_b);
+}
@@ -0,0 +1,158 @@
+#![allow(proc_macro_derive_resolution_fallback, bare_trait_objects, non_local_definitions)]
+#![allow(clippy::redundant_field_names)]
+
+extern crate actix;
+extern crate actix_remote;
+#[macro_use] extern crate actix_remote_derive;
+extern crate env_logger;
+extern crate futures;
+#[macro_use] extern crate serde_derive;
+extern crate tokio_core;
+extern crate tokio_io;
+
+mod common;
+
+use actix::prelude::*;
+use futures::Future;
+
+use actix_remote::{RegistrationError, RemoteError, RemoteMessage, World};
+
+use common::{retry, run, system, world};
+
+
+mod v1 {
+    use actix::prelude::*;
+
+    #[derive(Message, Serialize, Deserialize, RemoteMessage)]
+    #[rtype(String)]
+    #[remote(id="types.Item")]
+    pub struct Item {
+        pub name: String,
+    }
+}
+
+mod v2 {
+    use actix::prelude::*;
+
+    /// Same type id, different schema
+    #[derive(Message, Serialize, Deserialize, RemoteMessage)]
+    #[rtype(String)]
+    #[remote(id="types.Item")]
+    pub struct Item {
+        pub name: String,
+        pub size: u32,
+    }
+}
+
+/// Same type id, manual implementation without fingerprint
+#[derive(Message, Serialize, Deserialize)]
+#[rtype(String)]
+struct Plain {
+    name: String,
+}
+
+impl RemoteMessage for Plain {
+    fn type_id() -> &'static str {
+        "types.Item"
+    }
+}
+
+struct Store;
+
+impl Actor for Store {
+    type Context = Context<Self>;
+}
+
+impl Handler<v1::Item> for Store {
+    type Result = String;
+
+    fn handle(&mut self, msg: v1::Item, _: &mut Context<Self>) -> String {
+        msg.name
+    }
+}
+
+impl Handler<Plain> for Store {
+    type Result = String;
+
+    fn handle(&mut self, msg: Plain, _: &mut Context<Self>) -> String {
+        msg.name
+    }
+}
+
+#[test]
+fn test_fingerprint() {
+    assert_ne!(v1::Item::fingerprint(), 0);
+    assert_ne!(v1::Item::fingerprint(), v2::Item::fingerprint());
+    assert_eq!(Plain::fingerprint(), 0);
+}
+
+#[test]
+fn test_duplicate_type_id() {
+    let mut sys = system();
+
+    let a = world("types-duplicate-a", &[]).start();
+    let store: Addr<Syn, _> = Store.start();
+    run(&mut sys, World::register_recipient::<v1::Item>(&a, store.clone().recipient())).unwrap();
+    run(&mut sys, World::register_recipient::<v1::Item>(&a, store.clone().recipient())).unwrap();
+
+    // other type with same type id is rejected
+    match run(&mut sys, World::register_recipient::<Plain>(&a, store.recipient())) {
+        Err(RegistrationError::Duplicate(ref id)) if id == "types.Item" => (),
+        res => panic!("Unexpected result: {:?}", res),
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_duplicate_recipient() {
+    let mut a = world("types-recipient-a", &[]);
+    let _ = a.get_recipient::<v1::Item>();
+    let _ = a.get_recipient::<Plain>();
+}
+
+#[test]
+fn test_fingerprint_mismatch() {
+    let mut sys = system();
+
+    let a = world("types-mismatch-a", &[]).start();
+    let store: Addr<Syn, _> = Store.start();
+    run(&mut sys, World::register_recipient::<v1::Item>(&a, store.recipient())).unwrap();
+
+    let mut b = world("types-mismatch-b", &["types-mismatch-a"]);
+    let mut c = world("types-mismatch-c", &["types-mismatch-a"]);
+    let to_a = b.get_recipient::<v2::Item>();
+    let plain = c.get_recipient::<Plain>();
+    let (_b, _c) = (b.start(), c.start());
+
+    // unknown fingerprint is not checked
+    let name = run(&mut sys, retry(move || plain.send(Plain{name: "plain".to_owned()})));
+    assert_eq!(name.unwrap(), "plain");
+
+    // provider of same type id with other schema is not used
+    let res = run(&mut sys, retry(move || {
+        to_a.send(v2::Item{name: "v2".to_owned(), size: 1}).then(|res| match res {
+            Err(RemoteError::NotSupported) => Err(()),
+            res => Ok(res),
+        })
+    })).unwrap();
+    match res {
+        Err(RemoteError::TypeMismatch) => (),
+        res => panic!("Unexpected result: {:?}", res),
+    }
+}
+
+#[test]
+fn test_unknown_fingerprint() {
+    let mut sys = system();
+
+    // provider without fingerprint accepts messages of the type id
+    let a = world("types-unknown-a", &[]).start();
+    let store: Addr<Syn, _> = Store.start();
+    run(&mut sys, World::register_recipient::<Plain>(&a, store.recipient())).unwrap();
+
+    let mut b = world("types-unknown-b", &["types-unknown-a"]);
+    let to_a = b.get_recipient::<v1::Item>();
+    let _b = b.start();
+    let name = run(&mut sys, retry(move || to_a.send(v1::Item{name: "v1".to_owned()})));
+    assert_eq!(name.unwrap(), "v1");
+}
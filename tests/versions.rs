@@ -0,0 +1,148 @@
+#![allow(proc_macro_derive_resolution_fallback, bare_trait_objects, non_local_definitions)]
+#![allow(clippy::redundant_field_names)]
+
+extern crate actix;
+extern crate actix_remote;
+#[macro_use] extern crate actix_remote_derive;
+extern crate env_logger;
+extern crate futures;
+#[macro_use] extern crate serde_derive;
+extern crate tokio_core;
+extern crate tokio_io;
+
+mod common;
+
+use actix::prelude::*;
+use futures::Future;
+
+use actix_remote::{Policy, RemoteError, World};
+
+use common::{retry, run, system, world};
+
+
+mod v1 {
+    use actix::prelude::*;
+
+    #[derive(Message, Serialize, Deserialize, RemoteMessage)]
+    #[rtype(String)]
+    #[remote(id="versions.Job", version=1)]
+    pub struct Job {
+        pub name: String,
+    }
+}
+
+mod v2 {
+    use actix::prelude::*;
+
+    /// Same schema as `v1::Job`, newer version
+    #[derive(Message, Serialize, Deserialize, RemoteMessage)]
+    #[rtype(String)]
+    #[remote(id="versions.Job", version=2)]
+    pub struct Job {
+        pub name: String,
+    }
+}
+
+mod v4 {
+    use actix::prelude::*;
+
+    /// Version that no provider supports
+    #[derive(Message, Serialize, Deserialize, RemoteMessage)]
+    #[rtype(String)]
+    #[remote(id="versions.Job", version=4)]
+    pub struct Job {
+        pub name: String,
+    }
+}
+
+mod other {
+    use actix::prelude::*;
+
+    /// Same type id and version as `v1::Job`, other schema
+    #[derive(Message, Serialize, Deserialize, RemoteMessage)]
+    #[rtype(String)]
+    #[remote(id="versions.Job", version=1)]
+    pub struct Job {
+        pub title: String,
+    }
+}
+
+/// Provider, replies with name of its node
+struct Worker(&'static str);
+
+impl Actor for Worker {
+    type Context = Context<Self>;
+}
+
+impl Handler<v1::Job> for Worker {
+    type Result = String;
+
+    fn handle(&mut self, _: v1::Job, _: &mut Context<Self>) -> String {
+        self.0.to_owned()
+    }
+}
+
+impl Handler<v2::Job> for Worker {
+    type Result = String;
+
+    fn handle(&mut self, _: v2::Job, _: &mut Context<Self>) -> String {
+        self.0.to_owned()
+    }
+}
+
+impl Handler<other::Job> for Worker {
+    type Result = String;
+
+    fn handle(&mut self, _: other::Job, _: &mut Context<Self>) -> String {
+        self.0.to_owned()
+    }
+}
+
+#[test]
+fn test_routing() {
+    let mut sys = system();
+
+    // a supports version 1, b versions 2 and 3, x version 1 of other schema
+    let a = world("versions-a", &[]).start();
+    let b = world("versions-b", &["versions-a"]).start();
+    let x = world("versions-x", &["versions-a", "versions-b"]).start();
+    let worker: Addr<Syn, _> = Worker("a").start();
+    run(&mut sys, World::register_recipient_with_versions::<v1::Job>(
+        &a, worker.recipient(), Policy::allow_all(), 1, 1)).unwrap();
+    let worker: Addr<Syn, _> = Worker("b").start();
+    run(&mut sys, World::register_recipient_with_versions::<v2::Job>(
+        &b, worker.recipient(), Policy::allow_all(), 2, 3)).unwrap();
+    let worker: Addr<Syn, _> = Worker("x").start();
+    run(&mut sys, World::register_recipient::<other::Job>(&x, worker.recipient())).unwrap();
+
+    let nodes = ["versions-a", "versions-b", "versions-x"];
+    let mut c1 = world("versions-c1", &nodes);
+    let mut c2 = world("versions-c2", &nodes);
+    let mut c4 = world("versions-c4", &nodes);
+    let (to_v1, to_v2, to_v4) = (
+        c1.get_recipient::<v1::Job>(), c2.get_recipient::<v2::Job>(),
+        c4.get_recipient::<v4::Job>());
+    let _c = (c1.start(), c2.start(), c4.start());
+
+    // messages go only to providers of same schema that support version
+    let to = to_v1.clone();
+    run(&mut sys, retry(move || to.send(v1::Job{name: "1".to_owned()}))).unwrap();
+    let to = to_v2.clone();
+    run(&mut sys, retry(move || to.send(v2::Job{name: "2".to_owned()}))).unwrap();
+    for _ in 0..10 {
+        assert_eq!(run(&mut sys, to_v1.send(v1::Job{name: "1".to_owned()})).unwrap(), "a");
+        assert_eq!(run(&mut sys, to_v2.send(v2::Job{name: "2".to_owned()})).unwrap(), "b");
+    }
+
+    // version out of range of all providers is rejected
+    let res = run(&mut sys, retry(move || {
+        to_v4.send(v4::Job{name: "4".to_owned()}).then(|res| match res {
+            Err(RemoteError::NotSupported) => Err(()),
+            res => Ok(res),
+        })
+    })).unwrap();
+    match res {
+        Err(RemoteError::VersionMismatch) => (),
+        res => panic!("Unexpected result: {:?}", res),
+    }
+}
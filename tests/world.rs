@@ -0,0 +1,297 @@
+#![allow(proc_macro_derive_resolution_fallback, bare_trait_objects, non_local_definitions)]
+#![allow(clippy::redundant_field_names)]
+
+extern crate actix;
+extern crate actix_remote;
+#[macro_use] extern crate actix_remote_derive;
+extern crate env_logger;
+extern crate futures;
+#[macro_use] extern crate serde_derive;
+extern crate tokio_core;
+extern crate tokio_io;
+extern crate uuid;
+
+mod common;
+
+use futures::{Future, Poll, Stream};
+
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use actix::prelude::*;
+use actix_remote::{Incoming, Io, Listener, MemTransport, RemoteError, Terminated,
+                   TerminationReason, Transport, World};
+use futures::future;
+use tokio_io::{AsyncRead, AsyncWrite};
+use uuid::Uuid;
+
+use common::{delay, echo, named, retry, run, system, wait_for, world, Counted, Named, Ping,
+             Whoami};
+
+
+/// Transport over `mem://` listeners, streams stop reading
+/// and writing once transport is stalled
+#[derive(Clone, Default)]
+struct Stalling(Arc<AtomicBool>);
+
+impl Transport for Stalling {
+    fn scheme(&self) -> &str {
+        "mem"
+    }
+
+    fn listen(&self, addr: &str) -> io::Result<Box<Listener>> {
+        let lst = MemTransport.listen(addr)?;
+        Ok(Box::new(StallingListener{lst: lst, stalled: Arc::clone(&self.0)}))
+    }
+
+    fn dial(&self, addr: &str) -> Box<Future<Item=Box<Io>, Error=io::Error>> {
+        let stalled = Arc::clone(&self.0);
+        Box::new(MemTransport.dial(addr).map(move |io| StallingIo::boxed(io, stalled)))
+    }
+}
+
+struct StallingListener {
+    lst: Box<Listener>,
+    stalled: Arc<AtomicBool>,
+}
+
+impl Listener for StallingListener {
+    fn local_addr(&self) -> String {
+        self.lst.local_addr()
+    }
+
+    fn incoming(self: Box<Self>) -> Incoming {
+        let stalled = self.stalled;
+        Box::new(self.lst.incoming().map(
+            move |(io, peer)| (StallingIo::boxed(io, Arc::clone(&stalled)), peer)))
+    }
+}
+
+struct StallingIo {
+    io: Box<Io>,
+    stalled: Arc<AtomicBool>,
+}
+
+impl StallingIo {
+    fn boxed(io: Box<Io>, stalled: Arc<AtomicBool>) -> Box<Io> {
+        Box::new(StallingIo{io: io, stalled: stalled})
+    }
+}
+
+impl io::Read for StallingIo {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.stalled.load(Ordering::SeqCst) {
+            return Err(io::ErrorKind::WouldBlock.into())
+        }
+        self.io.read(buf)
+    }
+}
+
+impl io::Write for StallingIo {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.stalled.load(Ordering::SeqCst) {
+            return Ok(buf.len())
+        }
+        self.io.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.io.flush()
+    }
+}
+
+impl AsyncRead for StallingIo {}
+
+impl AsyncWrite for StallingIo {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.io.shutdown()
+    }
+}
+
+/// Watcher, keeps received notification
+struct Watcher(Arc<Mutex<Option<Terminated>>>);
+
+impl Actor for Watcher {
+    type Context = Context<Self>;
+}
+
+impl Handler<Terminated> for Watcher {
+    type Result = ();
+
+    fn handle(&mut self, msg: Terminated, _: &mut Context<Self>) {
+        *self.0.lock().unwrap() = Some(msg);
+    }
+}
+
+
+#[test]
+fn test_handshake_and_delivery() {
+    let mut sys = system();
+
+    let mut a = world("world-delivery-a", &[]);
+    let mut b = world("world-delivery-b", &["world-delivery-a"]);
+    let (id_a, id_b) = (a.node_id(), b.node_id());
+    let to_a = b.get_recipient::<Ping>();
+    let to_b = a.get_recipient::<Ping>();
+    let (a, b) = (a.start(), b.start());
+
+    run(&mut sys, echo(&a).join(echo(&b))).unwrap();
+
+    // b dials a, recipient is usable once a announces provider,
+    // provider sees handshake identity of the sender
+    let pong = run(&mut sys, retry(move || to_a.send(Ping(1)))).unwrap();
+    assert_eq!(pong.value, 1);
+    assert_eq!(pong.peer, id_b);
+
+    // a uses connection accepted from b
+    let pong = run(&mut sys, retry(move || to_b.send(Ping(2)))).unwrap();
+    assert_eq!(pong.value, 2);
+    assert_eq!(pong.peer, id_a);
+}
+
+#[test]
+fn test_no_provider() {
+    let mut sys = system();
+
+    let _a = world("world-none-a", &[]).start();
+    let mut b = world("world-none-b", &["world-none-a"]);
+    let to_a = b.get_recipient::<Ping>();
+    let _b = b.start();
+    run(&mut sys, delay(200)).unwrap();
+
+    // a does not provide message type
+    match run(&mut sys, to_a.send(Ping(1))) {
+        Err(RemoteError::NotSupported) => (),
+        res => panic!("Unexpected result: {:?}", res.map(|p| p.value)),
+    }
+}
+
+#[test]
+fn test_late_registration() {
+    let mut sys = system();
+
+    let a = world("world-late-a", &[]).start();
+    let mut b = world("world-late-b", &["world-late-a"]);
+    let to_a = b.get_recipient::<Ping>();
+    let _b = b.start();
+    run(&mut sys, delay(200)).unwrap();
+
+    // provider registered after handshake is announced to connected node
+    run(&mut sys, echo(&a)).unwrap();
+    let pong = run(&mut sys, retry(move || to_a.send(Ping(3)))).unwrap();
+    assert_eq!(pong.value, 3);
+}
+
+#[test]
+fn test_simultaneous_dial() {
+    let mut sys = system();
+    let transport = Counted::new("cmem", MemTransport);
+    let open = transport.open();
+
+    // a dials b by alias, so inbound connection from b does not match
+    // dialed address, b is not listening yet and a re-dials later
+    let mut a = World::unbound("cmem://dial-a".to_owned())
+        .transport(transport.clone()).bind_addr().unwrap()
+        .add_node(Some("cmem://dial-b-alias"));
+    let id_a = a.node_id();
+    let to_b = a.get_recipient::<Ping>();
+    let a = a.start();
+    run(&mut sys, echo(&a).join(delay(50))).unwrap();
+
+    // b dials a while a re-dials b
+    let mut b = World::unbound("cmem://dial-b".to_owned())
+        .transport(transport.clone()).bind_addr().unwrap()
+        .listen("cmem://dial-b-alias").unwrap()
+        .add_node(Some("cmem://dial-a"));
+    let id_b = b.node_id();
+    let to_a = b.get_recipient::<Ping>();
+    let b = b.start();
+    run(&mut sys, echo(&b)).unwrap();
+
+    let (a2, b2) = (to_a.clone(), to_b.clone());
+    let pong = run(&mut sys, retry(move || a2.send(Ping(1)))).unwrap();
+    assert_eq!(pong.peer, id_b);
+    let pong = run(&mut sys, retry(move || b2.send(Ping(2)))).unwrap();
+    assert_eq!(pong.peer, id_a);
+
+    // both connections were established, single connection is left,
+    // one stream on each side
+    let count = open.clone();
+    run(&mut sys, wait_for(move || count.load(Ordering::SeqCst) == 2)).unwrap();
+    run(&mut sys, delay(2000)).unwrap();
+    assert_eq!(open.load(Ordering::SeqCst), 2);
+
+    // dropped duplicate does not disconnect the node
+    assert_eq!(run(&mut sys, to_a.send(Ping(3))).unwrap().peer, id_b);
+    assert_eq!(run(&mut sys, to_b.send(Ping(4))).unwrap().peer, id_a);
+}
+
+#[test]
+fn test_restart() {
+    let mut sys = system();
+    let uuid = Uuid::new_v4();
+
+    let mut a = world("restart-a", &[]);
+    let to_b = a.get_recipient::<Whoami>();
+    let _a = a.start();
+
+    let b1 = world("restart-b1", &["restart-a"]).uuid(uuid);
+    let id_b1 = b1.node_id();
+    let b1 = b1.start();
+    run(&mut sys, named(&b1, "b1")).unwrap();
+    let to = to_b.clone();
+    assert_eq!(run(&mut sys, retry(move || to.send(Whoami))).unwrap(), "b1");
+
+    // same uuid, later incarnation, previous incarnation is still running
+    run(&mut sys, delay(10)).unwrap();
+    let b2 = world("restart-b2", &["restart-a"]).uuid(uuid);
+    assert!(b2.node_id().is_restart_of(&id_b1));
+    let b2 = b2.start();
+    run(&mut sys, named(&b2, "b2")).unwrap();
+
+    // old entry is evicted, re-connects of old incarnation are dropped
+    let to = to_b.clone();
+    let name = run(&mut sys, retry(move || to.send(Whoami).and_then(|name| {
+        if name == "b2" { Ok(name) } else { Err(RemoteError::NotSupported) }
+    }))).unwrap();
+    assert_eq!(name, "b2");
+    run(&mut sys, delay(1500)).unwrap();
+    let names = run(&mut sys, future::join_all(
+        (0..10).map(|_| to_b.send(Whoami)).collect::<Vec<_>>())).unwrap();
+    assert!(names.iter().all(|name| name == "b2"), "{:?}", names);
+}
+
+#[test]
+fn test_heartbeat() {
+    let mut sys = system();
+
+    let a = world("world-heartbeat-a", &[]).heartbeat(Duration::from_millis(100)).start();
+    let transport = Stalling::default();
+    let mut b = World::unbound("mem://world-heartbeat-b".to_owned())
+        .transport(transport.clone()).bind_addr().unwrap()
+        .add_node(Some("mem://world-heartbeat-a"));
+    let to_a = b.get_recipient::<Ping>();
+    let b = b.start();
+    run(&mut sys, echo(&a)).unwrap();
+    run(&mut sys, retry(move || to_a.send(Ping(1)))).unwrap();
+
+    let named: Addr<Syn, _> = Named("b").start();
+    let actor = run(&mut sys, World::register_actor_recipient(&b, named.recipient())).unwrap();
+    let state = Arc::new(Mutex::new(None));
+    let watcher: Addr<Syn, _> = Watcher(Arc::clone(&state)).start();
+    World::watch(&a, actor.id(), watcher.recipient());
+
+    // connection is kept while node answers pings
+    run(&mut sys, delay(500)).unwrap();
+    assert!(state.lock().unwrap().is_none());
+
+    // silent node is unreachable, even though its stream is open
+    transport.0.store(true, Ordering::SeqCst);
+    let s = Arc::clone(&state);
+    run(&mut sys, wait_for(move || s.lock().unwrap().is_some())).unwrap();
+    let terminated = state.lock().unwrap().take().unwrap();
+    assert_eq!(terminated.actor, actor.id());
+    assert_eq!(terminated.reason, TerminationReason::Unreachable);
+}